@@ -1,27 +1,111 @@
-use std::env;
+use std::{env, sync::Arc, time::Duration};
 
 use askama::Template;
 use axum::{
     http::StatusCode,
+    middleware,
     response::{Html, IntoResponse},
     routing::get,
-    Json, Router,
+    Extension, Json, Router,
 };
 use axum_extra::extract::CookieJar;
-use serde::Serialize;
-use tower_http::services::ServeDir;
+use serde::{Deserialize, Serialize};
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, RequestId, SetRequestIdLayer},
+    services::ServeDir,
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
+
+use crate::{
+    service_token::ServiceTokens,
+    telemetry::{request_span, trace_headers, REQUEST_ID_HEADER},
+};
+
+mod metrics;
+mod security_headers;
+mod service_token;
+mod telemetry;
 
 #[tokio::main]
 async fn main() {
+    let tracer_provider = telemetry::init_tracing();
+
+    let metrics = Arc::new(metrics::Metrics::default());
+    let security_headers = Arc::new(security_headers::SecurityHeaders::from_env(
+        &auth_service_url(),
+    ));
+    let service_tokens = Arc::new(service_token::ServiceTokens::from_env(
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap(),
+        &auth_service_internal_url(),
+    ));
+
     let app = Router::new()
         .nest_service("/assets", ServeDir::new("assets"))
         .route("/", get(root))
-        .route("/protected", get(protected));
+        .route("/protected", get(protected))
+        .route("/metrics", get(metrics::metrics))
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(health_ready))
+        .layer(middleware::from_fn_with_state(
+            metrics.clone(),
+            metrics::track_requests,
+        ))
+        .with_state(metrics)
+        .layer(Extension(service_tokens))
+        .layer(middleware::from_fn_with_state(
+            security_headers,
+            security_headers::set_security_headers,
+        ))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        // outermost, so everything further in sees the id
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
 
-    println!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    tracing::info!("listening on {}", listener.local_addr().unwrap());
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+
+    // export whatever spans are still batched up
+    if let Some(provider) = tracer_provider {
+        provider.shutdown().ok();
+    }
+}
+
+// SIGINT, i.e. Ctrl+C, or SIGTERM, which `docker stop` sends
+async fn shutdown_signal() {
+    let interrupt = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for SIGINT");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+    tracing::info!("shutting down");
 }
 
 #[derive(Template)]
@@ -31,13 +115,32 @@ struct IndexTemplate {
     logout_link: String,
 }
 
-async fn root() -> impl IntoResponse {
+// where browsers reach the auth service
+fn auth_service_url() -> String {
     let mut address = env::var("AUTH_SERVICE_IP").unwrap_or("localhost".to_owned());
     if address.is_empty() {
         address = "localhost".to_owned();
     }
-    let login_link = format!("http://{}:3000", address);
-    let logout_link = format!("http://{}:3000/logout", address);
+    format!("{}://{}:3000", auth_service_scheme(), address)
+}
+
+// where this service reaches the auth service
+fn auth_service_internal_url() -> String {
+    let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
+    format!("{}://{}:3000", auth_service_scheme(), auth_hostname)
+}
+
+// `https` once the auth service is given a certificate, which it then insists on
+fn auth_service_scheme() -> String {
+    match env::var("AUTH_SERVICE_SCHEME") {
+        Ok(scheme) if !scheme.is_empty() => scheme,
+        _ => "http".to_owned(),
+    }
+}
+
+async fn root() -> impl IntoResponse {
+    let login_link = auth_service_url();
+    let logout_link = format!("{}/logout", login_link);
 
     let template = IndexTemplate {
         login_link,
@@ -46,7 +149,11 @@ async fn root() -> impl IntoResponse {
     Html(template.render().unwrap())
 }
 
-async fn protected(jar: CookieJar) -> impl IntoResponse {
+async fn protected(
+    jar: CookieJar,
+    Extension(service_tokens): Extension<Arc<ServiceTokens>>,
+    request_id: Option<Extension<RequestId>>,
+) -> impl IntoResponse {
     let jwt_cookie = match jar.get("jwt") {
         Some(cookie) => cookie,
         None => {
@@ -60,29 +167,105 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
         "token": &jwt_cookie.value(),
     });
 
-    let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
-    let url = format!("http://{}:3000/verify-token", auth_hostname);
+    let url = format!("{}/verify-token", auth_service_internal_url());
+
+    // the auth service only checks tokens for services it knows
+    let service_token = match service_tokens.token().await {
+        Ok(token) => token,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to get a service token");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let mut request = api_client
+        .post(&url)
+        .bearer_auth(service_token)
+        .json(&verify_token_body)
+        .headers(trace_headers());
+    // so the auth service logs the check under the same id
+    if let Some(Extension(request_id)) = request_id {
+        request = request.header(REQUEST_ID_HEADER, request_id.header_value());
+    }
 
-    let response = match api_client.post(&url).json(&verify_token_body).send().await {
+    let response = match request.send().await {
         Ok(response) => response,
-        Err(_) => {
+        Err(e) => {
+            tracing::error!(error = %e, "failed to reach the auth service");
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
     };
 
+    // a challenge means it's our service token that was turned down, not the user's
+    if response
+        .headers()
+        .contains_key(reqwest::header::WWW_AUTHENTICATE)
+    {
+        tracing::error!(status = %response.status(), "the auth service rejected our service token");
+        service_tokens.discard().await;
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
     match response.status() {
         reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::BAD_REQUEST => {
             StatusCode::UNAUTHORIZED.into_response()
         }
-        reqwest::StatusCode::OK => Json(ProtectedRouteResponse {
-            img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
-        })
-        .into_response(),
+        reqwest::StatusCode::OK => {
+            // older auth services answer with an empty body
+            let claims = response.json::<VerifyTokenResponse>().await.ok();
+            Json(ProtectedRouteResponse {
+                img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
+                user: claims.map(|claims| claims.subject),
+            })
+            .into_response()
+        }
         _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
 
+async fn health_live() -> impl IntoResponse {
+    Json(serde_json::json!({ "status": "up" }))
+}
+
+// ready once the auth service answers, as nothing here works without it
+async fn health_ready() -> impl IntoResponse {
+    let url = format!("{}/health/live", auth_service_internal_url());
+
+    let api_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()
+        .unwrap();
+    let auth_service = match api_client.get(&url).send().await {
+        Ok(response) if response.status().is_success() => serde_json::json!({ "status": "up" }),
+        Ok(response) => {
+            serde_json::json!({ "status": "down", "error": response.status().to_string() })
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "auth service health check failed");
+            serde_json::json!({ "status": "down", "error": "Unreachable" })
+        }
+    };
+
+    let (status_code, status) = if auth_service["status"] == "up" {
+        (StatusCode::OK, "up")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "down")
+    };
+    let body = serde_json::json!({
+        "status": status,
+        "checks": { "authService": auth_service },
+    });
+
+    (status_code, Json(body))
+}
+
 #[derive(Serialize)]
 pub struct ProtectedRouteResponse {
     pub img_url: String,
+    pub user: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct VerifyTokenResponse {
+    subject: String,
 }
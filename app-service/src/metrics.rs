@@ -0,0 +1,104 @@
+use std::{sync::Arc, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus_client::{
+    encoding::{text::encode, EncodeLabelSet},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::Registry,
+};
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RequestLabels {
+    method: String,
+    route: String,
+    status: u16,
+}
+
+/// Request counters and latencies, served from `/metrics`.
+pub struct Metrics {
+    registry: Registry,
+    requests: Family<RequestLabels, Counter>,
+    request_duration: Family<RequestLabels, Histogram>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let requests = Family::default();
+        let request_duration = Family::<RequestLabels, Histogram>::new_with_constructor(|| {
+            // 5ms to 10s
+            Histogram::new(exponential_buckets(0.005, 2.0, 12))
+        });
+
+        let mut registry = Registry::with_prefix("app");
+        registry.register(
+            "http_requests",
+            "Requests handled, by route and status",
+            requests.clone(),
+        );
+        registry.register(
+            "http_request_duration_seconds",
+            "Time taken to handle requests, by route and status",
+            request_duration.clone(),
+        );
+
+        Self {
+            registry,
+            requests,
+            request_duration,
+        }
+    }
+}
+
+/// Times each request, counting it under its route.
+pub async fn track_requests(
+    State(metrics): State<Arc<Metrics>>,
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    // assets are served without a route, and every path asked for would be its own series
+    let route = matched_path
+        .as_ref()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    let labels = RequestLabels {
+        method,
+        route,
+        status: response.status().as_u16(),
+    };
+    metrics.requests.get_or_create(&labels).inc();
+    metrics
+        .request_duration
+        .get_or_create(&labels)
+        .observe(start.elapsed().as_secs_f64());
+    response
+}
+
+pub async fn metrics(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    let mut buffer = String::new();
+    if encode(&mut buffer, &metrics.registry).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, CONTENT_TYPE)],
+        buffer,
+    )
+        .into_response()
+}
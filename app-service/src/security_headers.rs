@@ -0,0 +1,95 @@
+use std::{env, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+const DEFAULT_STRICT_TRANSPORT_SECURITY: &str = "max-age=31536000; includeSubDomains";
+const DEFAULT_FRAME_OPTIONS: &str = "DENY";
+const DEFAULT_REFERRER_POLICY: &str = "strict-origin-when-cross-origin";
+
+// what `templates/index.html` and `assets/app.js` need: Bootstrap from jsDelivr, which also
+// inlines SVG icons as data URLs, inline `style` attributes, the protected image, and logging
+// out through the auth service
+fn default_content_security_policy(auth_origin: &str) -> String {
+    format!(
+        "default-src 'self'; \
+        script-src 'self' https://cdn.jsdelivr.net; \
+        style-src 'self' 'unsafe-inline' https://cdn.jsdelivr.net; \
+        img-src 'self' data: https://i.ibb.co; \
+        connect-src 'self' {auth_origin}; \
+        object-src 'none'; \
+        base-uri 'self'; \
+        form-action 'self'; \
+        frame-ancestors 'none'"
+    )
+}
+
+/// Headers set on every response, unless its handler set them already. Each can be
+/// overridden through the environment variable of the same name, or left out when it's empty.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl SecurityHeaders {
+    pub fn from_env(auth_origin: &str) -> Self {
+        let content_security_policy = default_content_security_policy(auth_origin);
+        let headers = [
+            (
+                header::STRICT_TRANSPORT_SECURITY,
+                "STRICT_TRANSPORT_SECURITY",
+                DEFAULT_STRICT_TRANSPORT_SECURITY,
+            ),
+            (
+                header::CONTENT_SECURITY_POLICY,
+                "CONTENT_SECURITY_POLICY",
+                content_security_policy.as_str(),
+            ),
+            (
+                header::X_FRAME_OPTIONS,
+                "X_FRAME_OPTIONS",
+                DEFAULT_FRAME_OPTIONS,
+            ),
+            (
+                header::REFERRER_POLICY,
+                "REFERRER_POLICY",
+                DEFAULT_REFERRER_POLICY,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, env_var, default)| {
+            let value = env::var(env_var).unwrap_or_else(|_| default.to_owned());
+            if value.is_empty() {
+                return None;
+            }
+            let value = HeaderValue::try_from(value)
+                .unwrap_or_else(|_| panic!("{env_var} must be a valid header value"));
+            Some((name, value))
+        })
+        .collect();
+
+        Self { headers }
+    }
+}
+
+pub async fn set_security_headers(
+    State(security_headers): State<Arc<SecurityHeaders>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+
+    let headers = response.headers_mut();
+    for (name, value) in &security_headers.headers {
+        headers.entry(name.clone()).or_insert_with(|| value.clone());
+    }
+    // not configurable, there's no reason to let browsers guess content types
+    headers
+        .entry(header::X_CONTENT_TYPE_OPTIONS)
+        .or_insert(HeaderValue::from_static("nosniff"));
+    response
+}
@@ -0,0 +1,88 @@
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+// a token this close to expiring is replaced, so it can't run out on the way to the auth service
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// Tokens for calling the auth service as a service client, from the client credentials grant.
+/// Each is reused until shortly before it expires.
+pub struct ServiceTokens {
+    http_client: reqwest::Client,
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    cached: Mutex<Option<CachedToken>>,
+}
+
+struct CachedToken {
+    access_token: String,
+    refresh_at: Instant,
+}
+
+impl ServiceTokens {
+    /// Reads the client's credentials from `AUTH_SERVICE_CLIENT_ID`, `app-service` by default,
+    /// and `AUTH_SERVICE_CLIENT_SECRET`.
+    pub fn from_env(http_client: reqwest::Client, auth_service_url: &str) -> Self {
+        let client_id = env::var("AUTH_SERVICE_CLIENT_ID")
+            .ok()
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| "app-service".to_owned());
+        let client_secret = env::var("AUTH_SERVICE_CLIENT_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .expect("AUTH_SERVICE_CLIENT_SECRET must be set");
+
+        Self {
+            http_client,
+            token_url: format!("{}/token", auth_service_url),
+            client_id,
+            client_secret,
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub async fn token(&self) -> Result<String, reqwest::Error> {
+        // held while fetching, so requests arriving meanwhile wait for the one token
+        let mut cached = self.cached.lock().await;
+        if let Some(token) = cached
+            .as_ref()
+            .filter(|token| token.refresh_at > Instant::now())
+        {
+            return Ok(token.access_token.clone());
+        }
+
+        let response: TokenResponse = self
+            .http_client
+            .post(&self.token_url)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&[("grant_type", "client_credentials")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let lifetime = Duration::from_secs(response.expires_in.max(0) as u64);
+        *cached = Some(CachedToken {
+            access_token: response.access_token.clone(),
+            refresh_at: Instant::now() + lifetime.saturating_sub(EXPIRY_MARGIN),
+        });
+        Ok(response.access_token)
+    }
+
+    /// Forgets the cached token, e.g. once the auth service has turned it down.
+    pub async fn discard(&self) {
+        *self.cached.lock().await = None;
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64,
+}
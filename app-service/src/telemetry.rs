@@ -0,0 +1,83 @@
+use std::env;
+
+use axum::{
+    extract::Request,
+    http::{HeaderMap, HeaderName},
+};
+use opentelemetry::{propagation::TextMapPropagator, trace::TracerProvider};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+use tracing::{info_span, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+const SERVICE_NAME: &str = "app-service";
+
+/// Logs to stdout, at the level `RUST_LOG` asks for or `info` by default. Spans are exported
+/// too when `OTEL_EXPORTER_OTLP_ENDPOINT` names an OTLP/HTTP collector, through the provider
+/// returned, which should be shut down before exiting.
+pub fn init_tracing() -> Option<SdkTracerProvider> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let tracer_provider = env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty())
+        .map(|endpoint| tracer_provider(&endpoint));
+    let otlp = tracer_provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(otlp)
+        .init();
+    tracer_provider
+}
+
+fn tracer_provider(endpoint: &str) -> SdkTracerProvider {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        // set here, the endpoint is taken as is rather than as the collector's base url
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()
+        .expect("failed to set up OTLP export");
+
+    SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+        .build()
+}
+
+/// The span everything done for a request is logged in, continuing the caller's trace when
+/// it sent a W3C `traceparent`.
+pub fn request_span(request: &Request) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+
+    let span = info_span!(
+        "request",
+        method = %request.method(),
+        path = request.uri().path(),
+        request_id,
+    );
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(request.headers()));
+    // only fails when spans aren't exported at all
+    let _ = span.set_parent(parent);
+    span
+}
+
+/// The `traceparent` for calls made on behalf of the current span, so they join its trace.
+pub fn trace_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    TraceContextPropagator::new().inject_context(
+        &Span::current().context(),
+        &mut HeaderInjector(&mut headers),
+    );
+    headers
+}
@@ -32,6 +32,16 @@ signupLoginLink.addEventListener("click", (e) => {
 
 // -----------------------------------------------------
 
+// logins started by /authorize carry on there once the cookie is set
+function continueAuthorization() {
+    const returnTo = new URLSearchParams(window.location.search).get("return_to");
+    if (returnTo !== null && returnTo.startsWith("/authorize?")) {
+        window.location.assign(returnTo);
+        return true;
+    }
+    return false;
+}
+
 const loginForm = document.getElementById("login-form");
 const loginButton = document.getElementById("login-form-submit");
 const loginErrAlter = document.getElementById("login-err-alert");
@@ -63,6 +73,9 @@ loginButton.addEventListener("click", (e) => {
             signupSection.style.display = "none";
             loginErrAlter.style.display = "none";
         } else if (response.status === 200) {
+            if (continueAuthorization()) {
+                return;
+            }
             loginForm.email.value = "";
             loginForm.password.value = "";
             loginErrAlter.style.display = "none";
@@ -81,6 +94,22 @@ loginButton.addEventListener("click", (e) => {
     });
 });
 
+const federatedLogin = document.getElementById("federated-login");
+
+fetch('/login/oidc').then(response => response.ok ? response.json() : []).then(providers => {
+    const returnTo = new URLSearchParams(window.location.search).get("return_to");
+    providers.forEach(provider => {
+        const link = document.createElement("a");
+        link.className = "btn btn-outline-dark d-block w-100 mb-2";
+        link.href = `/login/oidc/${encodeURIComponent(provider.id)}`;
+        if (returnTo !== null) {
+            link.href += `?return_to=${encodeURIComponent(returnTo)}`;
+        }
+        link.textContent = `Sign in with ${provider.name}`;
+        federatedLogin.appendChild(link);
+    });
+});
+
 const signupForm = document.getElementById("signup-form");
 const signupButton = document.getElementById("signup-form-submit");
 const signupErrAlter = document.getElementById("signup-err-alert");
@@ -111,6 +140,12 @@ signupButton.addEventListener("click", (e) => {
         } else {
             response.json().then(data => {
                 let error_msg = data.error;
+                // says what's wrong with each field, when it's the input's fault
+                if (Array.isArray(data.details)) {
+                    error_msg = data.details
+                        .map(detail => `${detail.field} ${detail.message ?? "is invalid"}`)
+                        .join(", ");
+                }
                 if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
                     signupErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
                     signupErrAlter.style.display = "block";
@@ -141,6 +176,9 @@ TwoFAButton.addEventListener("click", (e) => {
         body: JSON.stringify({ email, loginAttemptId, "2FACode": TwoFACode }),
     }).then(response => {
         if (response.ok) {
+            if (continueAuthorization()) {
+                return;
+            }
             TwoFAForm.email.value = "";
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
@@ -161,4 +199,14 @@ TwoFAButton.addEventListener("click", (e) => {
             });
         }
     });
-});
\ No newline at end of file
+});
+// magic links and identity providers land here when the login still needs its second factor
+const pendingLogin = new URLSearchParams(window.location.search);
+if (pendingLogin.has("loginAttemptId")) {
+    TwoFAForm.email.value = pendingLogin.get("email");
+    TwoFAForm.login_attempt_id.value = pendingLogin.get("loginAttemptId");
+
+    loginSection.style.display = "none";
+    twoFASection.style.display = "block";
+    signupSection.style.display = "none";
+}
@@ -1,34 +1,93 @@
 use std::sync::Arc;
 
-use crate::domain::{BannedTokenStore, UserStore};
-use crate::services::{HashMapUserStore, HashSetTokenStore};
+use crate::domain::{
+    AuditSink, BannedTokenStore, ClientStore, EmailClient, FederatedLoginStore, IdentityProvider,
+    LoginAttemptStore, PasskeyStore, SessionStore, UserStore,
+};
+use crate::services::{
+    HashMapClientStore, HashMapFederatedLoginStore, HashMapLoginAttemptStore, HashMapPasskeyStore,
+    HashMapSessionStore, HashMapUserStore, HashSetTokenStore, MeteredUserStore, MockEmailClient,
+    VecAuditSink,
+};
+use crate::utils::metrics::Metrics;
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: Arc<dyn UserStore + Send + Sync>,
     pub banned_token_store: Arc<dyn BannedTokenStore + Send + Sync>,
+    pub login_attempt_store: Arc<dyn LoginAttemptStore + Send + Sync>,
+    pub passkey_store: Arc<dyn PasskeyStore + Send + Sync>,
+    pub email_client: Arc<dyn EmailClient + Send + Sync>,
+    pub client_store: Arc<dyn ClientStore + Send + Sync>,
+    pub federated_login_store: Arc<dyn FederatedLoginStore + Send + Sync>,
+    pub session_store: Arc<dyn SessionStore + Send + Sync>,
+    pub audit_sink: Arc<dyn AuditSink + Send + Sync>,
+    pub identity_providers: Arc<Vec<IdentityProvider>>,
+    pub metrics: Arc<Metrics>,
+    // whether cookies are only sent back over HTTPS, set by `Application::build` when serving it
+    pub secure_cookies: bool,
 }
 
 impl AppState {
     // test impl
+    #[allow(clippy::too_many_arguments)]
     pub fn new_tester(
         user_store: Arc<HashMapUserStore>,
         banned_token_store: Arc<HashSetTokenStore>,
+        login_attempt_store: Arc<HashMapLoginAttemptStore>,
+        passkey_store: Arc<HashMapPasskeyStore>,
+        email_client: Arc<MockEmailClient>,
+        client_store: Arc<HashMapClientStore>,
+        federated_login_store: Arc<HashMapFederatedLoginStore>,
+        session_store: Arc<HashMapSessionStore>,
+        audit_sink: Arc<VecAuditSink>,
+        identity_providers: Vec<IdentityProvider>,
     ) -> Self {
+        let metrics = Arc::new(Metrics::default());
         Self {
-            user_store,
+            user_store: Arc::new(MeteredUserStore::new(user_store, metrics.clone())),
             banned_token_store,
+            login_attempt_store,
+            passkey_store,
+            email_client,
+            client_store,
+            federated_login_store,
+            session_store,
+            audit_sink,
+            identity_providers: Arc::new(identity_providers),
+            metrics,
+            secure_cookies: false,
         }
     }
 
     // generic impl (for prod)
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: impl UserStore + Send + Sync + 'static,
         banned_token_store: impl BannedTokenStore + Send + Sync + 'static,
+        login_attempt_store: impl LoginAttemptStore + Send + Sync + 'static,
+        passkey_store: impl PasskeyStore + Send + Sync + 'static,
+        email_client: impl EmailClient + Send + Sync + 'static,
+        client_store: impl ClientStore + Send + Sync + 'static,
+        federated_login_store: impl FederatedLoginStore + Send + Sync + 'static,
+        session_store: impl SessionStore + Send + Sync + 'static,
+        audit_sink: Arc<dyn AuditSink + Send + Sync>,
+        identity_providers: Vec<IdentityProvider>,
     ) -> Self {
+        let metrics = Arc::new(Metrics::default());
         Self {
-            user_store: Arc::new(user_store),
+            user_store: Arc::new(MeteredUserStore::new(Arc::new(user_store), metrics.clone())),
             banned_token_store: Arc::new(banned_token_store),
+            login_attempt_store: Arc::new(login_attempt_store),
+            passkey_store: Arc::new(passkey_store),
+            email_client: Arc::new(email_client),
+            client_store: Arc::new(client_store),
+            federated_login_store: Arc::new(federated_login_store),
+            session_store: Arc::new(session_store),
+            audit_sink,
+            identity_providers: Arc::new(identity_providers),
+            metrics,
+            secure_cookies: false,
         }
     }
 }
@@ -0,0 +1,726 @@
+//! Manages accounts in the state file the server loads at startup, see `services::StateFile`.
+//! A running server only sees the changes once restarted.
+
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use auth_service::{
+    domain::{
+        BannedTokenStore, Email, HashedPassword, LegacyPasswordHash, Password, Role, TwoFactorKind,
+        TwoFactorMethod, User, UserStore, UserStoreError,
+    },
+    services::{HashMapUserStore, HashSetTokenStore, StateFile, StateFileError},
+    utils::constants::{STATE_FILE, env},
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Parser)]
+#[command(name = "auth-admin", about = "Manage auth-service users")]
+struct Cli {
+    /// Defaults to `AUTH_STATE_FILE`
+    #[arg(long, global = true)]
+    state_file: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Creates a user, reading the password from stdin unless given
+    CreateUser {
+        email: String,
+        #[arg(long)]
+        password: Option<String>,
+        #[arg(long = "role")]
+        roles: Vec<Role>,
+        /// Requires a code sent by email at login
+        #[arg(long)]
+        email_2fa: bool,
+    },
+    /// Sets a new password, reading it from stdin unless given, and revokes the user's tokens
+    ResetPassword {
+        email: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Turns 2FA on with codes sent by email at login, leaving authenticator apps be, or off,
+    /// e.g. for a user who lost their authenticator
+    TwoFactor { email: String, state: Toggle },
+    /// Replaces the user's roles, revoking their tokens
+    SetRoles { email: String, roles: Vec<Role> },
+    /// Invalidates every token issued to the user so far
+    RevokeTokens { email: String },
+    /// Bans a single token
+    BanToken { token: String },
+    /// Writes all users, without their secrets, to stdout or a file
+    Export {
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Adds the users in a file written by `export`, skipping existing ones
+    Import {
+        path: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+    },
+    /// Adds users migrated from another system, one JSON object per line with their email
+    /// and bcrypt or PBKDF2 `passwordHash`, skipping existing ones. The hashes are replaced
+    /// by argon2id ones as the users log in.
+    ImportHashes { path: PathBuf },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Toggle {
+    On,
+    Off,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Error)]
+enum AdminError {
+    #[error(
+        "no state file given, pass --state-file or set {}",
+        env::STATE_FILE_ENV_VAR
+    )]
+    MissingStateFile,
+    #[error(transparent)]
+    StateFile(#[from] StateFileError),
+    #[error("user {0} not found")]
+    UserNotFound(String),
+    #[error("user {0} already exists")]
+    UserAlreadyExists(String),
+    #[error("invalid email {0}")]
+    InvalidEmail(String),
+    #[error("passwords need at least 8 characters")]
+    InvalidPassword,
+    #[error("{0}: authenticator apps can't be imported, the user has to enroll again")]
+    UnsupportedTwoFactor(String),
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Csv(#[from] csv::Error),
+    #[error("{0}")]
+    InvalidRow(String),
+    #[error("unexpected store error")]
+    UnexpectedError,
+}
+
+/// A user migrated from another system, a line of the file given to `import-hashes`.
+#[derive(Debug, Deserialize)]
+struct LegacyUserRecord {
+    email: String,
+    #[serde(rename = "passwordHash")]
+    password_hash: String,
+    #[serde(default)]
+    roles: Vec<Role>,
+}
+
+/// A user as exported and imported. Flat, so the same rows work for JSON and CSV.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct UserRow {
+    email: String,
+    // only read on import, never exported
+    #[serde(default, skip_serializing)]
+    password: Option<String>,
+    // space separated
+    #[serde(default)]
+    roles: String,
+    #[serde(default)]
+    locked: bool,
+    #[serde(rename = "twoFactorMethod", default)]
+    two_factor_method: Option<TwoFactorKind>,
+}
+
+impl From<&User> for UserRow {
+    fn from(user: &User) -> Self {
+        Self {
+            email: user.email.as_ref().to_string(),
+            password: None,
+            roles: user
+                .roles
+                .iter()
+                .map(Role::name)
+                .collect::<Vec<_>>()
+                .join(" "),
+            locked: user.locked,
+            two_factor_method: user.two_factor.as_ref().map(|method| method.kind()),
+        }
+    }
+}
+
+struct Stores {
+    users: HashMapUserStore,
+    banned_tokens: HashSetTokenStore,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run_with_state_file(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run_with_state_file(cli: Cli) -> Result<(), AdminError> {
+    let path = cli
+        .state_file
+        .or_else(|| STATE_FILE.clone())
+        .ok_or(AdminError::MissingStateFile)?;
+    // a running server has loaded the file already, and would never see the changes
+    let _lock = StateFile::lock(&path)?;
+
+    let stores = Stores {
+        users: HashMapUserStore::default(),
+        banned_tokens: HashSetTokenStore::default(),
+    };
+    StateFile::read(&path)?
+        .restore(&stores.users, &stores.banned_tokens)
+        .await?;
+
+    if run(cli.command, &stores).await? {
+        StateFile::snapshot(&stores.users, &stores.banned_tokens)
+            .await?
+            .write(&path)?;
+    }
+    Ok(())
+}
+
+/// Runs the command against the stores, returning whether anything changed.
+async fn run(command: Command, stores: &Stores) -> Result<bool, AdminError> {
+    match command {
+        Command::CreateUser {
+            email,
+            password,
+            roles,
+            email_2fa,
+        } => {
+            let password = parse_password(password)?;
+            let two_factor = email_2fa.then_some(TwoFactorMethod::Email);
+            let mut user = User::new(parse_email(&email)?, password, two_factor);
+            user.roles = roles;
+            add_user(stores, user).await?;
+            println!("created {email}");
+        }
+        Command::ResetPassword { email, password } => {
+            let password = parse_password(password)?;
+            update_user(stores, &email, |user| {
+                user.set_password(HashedPassword::new(&password));
+                user.revoke_tokens();
+                Ok(())
+            })
+            .await?;
+            println!("reset the password of {email}");
+        }
+        Command::TwoFactor { email, state } => {
+            update_user(stores, &email, |user| {
+                match state {
+                    // the authenticator's secret is the user's alone, so only email codes can be
+                    // set up here
+                    Toggle::On => {
+                        user.two_factor.get_or_insert(TwoFactorMethod::Email);
+                    }
+                    Toggle::Off => {
+                        user.two_factor = None;
+                        user.pending_totp = None;
+                        user.recovery_codes.clear();
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+            let state = match state {
+                Toggle::On => "on",
+                Toggle::Off => "off",
+            };
+            println!("turned 2FA {state} for {email}");
+        }
+        Command::SetRoles { email, roles } => {
+            update_user(stores, &email, |user| {
+                user.set_roles(roles);
+                Ok(())
+            })
+            .await?;
+            println!("set the roles of {email}");
+        }
+        Command::RevokeTokens { email } => {
+            update_user(stores, &email, |user| {
+                user.revoke_tokens();
+                Ok(())
+            })
+            .await?;
+            println!("revoked the tokens of {email}");
+        }
+        Command::BanToken { token } => {
+            stores
+                .banned_tokens
+                .add_token(token.trim())
+                .await
+                .map_err(|_| AdminError::InvalidRow("the token is empty".to_string()))?;
+            println!("banned the token");
+        }
+        Command::Export { format, output } => {
+            let users = stores
+                .users
+                .list_users()
+                .await
+                .map_err(|_| AdminError::UnexpectedError)?;
+            let rows: Vec<UserRow> = users.iter().map(UserRow::from).collect();
+            match output {
+                Some(path) => write_rows(&rows, format, fs::File::create(path)?)?,
+                None => write_rows(&rows, format, io::stdout().lock())?,
+            }
+            return Ok(false);
+        }
+        Command::Import { path, format } => {
+            let (imported, skipped) = import(stores, &path, format).await?;
+            println!("imported {imported} users, skipped {skipped}");
+            return Ok(imported > 0);
+        }
+        Command::ImportHashes { path } => {
+            let (imported, skipped) = import_hashes(stores, &path).await?;
+            println!("imported {imported} users, skipped {skipped}");
+            return Ok(imported > 0);
+        }
+    }
+    Ok(true)
+}
+
+fn parse_email(email: &str) -> Result<Email, AdminError> {
+    email
+        .parse()
+        .map_err(|_| AdminError::InvalidEmail(email.to_string()))
+}
+
+// read from stdin when not given, so it stays out of shell history
+fn parse_password(password: Option<String>) -> Result<Password, AdminError> {
+    let password = match password {
+        Some(password) => password,
+        None => {
+            eprint!("password: ");
+            io::stderr().flush()?;
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    password.parse().map_err(|_| AdminError::InvalidPassword)
+}
+
+async fn add_user(stores: &Stores, user: User) -> Result<(), AdminError> {
+    let email = user.email.as_ref().to_string();
+    stores.users.add_user(user).await.map_err(|e| match e {
+        UserStoreError::UserAlreadyExists => AdminError::UserAlreadyExists(email),
+        _ => AdminError::UnexpectedError,
+    })
+}
+
+async fn update_user(
+    stores: &Stores,
+    email: &str,
+    change: impl FnOnce(&mut User) -> Result<(), AdminError> + Send,
+) -> Result<(), AdminError> {
+    let mut outcome = Ok(());
+    stores
+        .users
+        .modify_user(
+            &parse_email(email)?,
+            Box::new(|user| {
+                // all of the change or none of it
+                let mut changed = user.clone();
+                outcome = change(&mut changed);
+                if outcome.is_ok() {
+                    *user = changed;
+                }
+            }),
+        )
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AdminError::UserNotFound(email.to_string()),
+            _ => AdminError::UnexpectedError,
+        })?;
+    outcome
+}
+
+fn write_rows(rows: &[UserRow], format: Format, mut writer: impl Write) -> Result<(), AdminError> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut writer, rows)?;
+            writeln!(writer)?;
+        }
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            for row in rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn read_rows(path: &Path, format: Format) -> Result<Vec<UserRow>, AdminError> {
+    let file = fs::File::open(path)?;
+    Ok(match format {
+        Format::Json => serde_json::from_reader(file)?,
+        Format::Csv => csv::Reader::from_reader(file)
+            .deserialize()
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Adds each row as a user, returning how many were imported and skipped.
+async fn import(
+    stores: &Stores,
+    path: &Path,
+    format: Format,
+) -> Result<(usize, usize), AdminError> {
+    let (mut imported, mut skipped) = (0, 0);
+    for row in read_rows(path, format)? {
+        match user_from_row(row) {
+            Ok(user) => match add_user(stores, user).await {
+                Ok(()) => imported += 1,
+                Err(e @ AdminError::UserAlreadyExists(_)) => {
+                    eprintln!("skipped: {e}");
+                    skipped += 1;
+                }
+                Err(e) => return Err(e),
+            },
+            Err(e) => {
+                eprintln!("skipped: {e}");
+                skipped += 1;
+            }
+        }
+    }
+    Ok((imported, skipped))
+}
+
+/// Adds a user for each line, returning how many were imported and skipped.
+async fn import_hashes(stores: &Stores, path: &Path) -> Result<(usize, usize), AdminError> {
+    let (mut imported, mut skipped) = (0, 0);
+    for (idx, line) in io::BufReader::new(fs::File::open(path)?)
+        .lines()
+        .enumerate()
+    {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match user_from_record(&line) {
+            Ok(user) => match add_user(stores, user).await {
+                Ok(()) => imported += 1,
+                Err(e @ AdminError::UserAlreadyExists(_)) => {
+                    eprintln!("skipped line {}: {e}", idx + 1);
+                    skipped += 1;
+                }
+                Err(e) => return Err(e),
+            },
+            Err(e) => {
+                eprintln!("skipped line {}: {e}", idx + 1);
+                skipped += 1;
+            }
+        }
+    }
+    Ok((imported, skipped))
+}
+
+fn user_from_record(line: &str) -> Result<User, AdminError> {
+    let record: LegacyUserRecord = serde_json::from_str(line)?;
+    let hash = LegacyPasswordHash::parse(&record.password_hash).ok_or_else(|| {
+        AdminError::InvalidRow(format!("{}: unsupported password hash", record.email))
+    })?;
+
+    let mut user = User::with_legacy_password(parse_email(&record.email)?, hash);
+    user.roles = record.roles;
+    Ok(user)
+}
+
+fn user_from_row(row: UserRow) -> Result<User, AdminError> {
+    let email = parse_email(&row.email)?;
+    let two_factor = match row.two_factor_method {
+        Some(TwoFactorKind::Email) => Some(TwoFactorMethod::Email),
+        Some(TwoFactorKind::Totp) => return Err(AdminError::UnsupportedTwoFactor(row.email)),
+        None => None,
+    };
+    let password = match row.password {
+        Some(password) => password
+            .parse()
+            .map_err(|_| AdminError::InvalidRow(format!("{}: invalid password", row.email)))?,
+        // exports carry no passwords, those users log in with a magic link or get reset
+        None => Password::unusable(),
+    };
+    let roles = row
+        .roles
+        .split_whitespace()
+        .map(|role| role.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|e| AdminError::InvalidRow(format!("{}: {e}", row.email)))?;
+
+    let mut user = User::new(email, password, two_factor);
+    user.roles = roles;
+    user.locked = row.locked;
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use auth_service::domain::TotpSecret;
+
+    use super::*;
+
+    fn stores() -> Stores {
+        Stores {
+            users: HashMapUserStore::default(),
+            banned_tokens: HashSetTokenStore::default(),
+        }
+    }
+
+    async fn create_user(stores: &Stores, email: &str) {
+        let command = Command::CreateUser {
+            email: email.to_string(),
+            password: Some("password123".to_string()),
+            roles: vec![Role::Admin],
+            email_2fa: false,
+        };
+        assert!(run(command, stores).await.unwrap());
+    }
+
+    async fn get_user(stores: &Stores, email: &str) -> User {
+        stores
+            .users
+            .get_user(&email.parse().unwrap())
+            .await
+            .unwrap()
+    }
+
+    fn temp_path(extension: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("auth-admin-{}.{extension}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_create_user() {
+        let stores = stores();
+        create_user(&stores, "a@b.com").await;
+
+        let user = get_user(&stores, "a@b.com").await;
+        assert!(user.password.verify(&"password123".parse().unwrap()));
+        assert_eq!(user.roles, vec![Role::Admin]);
+
+        let command = Command::CreateUser {
+            email: "a@b.com".to_string(),
+            password: Some("password123".to_string()),
+            roles: vec![],
+            email_2fa: false,
+        };
+        assert!(matches!(
+            run(command, &stores).await,
+            Err(AdminError::UserAlreadyExists(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reset_password_revokes_tokens() {
+        let stores = stores();
+        create_user(&stores, "a@b.com").await;
+
+        let command = Command::ResetPassword {
+            email: "a@b.com".to_string(),
+            password: Some("new password".to_string()),
+        };
+        run(command, &stores).await.unwrap();
+
+        let user = get_user(&stores, "a@b.com").await;
+        assert!(user.password.verify(&"new password".parse().unwrap()));
+        assert_eq!(user.token_version, 1);
+    }
+
+    #[tokio::test]
+    async fn test_toggle_two_factor() {
+        let stores = stores();
+        create_user(&stores, "a@b.com").await;
+        let toggle = |state| Command::TwoFactor {
+            email: "a@b.com".to_string(),
+            state,
+        };
+
+        run(toggle(Toggle::On), &stores).await.unwrap();
+        assert_eq!(
+            get_user(&stores, "a@b.com").await.two_factor,
+            Some(TwoFactorMethod::Email)
+        );
+
+        let totp = TwoFactorMethod::Totp {
+            secret: TotpSecret::generate(),
+            last_used_step: None,
+        };
+        update_user(&stores, "a@b.com", |user| {
+            user.two_factor = Some(totp.clone());
+            user.reset_recovery_codes();
+            Ok(())
+        })
+        .await
+        .unwrap();
+        run(toggle(Toggle::On), &stores).await.unwrap();
+        assert_eq!(get_user(&stores, "a@b.com").await.two_factor, Some(totp));
+
+        run(toggle(Toggle::Off), &stores).await.unwrap();
+        let user = get_user(&stores, "a@b.com").await;
+        assert!(!user.requires_2fa());
+        assert!(user.recovery_codes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_and_ban_tokens() {
+        let stores = stores();
+        create_user(&stores, "a@b.com").await;
+
+        let command = Command::RevokeTokens {
+            email: "a@b.com".to_string(),
+        };
+        run(command, &stores).await.unwrap();
+        let command = Command::BanToken {
+            token: "token".to_string(),
+        };
+        run(command, &stores).await.unwrap();
+
+        assert_eq!(get_user(&stores, "a@b.com").await.token_version, 1);
+        assert!(stores.banned_tokens.check_token("token").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_user() {
+        let command = Command::RevokeTokens {
+            email: "a@b.com".to_string(),
+        };
+
+        assert!(matches!(
+            run(command, &stores()).await,
+            Err(AdminError::UserNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import() {
+        for format in [Format::Json, Format::Csv] {
+            let stores = stores();
+            create_user(&stores, "a@b.com").await;
+            create_user(&stores, "c@d.com").await;
+            let path = temp_path("export");
+
+            let command = Command::Export {
+                format,
+                output: Some(path.clone()),
+            };
+            assert!(!run(command, &stores).await.unwrap());
+            let exported = fs::read_to_string(&path).unwrap();
+            assert!(!exported.contains("password123"));
+
+            let other = self::stores();
+            create_user(&other, "a@b.com").await;
+            let (imported, skipped) = import(&other, &path, format).await.unwrap();
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!((imported, skipped), (1, 1), "Failed for {format:?}");
+            let user = get_user(&other, "c@d.com").await;
+            assert_eq!(user.roles, vec![Role::Admin]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_with_passwords() {
+        let stores = stores();
+        let path = temp_path("csv");
+        fs::write(
+            &path,
+            "email,password,roles,locked,twoFactorMethod\n\
+            a@b.com,password123,support,false,email\n\
+            c@d.com,password123,,true,totp\n\
+            e@f.com,short,,false,\n",
+        )
+        .unwrap();
+
+        let (imported, skipped) = import(&stores, &path, Format::Csv).await.unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!((imported, skipped), (1, 2));
+        let user = get_user(&stores, "a@b.com").await;
+        assert!(user.password.verify(&"password123".parse().unwrap()));
+        assert_eq!(user.roles, vec![Role::Support]);
+        assert_eq!(user.two_factor, Some(TwoFactorMethod::Email));
+    }
+
+    #[tokio::test]
+    async fn test_import_hashes() {
+        let stores = stores();
+        create_user(&stores, "taken@b.com").await;
+        let path = temp_path("jsonl");
+        let hash = bcrypt::hash("password123", 4).unwrap();
+        let lines = [
+            format!(r#"{{"email":"a@b.com","passwordHash":"{hash}","roles":["support"]}}"#),
+            String::new(),
+            format!(r#"{{"email":"taken@b.com","passwordHash":"{hash}"}}"#),
+            r#"{"email":"c@d.com","passwordHash":"password123"}"#.to_string(),
+            "not json".to_string(),
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let (imported, skipped) = import_hashes(&stores, &path).await.unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!((imported, skipped), (1, 3));
+        let user = get_user(&stores, "a@b.com").await;
+        assert_eq!(user.roles, vec![Role::Support]);
+        assert!(
+            user.legacy_password
+                .unwrap()
+                .verify(&"password123".parse().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_saved_to_state_file() {
+        let path = temp_path("json");
+        let cli = |command| Cli {
+            state_file: Some(path.clone()),
+            command,
+        };
+
+        run_with_state_file(cli(Command::CreateUser {
+            email: "a@b.com".to_string(),
+            password: Some("password123".to_string()),
+            roles: vec![],
+            email_2fa: false,
+        }))
+        .await
+        .unwrap();
+        run_with_state_file(cli(Command::RevokeTokens {
+            email: "a@b.com".to_string(),
+        }))
+        .await
+        .unwrap();
+
+        let state = StateFile::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(state.users.len(), 1);
+        assert_eq!(state.users[0].token_version, 1);
+    }
+}
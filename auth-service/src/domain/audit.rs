@@ -0,0 +1,251 @@
+use async_trait::async_trait;
+use axum::http::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::Email;
+
+/// Something that happened to an account, kept for security reviews and the user's own export.
+///
+/// Only ever holds what's safe to show the user it's about: no passwords, tokens or codes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    // unix seconds
+    pub timestamp: i64,
+    pub kind: AuditEventKind,
+    // unset when the request never said who it was for, e.g. a malformed login
+    pub email: Option<String>,
+    // who did it, when not the user themselves
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(rename = "requestId")]
+    pub request_id: Option<String>,
+    pub ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    pub outcome: AuditOutcome,
+}
+
+impl AuditEvent {
+    /// Drops what ties the event to a person, keeping what happened and when.
+    pub fn anonymize(&mut self) {
+        self.email = None;
+        self.ip = None;
+        self.user_agent = None;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventKind {
+    Signup,
+    Login,
+    // a password login still waiting on its second factor
+    LoginPendingSecondFactor,
+    SecondFactor,
+    MagicLinkRequested,
+    MagicLinkLogin,
+    FederatedLoginStarted,
+    FederatedLogin,
+    PasskeyRegistrationStarted,
+    PasskeyRegistered,
+    PasskeyLoginStarted,
+    PasskeyLogin,
+    Logout,
+    TokenVerified,
+    TokenRejected,
+    TotpEnrollmentStarted,
+    TotpEnrolled,
+    RecoveryCodesRegenerated,
+    SessionsListed,
+    SessionEnded,
+    AllSessionsEnded,
+    AccountDeleted,
+    AccountExported,
+    UsersListed,
+    UserLocked,
+    UserUnlocked,
+    UserDeleted,
+    ClientRegistered,
+    Authorization,
+    ConsentGranted,
+    ConsentDenied,
+    TokenIssued,
+    TokenIntrospected,
+    TokenRevoked,
+    UserInfo,
+}
+
+impl AuditEventKind {
+    /// The event a request to the route records unless its handler says otherwise. Routes
+    /// that don't touch any account, like discovery documents and assets, record nothing.
+    pub fn for_route(method: &Method, path: &str) -> Option<Self> {
+        let kind = match (method.as_str(), path) {
+            ("POST", "/signup") => Self::Signup,
+            ("POST", "/login") => Self::Login,
+            ("POST", "/login/magic-link") => Self::MagicLinkRequested,
+            ("GET", "/login/magic-link/callback") => Self::MagicLinkLogin,
+            ("GET", "/login/oidc/{provider}") => Self::FederatedLoginStarted,
+            ("GET", "/login/oidc/{provider}/callback") => Self::FederatedLogin,
+            ("POST", "/logout") => Self::Logout,
+            ("POST", "/verify-2fa") => Self::SecondFactor,
+            ("POST", "/2fa/totp") => Self::TotpEnrollmentStarted,
+            ("POST", "/2fa/totp/confirm") => Self::TotpEnrolled,
+            ("POST", "/2fa/recovery-codes") => Self::RecoveryCodesRegenerated,
+            ("POST", "/verify-token") => Self::TokenVerified,
+            ("POST", "/passkeys/register/start") => Self::PasskeyRegistrationStarted,
+            ("POST", "/passkeys/register/finish") => Self::PasskeyRegistered,
+            ("POST", "/passkeys/login/start") => Self::PasskeyLoginStarted,
+            ("POST", "/passkeys/login/finish") => Self::PasskeyLogin,
+            ("GET", "/sessions") => Self::SessionsListed,
+            ("DELETE", "/sessions") => Self::AllSessionsEnded,
+            ("DELETE", "/sessions/{id}") => Self::SessionEnded,
+            ("DELETE", "/account") => Self::AccountDeleted,
+            ("GET", "/account/export") => Self::AccountExported,
+            ("GET", "/admin/users") => Self::UsersListed,
+            ("POST", "/admin/users/{email}/lock") => Self::UserLocked,
+            ("POST", "/admin/users/{email}/unlock") => Self::UserUnlocked,
+            ("DELETE", "/admin/users/{email}") => Self::UserDeleted,
+            ("POST", "/clients") => Self::ClientRegistered,
+            ("GET", "/authorize") => Self::Authorization,
+            ("POST", "/authorize") => Self::ConsentGranted,
+            ("POST", "/token") => Self::TokenIssued,
+            ("POST", "/introspect") => Self::TokenIntrospected,
+            ("POST", "/revoke") => Self::TokenRevoked,
+            ("GET" | "POST", "/userinfo") => Self::UserInfo,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure {
+        // the error the client was given, which never carries secrets either
+        reason: String,
+    },
+}
+
+/// The error message behind a failed response, left in its extensions for the audit log.
+#[derive(Clone, Debug)]
+pub struct FailureReason(pub String);
+
+#[async_trait]
+pub trait AuditSink {
+    async fn record(&self, event: AuditEvent) -> Result<(), AuditSinkError>;
+    // oldest first, for the user's account export
+    async fn events_for(&self, email: &Email) -> Result<Vec<AuditEvent>, AuditSinkError>;
+    // anonymizes the user's events once their account is deleted, so whoever signs up with the
+    // address next doesn't get them in their export
+    async fn forget(&self, email: &Email) -> Result<(), AuditSinkError>;
+    // makes sure everything recorded so far is kept, before shutting down
+    async fn flush(&self) -> Result<(), AuditSinkError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuditSinkError {
+    UnexpectedError,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_serialization() {
+        let event = AuditEvent {
+            timestamp: 1_700_000_000,
+            kind: AuditEventKind::LoginPendingSecondFactor,
+            email: Some("a@b.com".to_string()),
+            actor: None,
+            request_id: Some("id".to_string()),
+            ip: None,
+            user_agent: None,
+            outcome: AuditOutcome::Failure {
+                reason: "Account is locked!".to_string(),
+            },
+        };
+
+        let json = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(json["kind"], "login_pending_second_factor");
+        assert_eq!(json["outcome"]["result"], "failure");
+        assert_eq!(json["outcome"]["reason"], "Account is locked!");
+        assert_eq!(serde_json::from_value::<AuditEvent>(json).unwrap(), event);
+    }
+
+    #[test]
+    fn test_for_route() {
+        assert_eq!(
+            AuditEventKind::for_route(&Method::POST, "/login"),
+            Some(AuditEventKind::Login)
+        );
+        assert_eq!(
+            AuditEventKind::for_route(&Method::DELETE, "/sessions/{id}"),
+            Some(AuditEventKind::SessionEnded)
+        );
+        assert_eq!(AuditEventKind::for_route(&Method::GET, "/login"), None);
+        assert_eq!(
+            AuditEventKind::for_route(&Method::GET, "/.well-known/openid-configuration"),
+            None
+        );
+    }
+
+    // routes that don't touch any account
+    const UNAUDITED_ROUTES: &[(&str, &str)] = &[
+        ("GET", "/login/oidc"),
+        ("GET", "/.well-known/openid-configuration"),
+        ("GET", "/jwks.json"),
+        ("GET", "/metrics"),
+        ("GET", "/health/live"),
+        ("GET", "/health/ready"),
+    ];
+
+    // each method and path the router serves, read off its source so a new route can't be
+    // missed here
+    fn router_routes() -> Vec<(Method, String)> {
+        let mut routes = Vec::new();
+        for route in include_str!("../lib.rs").split(".route(\"").skip(1) {
+            let (path, rest) = route.split_once('"').unwrap();
+            let mut depth = 1;
+            let end = rest
+                .find(|c| {
+                    depth += match c {
+                        '(' => 1,
+                        ')' => -1,
+                        _ => 0,
+                    };
+                    depth == 0
+                })
+                .unwrap();
+            let handlers = &rest[..end];
+            for method in [Method::GET, Method::POST, Method::DELETE] {
+                let call = format!("{}(", method.as_str().to_lowercase());
+                let called = handlers.match_indices(&call).any(|(idx, _)| {
+                    !handlers[..idx].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+                });
+                if called {
+                    routes.push((method, path.to_string()));
+                }
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn test_every_route_has_a_kind() {
+        let routes = router_routes();
+        assert!(routes.contains(&(Method::POST, "/authorize".to_string())));
+
+        for (method, path) in routes {
+            let unaudited = UNAUDITED_ROUTES.contains(&(method.as_str(), path.as_str()));
+            assert_eq!(
+                AuditEventKind::for_route(&method, &path).is_none(),
+                unaudited,
+                "{method} {path}"
+            );
+        }
+    }
+}
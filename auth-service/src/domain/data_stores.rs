@@ -1,19 +1,157 @@
 use async_trait::async_trait;
 
-use crate::domain::{Email, Password, User};
+use crate::domain::{
+    AuthorizationCode, ClientId, Email, EmailCode, FederationState, LoginAttemptId, OAuthClient,
+    PasskeyChallenge, PasskeyCredential, Password, PendingAuthorization, PendingCeremony,
+    PendingFederatedLogin, ServiceClient, Session, SessionId, User,
+};
+
+// a change to make to a user, see `UserStore::modify_user`
+pub type UserChange<'a> = Box<dyn FnOnce(&mut User) + Send + 'a>;
 
 #[async_trait]
 pub trait UserStore {
     async fn add_user(&self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
+    // applies `change` with no other write to the user in between, so nothing another request
+    // changed meanwhile is lost, returning the user as changed. `change` mustn't call the store
+    async fn modify_user(
+        &self,
+        email: &Email,
+        change: UserChange<'_>,
+    ) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password)
     -> Result<(), UserStoreError>;
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError>;
+    // ordered by email, for admins
+    async fn list_users(&self) -> Result<Vec<User>, UserStoreError>;
+    async fn search_users(&self, query: &str) -> Result<Vec<User>, UserStoreError>;
+    async fn set_locked(&self, email: &Email, locked: bool) -> Result<(), UserStoreError>;
+    // checks an authenticator code and marks its step used in one go, so two requests can't
+    // both get in with the same code
+    async fn use_totp_code(
+        &self,
+        email: &Email,
+        code: &str,
+        unix_time: u64,
+    ) -> Result<(), UserStoreError>;
+    // removes a matching recovery code in the same step as finding it, for the same reason
+    async fn use_recovery_code(&self, email: &Email, code: &str) -> Result<(), UserStoreError>;
+    // whether the store can serve requests right now, for readiness probes
+    async fn health_check(&self) -> Result<(), UserStoreError>;
 }
 
 #[async_trait]
 pub trait BannedTokenStore {
     async fn add_token(&self, token: &str) -> Result<(), TokenStoreError>;
     async fn check_token(&self, token: &str) -> Result<bool, TokenStoreError>;
+    async fn list_tokens(&self) -> Result<Vec<String>, TokenStoreError>;
+    async fn health_check(&self) -> Result<(), TokenStoreError>;
+}
+
+// logins waiting on a second factor
+#[async_trait]
+pub trait LoginAttemptStore {
+    // `email_code` is the code sent to users with email 2FA
+    async fn add_attempt(
+        &self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        email_code: Option<EmailCode>,
+        expires_at: i64,
+    ) -> Result<(), LoginAttemptStoreError>;
+    async fn get_attempt(&self, email: &Email) -> Result<LoginAttemptId, LoginAttemptStoreError>;
+    // counts a guess at the second factor against the attempt, which is gone once it's expired
+    // or had `MAX_SECOND_FACTOR_GUESSES`, returning the code emailed for it if any
+    async fn check_attempt(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        now: i64,
+    ) -> Result<Option<EmailCode>, LoginAttemptStoreError>;
+    async fn remove_attempt(&self, email: &Email) -> Result<(), LoginAttemptStoreError>;
+}
+
+#[async_trait]
+pub trait PasskeyStore {
+    async fn add_credential(
+        &self,
+        email: Email,
+        credential: PasskeyCredential,
+    ) -> Result<(), PasskeyStoreError>;
+    async fn get_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PasskeyCredential>, PasskeyStoreError>;
+    async fn update_sign_count(
+        &self,
+        email: &Email,
+        credential_id: &[u8],
+        sign_count: u32,
+    ) -> Result<(), PasskeyStoreError>;
+    async fn delete_credentials(&self, email: &Email) -> Result<(), PasskeyStoreError>;
+    async fn add_challenge(
+        &self,
+        challenge: PasskeyChallenge,
+        pending: PendingCeremony,
+    ) -> Result<(), PasskeyStoreError>;
+    // challenges are single use, so looking one up also removes it
+    async fn take_challenge(
+        &self,
+        challenge: &PasskeyChallenge,
+    ) -> Result<PendingCeremony, PasskeyStoreError>;
+    // drops challenges whose ceremony was abandoned, returning how many
+    async fn remove_expired_challenges(&self, now: i64) -> Result<usize, PasskeyStoreError>;
+}
+
+// registered OAuth clients, the authorization codes issued to them, and service clients
+#[async_trait]
+pub trait ClientStore {
+    async fn add_client(&self, client: OAuthClient) -> Result<(), ClientStoreError>;
+    async fn get_client(&self, id: &ClientId) -> Result<OAuthClient, ClientStoreError>;
+    async fn add_code(
+        &self,
+        code: AuthorizationCode,
+        pending: PendingAuthorization,
+    ) -> Result<(), ClientStoreError>;
+    // codes are single use, so looking one up also removes it
+    async fn take_code(
+        &self,
+        code: &AuthorizationCode,
+    ) -> Result<PendingAuthorization, ClientStoreError>;
+    async fn add_service_client(&self, client: ServiceClient) -> Result<(), ClientStoreError>;
+    async fn get_service_client(&self, id: &str) -> Result<ServiceClient, ClientStoreError>;
+    // drops codes that were never exchanged, returning how many
+    async fn remove_expired_codes(&self, now: i64) -> Result<usize, ClientStoreError>;
+}
+
+// logins waiting on an identity provider's callback
+#[async_trait]
+pub trait FederatedLoginStore {
+    async fn add_login(
+        &self,
+        state: FederationState,
+        pending: PendingFederatedLogin,
+    ) -> Result<(), FederatedLoginStoreError>;
+    // a state is single use, so looking one up also removes it
+    async fn take_login(
+        &self,
+        state: &FederationState,
+    ) -> Result<PendingFederatedLogin, FederatedLoginStoreError>;
+    // drops logins the user never came back from, returning how many
+    async fn remove_expired_logins(&self, now: i64) -> Result<usize, FederatedLoginStoreError>;
+}
+
+// a session per auth token issued
+#[async_trait]
+pub trait SessionStore {
+    async fn add_session(&self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    async fn touch_session(&self, id: &SessionId, now: i64) -> Result<(), SessionStoreError>;
+    async fn remove_session(&self, id: &SessionId) -> Result<Session, SessionStoreError>;
+    async fn remove_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    // drops sessions whose token has expired, returning how many
+    async fn remove_expired_sessions(&self, now: i64) -> Result<usize, SessionStoreError>;
 }
 
 #[derive(Debug, PartialEq)]
@@ -29,3 +167,37 @@ pub enum TokenStoreError {
     MissingToken,
     UnexpectedError,
 }
+
+#[derive(Debug, PartialEq)]
+pub enum LoginAttemptStoreError {
+    LoginAttemptNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PasskeyStoreError {
+    CredentialAlreadyExists,
+    CredentialNotFound,
+    ChallengeNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ClientStoreError {
+    ClientAlreadyExists,
+    ClientNotFound,
+    CodeNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FederatedLoginStoreError {
+    LoginNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionStoreError {
+    SessionNotFound,
+    UnexpectedError,
+}
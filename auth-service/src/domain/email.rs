@@ -14,9 +14,7 @@ impl FromStr for Email {
     fn from_str(email: &str) -> Result<Self, AuthAPIError> {
         let parsed = Email(email.to_string());
 
-        parsed
-            .validate()
-            .map_err(|_| AuthAPIError::InvalidCredentials)?;
+        parsed.validate()?;
         Ok(parsed)
     }
 }
@@ -27,7 +25,11 @@ impl Validate for Email {
             .then_some(())
             .ok_or_else(|| {
                 let mut errs = ValidationErrors::new();
-                errs.add("email", ValidationError::new("invalid email"));
+                errs.add(
+                    "email",
+                    ValidationError::new("email")
+                        .with_message("must be a valid email address".into()),
+                );
                 errs
             })
     }
@@ -0,0 +1,20 @@
+use async_trait::async_trait;
+
+use crate::domain::Email;
+
+#[async_trait]
+pub trait EmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<(), EmailClientError>;
+    // whether emails can be sent right now, for readiness probes
+    async fn health_check(&self) -> Result<(), EmailClientError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EmailClientError {
+    UnexpectedError,
+}
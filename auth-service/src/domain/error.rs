@@ -1,15 +1,20 @@
 use axum::{
-    Json,
-    http::StatusCode,
+    Extension, Json,
+    http::{StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use validator::ValidationErrors;
 
 use crate::{
     ErrorResponse,
-    domain::{TokenStoreError, UserStoreError},
-    utils::auth::GenerateTokenError,
+    domain::{
+        AuditSinkError, ClientStoreError, EmailClientError, FailureReason,
+        FederatedLoginStoreError, LoginAttemptStoreError, PasskeyStoreError, SessionStoreError,
+        TokenStoreError, UserStoreError,
+    },
+    utils::{auth::GenerateTokenError, federation::FederationError, webauthn::WebAuthnError},
 };
 
 #[derive(Error, Debug, Serialize, Deserialize)]
@@ -18,6 +23,9 @@ pub enum AuthAPIError {
     UserAlreadyExists,
     #[error("Invalid credentials!")]
     InvalidCredentials,
+    // only where saying which field is wrong gives nothing away, unlike logging in
+    #[error("Validation failed!")]
+    ValidationFailed(Vec<FieldError>),
     #[error("Unexpected error!")]
     UnexpectedError,
     #[error("Authentication failed!")]
@@ -26,20 +34,106 @@ pub enum AuthAPIError {
     MissingToken,
     #[error("JWT is not valid!")]
     InvalidToken,
+    #[error("No pending 2FA enrollment!")]
+    MissingEnrollment,
+    #[error("2FA is not enabled!")]
+    TwoFactorNotEnabled,
+    #[error("Unknown identity provider!")]
+    UnknownProvider,
+    #[error("Session not found!")]
+    SessionNotFound,
+    #[error("User not found!")]
+    UserNotFound,
+    #[error("Account is locked!")]
+    AccountLocked,
+    #[error("Insufficient permissions!")]
+    Forbidden,
+}
+
+impl AuthAPIError {
+    /// What clients match on, as the messages may be reworded.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UserAlreadyExists => "user_already_exists",
+            Self::InvalidCredentials => "invalid_credentials",
+            Self::ValidationFailed(_) => "validation_failed",
+            Self::UnexpectedError => "unexpected_error",
+            Self::AuthenticationError => "authentication_failed",
+            Self::MissingToken => "missing_token",
+            Self::InvalidToken => "invalid_token",
+            Self::MissingEnrollment => "missing_enrollment",
+            Self::TwoFactorNotEnabled => "two_factor_not_enabled",
+            Self::UnknownProvider => "unknown_provider",
+            Self::SessionNotFound => "session_not_found",
+            Self::UserNotFound => "user_not_found",
+            Self::AccountLocked => "account_locked",
+            Self::Forbidden => "forbidden",
+        }
+    }
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         let status = match self {
             Self::UserAlreadyExists => StatusCode::CONFLICT,
-            Self::InvalidCredentials | Self::MissingToken => StatusCode::BAD_REQUEST,
+            Self::InvalidCredentials
+            | Self::ValidationFailed(_)
+            | Self::MissingToken
+            | Self::MissingEnrollment
+            | Self::TwoFactorNotEnabled => StatusCode::BAD_REQUEST,
             Self::AuthenticationError | Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::AccountLocked | Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::UnknownProvider | Self::SessionNotFound | Self::UserNotFound => {
+                StatusCode::NOT_FOUND
+            }
             Self::UnexpectedError => StatusCode::INTERNAL_SERVER_ERROR,
         };
-        let body = Json(ErrorResponse {
+        let body = ErrorResponse {
             error: self.to_string(),
-        });
-        (status, body).into_response()
+            code: self.code().to_string(),
+            details: match &self {
+                Self::ValidationFailed(fields) => Some(fields.clone()),
+                _ => None,
+            },
+        };
+        // the audit log records why the request failed
+        let reason = Extension(FailureReason(self.to_string()));
+        // left for `negotiate_problem_details`, in case the client wants problem details
+        (status, reason, Extension(body.clone()), Json(body)).into_response()
+    }
+}
+
+/// Why one field of a request was rejected.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl FieldError {
+    /// One per failed check, ordered by field.
+    pub fn from_validation_errors(errors: &ValidationErrors) -> Vec<Self> {
+        let mut field_errors: Vec<Self> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| Self {
+                    field: field.to_string(),
+                    code: error.code.to_string(),
+                    message: error.message.as_ref().map(|message| message.to_string()),
+                })
+            })
+            .collect();
+        field_errors.sort_by(|a, b| a.field.cmp(&b.field));
+        field_errors
+    }
+}
+
+impl From<ValidationErrors> for AuthAPIError {
+    fn from(value: ValidationErrors) -> Self {
+        Self::ValidationFailed(FieldError::from_validation_errors(&value))
     }
 }
 
@@ -61,6 +155,8 @@ impl From<GenerateTokenError> for AuthAPIError {
             GenerateTokenError::TokenError(_) => Self::InvalidToken,
             GenerateTokenError::UnexpectedError => Self::UnexpectedError,
             GenerateTokenError::BannedToken => Self::InvalidToken,
+            GenerateTokenError::NonceMismatch => Self::InvalidToken,
+            GenerateTokenError::RevokedToken => Self::InvalidToken,
         }
     }
 }
@@ -73,3 +169,177 @@ impl From<TokenStoreError> for AuthAPIError {
         }
     }
 }
+
+impl From<LoginAttemptStoreError> for AuthAPIError {
+    fn from(value: LoginAttemptStoreError) -> Self {
+        match value {
+            LoginAttemptStoreError::LoginAttemptNotFound => Self::AuthenticationError,
+            LoginAttemptStoreError::UnexpectedError => Self::UnexpectedError,
+        }
+    }
+}
+
+impl From<PasskeyStoreError> for AuthAPIError {
+    fn from(value: PasskeyStoreError) -> Self {
+        match value {
+            PasskeyStoreError::CredentialAlreadyExists
+            | PasskeyStoreError::CredentialNotFound
+            | PasskeyStoreError::ChallengeNotFound => Self::AuthenticationError,
+            PasskeyStoreError::UnexpectedError => Self::UnexpectedError,
+        }
+    }
+}
+
+impl From<WebAuthnError> for AuthAPIError {
+    fn from(value: WebAuthnError) -> Self {
+        match value {
+            WebAuthnError::MalformedClientData | WebAuthnError::MalformedAuthenticatorData => {
+                Self::InvalidCredentials
+            }
+            _ => Self::AuthenticationError,
+        }
+    }
+}
+
+impl From<EmailClientError> for AuthAPIError {
+    fn from(value: EmailClientError) -> Self {
+        match value {
+            EmailClientError::UnexpectedError => Self::UnexpectedError,
+        }
+    }
+}
+
+impl From<FederatedLoginStoreError> for AuthAPIError {
+    fn from(value: FederatedLoginStoreError) -> Self {
+        match value {
+            FederatedLoginStoreError::LoginNotFound => Self::AuthenticationError,
+            FederatedLoginStoreError::UnexpectedError => Self::UnexpectedError,
+        }
+    }
+}
+
+impl From<SessionStoreError> for AuthAPIError {
+    fn from(value: SessionStoreError) -> Self {
+        match value {
+            SessionStoreError::SessionNotFound => Self::SessionNotFound,
+            SessionStoreError::UnexpectedError => Self::UnexpectedError,
+        }
+    }
+}
+
+impl From<AuditSinkError> for AuthAPIError {
+    fn from(value: AuditSinkError) -> Self {
+        match value {
+            AuditSinkError::UnexpectedError => Self::UnexpectedError,
+        }
+    }
+}
+
+impl From<FederationError> for AuthAPIError {
+    fn from(value: FederationError) -> Self {
+        match value {
+            FederationError::ProviderUnavailable(_) | FederationError::Misconfigured => {
+                Self::UnexpectedError
+            }
+            FederationError::InvalidIdToken | FederationError::EmailNotVerified => {
+                Self::AuthenticationError
+            }
+        }
+    }
+}
+
+/// Errors from the OAuth endpoints, which report spec-defined codes (RFC 6749 §5.2) instead.
+#[derive(Error, Debug, PartialEq)]
+pub enum OAuthError {
+    #[error("invalid_request")]
+    InvalidRequest,
+    #[error("invalid_client")]
+    InvalidClient,
+    #[error("invalid_grant")]
+    InvalidGrant,
+    #[error("unauthorized_client")]
+    UnauthorizedClient,
+    #[error("invalid_scope")]
+    InvalidScope,
+    #[error("invalid_token")]
+    InvalidToken,
+    #[error("insufficient_scope")]
+    InsufficientScope,
+    #[error("invalid_redirect_uri")]
+    InvalidRedirectUri,
+    #[error("invalid_client_metadata")]
+    InvalidClientMetadata,
+    #[error("unsupported_grant_type")]
+    UnsupportedGrantType,
+    #[error("unsupported_response_type")]
+    UnsupportedResponseType,
+    #[error("access_denied")]
+    AccessDenied,
+    #[error("server_error")]
+    ServerError,
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::InvalidClient | Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::InsufficientScope => StatusCode::FORBIDDEN,
+            Self::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        // the spec's codes are stable already, and its format isn't ours to change
+        let body = Json(ErrorResponse {
+            error: self.to_string(),
+            code: self.to_string(),
+            details: None,
+        });
+
+        // bearer token failures also have to say so in the challenge (RFC 6750 §3), as do
+        // failed client logins (RFC 6749 §5.2)
+        let reason = Extension(FailureReason(self.to_string()));
+        let challenge = match self {
+            Self::InvalidToken => r#"Bearer error="invalid_token""#,
+            Self::InsufficientScope => r#"Bearer error="insufficient_scope""#,
+            Self::InvalidClient => "Basic",
+            _ => return (status, reason, body).into_response(),
+        };
+        (
+            status,
+            [(header::WWW_AUTHENTICATE, challenge)],
+            reason,
+            body,
+        )
+            .into_response()
+    }
+}
+
+impl From<ClientStoreError> for OAuthError {
+    fn from(value: ClientStoreError) -> Self {
+        match value {
+            ClientStoreError::ClientNotFound => Self::InvalidClient,
+            ClientStoreError::CodeNotFound => Self::InvalidGrant,
+            ClientStoreError::ClientAlreadyExists | ClientStoreError::UnexpectedError => {
+                Self::ServerError
+            }
+        }
+    }
+}
+
+impl From<GenerateTokenError> for OAuthError {
+    fn from(value: GenerateTokenError) -> Self {
+        match value {
+            GenerateTokenError::UnexpectedError => Self::ServerError,
+            _ => Self::InvalidToken,
+        }
+    }
+}
+
+impl From<UserStoreError> for OAuthError {
+    fn from(value: UserStoreError) -> Self {
+        match value {
+            // the account went away after the code was issued
+            UserStoreError::UserNotFound => Self::InvalidGrant,
+            _ => Self::ServerError,
+        }
+    }
+}
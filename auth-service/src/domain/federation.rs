@@ -0,0 +1,75 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+// 10 min to get through the identity provider's login
+pub const FEDERATED_LOGIN_TTL_SECONDS: i64 = 600;
+const FEDERATION_STATE_BYTES: usize = 32;
+
+/// An external OpenID Connect provider users can sign in with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdentityProvider {
+    // used in urls, e.g. `/login/oidc/google`
+    pub id: String,
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// A login with an identity provider, tied to a user by provider and subject.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalIdentity {
+    pub provider: String,
+    pub subject: String,
+}
+
+/// The `state` sent to the identity provider and echoed back to the callback.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct FederationState(String);
+
+impl FederationState {
+    pub fn generate() -> Self {
+        Self(random_token())
+    }
+
+    pub fn from_request(state: String) -> Self {
+        Self(state)
+    }
+}
+
+impl AsRef<str> for FederationState {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// What the callback needs to finish a login started with an identity provider.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingFederatedLogin {
+    pub provider: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    // where to send the user once logged in
+    pub return_to: Option<String>,
+    pub expires_at: i64,
+}
+
+impl PendingFederatedLogin {
+    pub fn new(provider: String, return_to: Option<String>, expires_at: i64) -> Self {
+        Self {
+            provider,
+            nonce: random_token(),
+            code_verifier: random_token(),
+            return_to,
+            expires_at,
+        }
+    }
+}
+
+// 43 characters, which also makes for a valid PKCE verifier
+fn random_token() -> String {
+    let mut bytes = [0; FEDERATION_STATE_BYTES];
+    rand::rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
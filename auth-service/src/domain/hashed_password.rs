@@ -0,0 +1,104 @@
+use std::fmt;
+
+use argon2::{
+    Algorithm, Argon2,
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::Password;
+
+/// An argon2id hash of a user's password, all that's kept of it.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct HashedPassword(String);
+
+impl HashedPassword {
+    /// Slow on purpose, so better run off the async workers.
+    pub fn new(password: &Password) -> Self {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default()
+            .hash_password(password.as_ref().as_bytes(), &salt)
+            .expect("default argon2 parameters are valid");
+        Self(hash.to_string())
+    }
+
+    pub fn parse(hash: &str) -> Option<Self> {
+        let parsed = PasswordHash::new(hash).ok()?;
+        (parsed.algorithm == Algorithm::Argon2id.ident()).then(|| Self(hash.to_string()))
+    }
+
+    /// Slow on purpose, like hashing.
+    pub fn verify(&self, password: &Password) -> bool {
+        PasswordHash::new(&self.0).is_ok_and(|hash| {
+            Argon2::default()
+                .verify_password(password.as_ref().as_bytes(), &hash)
+                .is_ok()
+        })
+    }
+}
+
+impl TryFrom<String> for HashedPassword {
+    type Error = String;
+
+    fn try_from(hash: String) -> Result<Self, Self::Error> {
+        Self::parse(&hash).ok_or_else(|| "unsupported password hash".to_string())
+    }
+}
+
+impl From<HashedPassword> for String {
+    fn from(hash: HashedPassword) -> Self {
+        hash.0
+    }
+}
+
+// like legacy hashes, these are only good for cracking
+impl fmt::Debug for HashedPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HashedPassword(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn password(password: &str) -> Password {
+        password.parse().unwrap()
+    }
+
+    #[test]
+    fn test_verify() {
+        let hash = HashedPassword::new(&password("password123"));
+
+        assert!(hash.verify(&password("password123")));
+        assert!(!hash.verify(&password("password124")));
+    }
+
+    #[test]
+    fn test_hashes_are_salted() {
+        assert_ne!(
+            HashedPassword::new(&password("password123")),
+            HashedPassword::new(&password("password123"))
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_other_formats() {
+        assert!(HashedPassword::parse("password123").is_none());
+        assert!(HashedPassword::parse(&bcrypt::hash("password123", 4).unwrap()).is_none());
+        assert!(HashedPassword::parse("$argon2i$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA").is_none());
+        assert!(HashedPassword::parse("").is_none());
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let hash = HashedPassword::new(&password("password123"));
+
+        let json = serde_json::to_string(&hash).unwrap();
+
+        assert!(!json.contains("password123"));
+        assert_eq!(serde_json::from_str::<HashedPassword>(&json).unwrap(), hash);
+        assert!(serde_json::from_str::<HashedPassword>("\"plaintext\"").is_err());
+    }
+}
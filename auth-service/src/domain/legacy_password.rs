@@ -0,0 +1,143 @@
+use std::fmt;
+
+use pbkdf2::{
+    Algorithm, Pbkdf2,
+    password_hash::{PasswordHash, PasswordVerifier},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::Password;
+
+/// A password hash imported from another system, checked instead of the user's password
+/// until their next successful login replaces it.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum LegacyPasswordHash {
+    // modular crypt format, e.g. `$2b$12$...`
+    Bcrypt(String),
+    // PHC string format, e.g. `$pbkdf2-sha256$i=600000,l=32$...`
+    Pbkdf2(String),
+}
+
+impl LegacyPasswordHash {
+    pub fn parse(hash: &str) -> Option<Self> {
+        if hash.parse::<bcrypt::HashParts>().is_ok() {
+            return Some(Self::Bcrypt(hash.to_string()));
+        }
+        let parsed = PasswordHash::new(hash).ok()?;
+        Algorithm::try_from(parsed.algorithm).ok()?;
+        Some(Self::Pbkdf2(hash.to_string()))
+    }
+
+    /// Slow on purpose, so better run off the async workers.
+    pub fn verify(&self, password: &Password) -> bool {
+        let password = password.as_ref().as_bytes();
+        match self {
+            Self::Bcrypt(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            Self::Pbkdf2(hash) => PasswordHash::new(hash)
+                .is_ok_and(|hash| Pbkdf2.verify_password(password, &hash).is_ok()),
+        }
+    }
+}
+
+impl TryFrom<String> for LegacyPasswordHash {
+    type Error = String;
+
+    fn try_from(hash: String) -> Result<Self, Self::Error> {
+        Self::parse(&hash).ok_or_else(|| "unsupported password hash".to_string())
+    }
+}
+
+impl From<LegacyPasswordHash> for String {
+    fn from(hash: LegacyPasswordHash) -> Self {
+        match hash {
+            LegacyPasswordHash::Bcrypt(hash) | LegacyPasswordHash::Pbkdf2(hash) => hash,
+        }
+    }
+}
+
+// offline cracking is what these hashes are for, so keep them out of logs
+impl fmt::Debug for LegacyPasswordHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bcrypt(_) => f.write_str("Bcrypt(..)"),
+            Self::Pbkdf2(_) => f.write_str("Pbkdf2(..)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pbkdf2::{
+        Params,
+        password_hash::{PasswordHasher, SaltString},
+    };
+
+    use super::*;
+
+    // the lowest costs either scheme allows, tests don't need the real ones
+    fn bcrypt_hash(password: &str) -> String {
+        bcrypt::hash(password, 4).unwrap()
+    }
+
+    fn pbkdf2_hash(password: &str) -> String {
+        let params = Params {
+            rounds: 1000,
+            output_length: 32,
+        };
+        Pbkdf2
+            .hash_password_customized(
+                password.as_bytes(),
+                Some(Algorithm::Pbkdf2Sha256.ident()),
+                None,
+                params,
+                &SaltString::from_b64("c2FsdHNhbHRzYWx0").unwrap(),
+            )
+            .unwrap()
+            .to_string()
+    }
+
+    fn password(password: &str) -> Password {
+        password.parse().unwrap()
+    }
+
+    #[test]
+    fn test_verify_bcrypt() {
+        let hash = LegacyPasswordHash::parse(&bcrypt_hash("password123")).unwrap();
+
+        assert!(matches!(hash, LegacyPasswordHash::Bcrypt(_)));
+        assert!(hash.verify(&password("password123")));
+        assert!(!hash.verify(&password("password124")));
+    }
+
+    #[test]
+    fn test_verify_pbkdf2() {
+        let hash = LegacyPasswordHash::parse(&pbkdf2_hash("password123")).unwrap();
+
+        assert!(matches!(hash, LegacyPasswordHash::Pbkdf2(_)));
+        assert!(hash.verify(&password("password123")));
+        assert!(!hash.verify(&password("password124")));
+    }
+
+    #[test]
+    fn test_parse_rejects_other_formats() {
+        assert!(LegacyPasswordHash::parse("password123").is_none());
+        assert!(
+            LegacyPasswordHash::parse("$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA").is_none()
+        );
+        assert!(LegacyPasswordHash::parse("").is_none());
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let hash = LegacyPasswordHash::parse(&bcrypt_hash("password123")).unwrap();
+
+        let json = serde_json::to_string(&hash).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<LegacyPasswordHash>(&json).unwrap(),
+            hash
+        );
+        assert!(serde_json::from_str::<LegacyPasswordHash>("\"plaintext\"").is_err());
+    }
+}
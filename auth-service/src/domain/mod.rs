@@ -1,11 +1,31 @@
+mod audit;
 mod data_stores;
 mod email;
+mod email_client;
 mod error;
+mod federation;
+mod hashed_password;
+mod legacy_password;
+mod oauth;
+mod passkey;
 mod password;
+mod role;
+mod session;
+mod two_factor;
 mod user;
 
+pub use audit::*;
 pub use data_stores::*;
 pub use email::*;
+pub use email_client::*;
 pub use error::*;
+pub use federation::*;
+pub use hashed_password::*;
+pub use legacy_password::*;
+pub use oauth::*;
+pub use passkey::*;
 pub use password::*;
+pub use role::*;
+pub use session::*;
+pub use two_factor::*;
 pub use user::*;
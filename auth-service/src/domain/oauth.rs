@@ -0,0 +1,253 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::domain::Email;
+
+// 1 min, codes are meant to be exchanged straight away
+pub const AUTHORIZATION_CODE_TTL_SECONDS: i64 = 60;
+const AUTHORIZATION_CODE_BYTES: usize = 32;
+
+pub const SCOPE_OPENID: &str = "openid";
+pub const SCOPE_EMAIL: &str = "email";
+pub const SUPPORTED_SCOPES: [&str; 2] = [SCOPE_OPENID, SCOPE_EMAIL];
+
+// scopes only service clients can be granted
+pub const SCOPE_TOKENS_VERIFY: &str = "tokens:verify";
+pub const SCOPE_TOKENS_REVOKE: &str = "tokens:revoke";
+pub const SCOPE_METRICS_READ: &str = "metrics:read";
+pub const SERVICE_SCOPES: [&str; 3] =
+    [SCOPE_TOKENS_VERIFY, SCOPE_TOKENS_REVOKE, SCOPE_METRICS_READ];
+// short enough to be guessable isn't worth hashing
+pub const MIN_CLIENT_SECRET_LENGTH: usize = 32;
+
+/// A scope a route can require of service tokens, see `AuthenticatedService`.
+pub trait ServiceScope {
+    const NAME: &'static str;
+}
+
+/// Lets a service check user tokens on behalf of its users.
+pub struct VerifyTokens;
+
+impl ServiceScope for VerifyTokens {
+    const NAME: &'static str = SCOPE_TOKENS_VERIFY;
+}
+
+/// Lets a service revoke tokens, e.g. a gateway logging users out.
+pub struct RevokeTokens;
+
+impl ServiceScope for RevokeTokens {
+    const NAME: &'static str = SCOPE_TOKENS_REVOKE;
+}
+
+/// Lets a monitoring system scrape `/metrics`.
+pub struct ReadMetrics;
+
+impl ServiceScope for ReadMetrics {
+    const NAME: &'static str = SCOPE_METRICS_READ;
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ClientId(String);
+
+impl ClientId {
+    pub fn parse(id: &str) -> Option<Self> {
+        Uuid::parse_str(id).ok().map(|id| Self(id.to_string()))
+    }
+}
+
+impl Default for ClientId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for ClientId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A registered relying party. Clients are public, so PKCE stands in for a client secret.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OAuthClient {
+    pub id: ClientId,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub owner: Email,
+}
+
+impl OAuthClient {
+    // exact matches only, as the spec recommends
+    pub fn allows_redirect(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris.iter().any(|uri| uri == redirect_uri)
+    }
+}
+
+/// A machine identity using the client credentials grant, e.g. `app-service`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceClient {
+    pub id: String,
+    pub secret_hash: ClientSecretHash,
+    pub scopes: Vec<String>,
+}
+
+impl ServiceClient {
+    pub fn allows_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|allowed| allowed == scope)
+    }
+}
+
+// service secrets are checked against a minimum length, so a fast hash is sufficient
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientSecretHash([u8; 32]);
+
+impl ClientSecretHash {
+    pub fn of(secret: &str) -> Self {
+        Self(Sha256::digest(secret.as_bytes()).into())
+    }
+
+    pub fn matches(&self, secret: &str) -> bool {
+        *self == Self::of(secret)
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct AuthorizationCode(String);
+
+impl AuthorizationCode {
+    pub fn generate() -> Self {
+        let mut bytes = [0; AUTHORIZATION_CODE_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    pub fn from_request(code: String) -> Self {
+        Self(code)
+    }
+}
+
+impl AsRef<str> for AuthorizationCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// An S256 PKCE code challenge (RFC 7636).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeChallenge(String);
+
+impl CodeChallenge {
+    pub fn parse(challenge: &str) -> Option<Self> {
+        // base64url of a SHA-256 digest, unpadded
+        let valid = challenge.len() == 43
+            && challenge
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        valid.then(|| Self(challenge.to_string()))
+    }
+
+    pub fn of(verifier: &str) -> Self {
+        Self(URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())))
+    }
+
+    pub fn matches(&self, verifier: &str) -> bool {
+        let valid = (43..=128).contains(&verifier.len())
+            && verifier
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c));
+        valid && *self == Self::of(verifier)
+    }
+}
+
+impl AsRef<str> for CodeChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// An authorization request waiting for the user to allow it, carried by the consent page.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsentRequest {
+    pub client_id: String,
+    pub redirect_uri: String,
+    // space separated, as in OAuth
+    pub scope: String,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: String,
+}
+
+/// Everything `/token` needs to check before redeeming an authorization code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingAuthorization {
+    pub client_id: ClientId,
+    pub redirect_uri: String,
+    pub email: Email,
+    pub scopes: Vec<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: CodeChallenge,
+    pub expires_at: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_challenge_rfc_7636_vector() {
+        let challenge =
+            CodeChallenge::parse("E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM").unwrap();
+
+        assert!(challenge.matches("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"));
+        assert!(!challenge.matches("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXj"));
+    }
+
+    #[test]
+    fn test_code_challenge_rejects_bad_input() {
+        assert!(CodeChallenge::parse("too-short").is_none());
+        assert!(CodeChallenge::parse("E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw+cM").is_none());
+
+        // verifiers have to be at least 43 characters
+        let challenge = CodeChallenge::of("short");
+        assert!(!challenge.matches("short"));
+    }
+
+    #[test]
+    fn test_client_allows_only_registered_redirects() {
+        let client = OAuthClient {
+            id: ClientId::default(),
+            name: "app".to_string(),
+            redirect_uris: vec!["https://app.example.com/callback".to_string()],
+            owner: "a@b.com".parse().unwrap(),
+        };
+
+        assert!(client.allows_redirect("https://app.example.com/callback"));
+        assert!(!client.allows_redirect("https://app.example.com/callback/"));
+        assert!(!client.allows_redirect("https://evil.com/callback"));
+    }
+
+    #[test]
+    fn test_service_client_secret_and_scopes() {
+        let client = ServiceClient {
+            id: "app-service".to_string(),
+            secret_hash: ClientSecretHash::of("a secret that is long enough to use"),
+            scopes: vec![SCOPE_TOKENS_VERIFY.to_string()],
+        };
+
+        assert!(
+            client
+                .secret_hash
+                .matches("a secret that is long enough to use")
+        );
+        assert!(
+            !client
+                .secret_hash
+                .matches("a secret that is long enough to us")
+        );
+        assert!(client.allows_scope(SCOPE_TOKENS_VERIFY));
+        assert!(!client.allows_scope(SCOPE_OPENID));
+    }
+}
@@ -0,0 +1,67 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::RngCore;
+
+use crate::domain::Email;
+
+// 5 min
+pub const PASSKEY_CHALLENGE_TTL_SECONDS: i64 = 300;
+const PASSKEY_CHALLENGE_BYTES: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasskeyCredential {
+    pub id: Vec<u8>,
+    // uncompressed SEC1 P-256 point
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+impl PasskeyCredential {
+    pub fn encoded_id(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.id)
+    }
+}
+
+/// Random bytes the authenticator signs over, base64url encoded as the browser reports them.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PasskeyChallenge(String);
+
+impl PasskeyChallenge {
+    pub fn generate() -> Self {
+        let mut bytes = [0; PASSKEY_CHALLENGE_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    pub fn from_client_data(challenge: String) -> Self {
+        Self(challenge)
+    }
+}
+
+impl AsRef<str> for PasskeyChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ceremony {
+    Registration,
+    Authentication,
+}
+
+impl Ceremony {
+    /// The `type` the browser puts in the client data for this ceremony.
+    pub fn client_data_type(&self) -> &'static str {
+        match self {
+            Self::Registration => "webauthn.create",
+            Self::Authentication => "webauthn.get",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingCeremony {
+    pub email: Email,
+    pub ceremony: Ceremony,
+    pub expires_at: i64,
+}
@@ -1,5 +1,7 @@
 use std::str::FromStr;
 
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use validator::{Validate, ValidateRange, ValidationError, ValidationErrors};
 
@@ -8,15 +10,28 @@ use crate::domain::AuthAPIError;
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Password(String);
 
+impl Password {
+    /// A random password nobody knows, for accounts that log in some other way.
+    pub fn unusable() -> Self {
+        let mut bytes = [0; 32];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    /// A password as entered at login. It's only checked against the stored hash, so isn't held
+    /// to the rules for new passwords, which imported ones may predate.
+    pub fn unchecked(password: &str) -> Self {
+        Self(password.to_string())
+    }
+}
+
 impl FromStr for Password {
     type Err = AuthAPIError;
 
     fn from_str(password: &str) -> Result<Self, AuthAPIError> {
         let parsed = Password(password.to_string());
 
-        parsed
-            .validate()
-            .map_err(|_| AuthAPIError::InvalidCredentials)?;
+        parsed.validate()?;
         Ok(parsed)
     }
 }
@@ -27,7 +42,10 @@ impl Validate for Password {
             .then_some(())
             .ok_or_else(|| {
                 let mut errs = ValidationErrors::new();
-                errs.add("password", ValidationError::new("invalid password"));
+                let mut error = ValidationError::new("length")
+                    .with_message("must be at least 8 characters long".into());
+                error.add_param("min".into(), &8);
+                errs.add("password", error);
                 errs
             })
     }
@@ -44,6 +62,7 @@ mod tests {
 
     use super::*;
 
+    use crate::domain::FieldError;
     use fake::{Fake, faker::internet::en::Password};
     use quickcheck_macros::quickcheck;
     use rand::{SeedableRng, rngs::SmallRng};
@@ -79,4 +98,20 @@ mod tests {
     fn invalid_passwords_parsed_unsuccessfully(password: InvalidArbitraryPassword) -> bool {
         password.0.parse::<Password>().is_err()
     }
+
+    #[test]
+    fn too_short_password_explains_why() {
+        let errors = super::Password("1234567".to_string())
+            .validate()
+            .unwrap_err();
+
+        assert_eq!(
+            FieldError::from_validation_errors(&errors),
+            vec![FieldError {
+                field: "password".to_string(),
+                code: "length".to_string(),
+                message: Some("must be at least 8 characters long".to_string()),
+            }]
+        );
+    }
 }
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+
+/// A privilege granted to a user on top of managing their own account.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // can look users up, e.g. to answer support requests
+    Support,
+    Admin,
+}
+
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Support => "support",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            Self::Support => &[Permission::ReadUsers],
+            Self::Admin => &[
+                Permission::ReadUsers,
+                Permission::ManageUsers,
+                Permission::ManageClients,
+            ],
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        [Self::Support, Self::Admin]
+            .into_iter()
+            .find(|role| role.name() == value)
+            .ok_or_else(|| format!("unknown role {value}"))
+    }
+}
+
+/// What a role allows, checked by routes instead of the roles themselves.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Permission {
+    #[serde(rename = "users:read")]
+    ReadUsers,
+    #[serde(rename = "users:manage")]
+    ManageUsers,
+    #[serde(rename = "clients:manage")]
+    ManageClients,
+}
+
+/// A permission a route requires, see `utils::extractors::AuthorizedUser`.
+pub trait RequiredPermission {
+    const PERMISSION: Permission;
+}
+
+/// Lets a user list and search all users.
+pub struct ReadUsers;
+
+impl RequiredPermission for ReadUsers {
+    const PERMISSION: Permission = Permission::ReadUsers;
+}
+
+/// Lets a user lock, unlock and delete other users.
+pub struct ManageUsers;
+
+impl RequiredPermission for ManageUsers {
+    const PERMISSION: Permission = Permission::ManageUsers;
+}
+
+/// Lets a user register OAuth clients, which users then log in to with their accounts.
+pub struct ManageClients;
+
+impl RequiredPermission for ManageClients {
+    const PERMISSION: Permission = Permission::ManageClients;
+}
@@ -0,0 +1,131 @@
+use crate::domain::Email;
+
+/// Identifies a session by its token's `jti`.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct SessionId(String);
+
+impl SessionId {
+    pub fn from_token_id(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl AsRef<str> for SessionId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Where a token was issued to, as far as the request tells.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceInfo {
+    // a human readable label, e.g. "Firefox on Linux"
+    pub name: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl DeviceInfo {
+    pub fn new(ip: Option<String>, user_agent: Option<String>) -> Self {
+        Self {
+            name: describe_device(user_agent.as_deref()),
+            ip,
+            user_agent,
+        }
+    }
+}
+
+/// A token issued to a user, kept so they can see where they're logged in and revoke it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub id: SessionId,
+    pub email: Email,
+    // needed to ban the token, never shown to anyone
+    pub token: String,
+    pub device: DeviceInfo,
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    pub expires_at: i64,
+}
+
+impl Session {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at <= now
+    }
+}
+
+// good enough to tell devices apart, not meant to be exact
+fn describe_device(user_agent: Option<&str>) -> String {
+    let Some(user_agent) = user_agent else {
+        return "Unknown device".to_string();
+    };
+
+    // order matters, e.g. Edge and Chrome user agents also claim to be Safari
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ]
+    .into_iter()
+    .find(|(marker, _)| user_agent.contains(marker))
+    .map(|(_, browser)| browser);
+    let os = [
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iPadOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("Linux", "Linux"),
+    ]
+    .into_iter()
+    .find(|(marker, _)| user_agent.contains(marker))
+    .map(|(_, os)| os);
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{browser} on {os}"),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        // not a browser, so the product token is the best name there is
+        (None, None) => user_agent
+            .split_whitespace()
+            .next()
+            .unwrap_or("Unknown device")
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_device() {
+        let test_cases = [
+            (
+                Some("Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0"),
+                "Firefox on Linux",
+            ),
+            (
+                Some(
+                    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like \
+                    Gecko) Chrome/129.0.0.0 Safari/537.36 Edg/129.0.0.0",
+                ),
+                "Edge on Windows",
+            ),
+            (
+                Some(
+                    "Mozilla/5.0 (iPhone; CPU iPhone OS 17_6 like Mac OS X) AppleWebKit/605.1.15 \
+                    (KHTML, like Gecko) Version/17.6 Mobile/15E148 Safari/604.1",
+                ),
+                "Safari on iOS",
+            ),
+            (Some("curl/8.5.0"), "curl/8.5.0"),
+            (None, "Unknown device"),
+        ];
+
+        for (user_agent, expected) in test_cases {
+            assert_eq!(describe_device(user_agent), expected);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::fmt;
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+use crate::domain::Email;
+
+pub const TOTP_ISSUER: &str = "auth-service";
+pub const TOTP_DIGITS: usize = 6;
+pub const TOTP_STEP_SECONDS: u64 = 30;
+// 160 bits, as recommended by RFC 4226
+const TOTP_SECRET_BYTES: usize = 20;
+
+// 5 min to get a code out of the authenticator or the inbox
+pub const LOGIN_ATTEMPT_TTL_SECONDS: i64 = 300;
+// a million possible codes, so a handful of guesses barely moves the odds
+pub const MAX_SECOND_FACTOR_GUESSES: u32 = 5;
+
+const EMAIL_CODE_DIGITS: usize = 6;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10;
+// no 0/o or 1/l so codes can be read back off paper
+const RECOVERY_CODE_ALPHABET: &[u8] = b"23456789abcdefghijkmnpqrstuvwxyz";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TwoFactorMethod {
+    Email,
+    Totp {
+        secret: TotpSecret,
+        // the last time step a code was accepted for, so a code can't be replayed
+        last_used_step: Option<u64>,
+    },
+}
+
+impl TwoFactorMethod {
+    pub fn totp(secret: TotpSecret) -> Self {
+        Self::Totp {
+            secret,
+            last_used_step: None,
+        }
+    }
+
+    pub fn kind(&self) -> TwoFactorKind {
+        match self {
+            Self::Email => TwoFactorKind::Email,
+            Self::Totp { .. } => TwoFactorKind::Totp,
+        }
+    }
+}
+
+/// The method without any of its secrets, safe to hand back to clients.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TwoFactorKind {
+    Email,
+    Totp,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct TotpSecret(Vec<u8>);
+
+impl TotpSecret {
+    pub fn generate() -> Self {
+        let mut bytes = vec![0; TOTP_SECRET_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+
+    pub fn to_base32(&self) -> String {
+        self.totp(String::new()).get_secret_base32()
+    }
+
+    pub fn from_base32(encoded: &str) -> Option<Self> {
+        Secret::Encoded(encoded.to_string())
+            .to_bytes()
+            .ok()
+            .map(Self)
+    }
+
+    pub fn provisioning_uri(&self, email: &Email) -> String {
+        self.totp(email.as_ref().to_string()).get_url()
+    }
+
+    pub fn generate_code(&self, unix_time: u64) -> String {
+        self.totp(String::new()).generate(unix_time)
+    }
+
+    /// Checks `code` against the steps either side of `unix_time`, returning the step it matched.
+    pub fn verify(&self, code: &str, unix_time: u64) -> Option<u64> {
+        let totp = self.totp(String::new());
+        let current_step = unix_time / TOTP_STEP_SECONDS;
+
+        (current_step.saturating_sub(1)..=current_step + 1)
+            .find(|step| totp.check(code, step * TOTP_STEP_SECONDS))
+    }
+
+    fn totp(&self, account_name: String) -> TOTP {
+        // skew is handled by `verify` so the matching step is known
+        TOTP::new_unchecked(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            0,
+            TOTP_STEP_SECONDS,
+            self.0.clone(),
+            Some(TOTP_ISSUER.to_string()),
+            account_name,
+        )
+    }
+}
+
+// never print the secret itself
+impl fmt::Debug for TotpSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TotpSecret(..)")
+    }
+}
+
+/// A one-time fallback for a lost authenticator, handed to the user once and only stored hashed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryCode(String);
+
+impl RecoveryCode {
+    pub fn generate() -> Self {
+        let mut rng = rand::rng();
+        let code: String = (0..RECOVERY_CODE_LENGTH)
+            .map(|_| {
+                let idx = rng.random_range(0..RECOVERY_CODE_ALPHABET.len());
+                RECOVERY_CODE_ALPHABET[idx] as char
+            })
+            .collect();
+        let (head, tail) = code.split_at(RECOVERY_CODE_LENGTH / 2);
+        Self(format!("{head}-{tail}"))
+    }
+
+    pub fn generate_set() -> Vec<Self> {
+        (0..RECOVERY_CODE_COUNT).map(|_| Self::generate()).collect()
+    }
+
+    pub fn hash(&self) -> RecoveryCodeHash {
+        RecoveryCodeHash::of(&self.0)
+    }
+}
+
+impl AsRef<str> for RecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// recovery codes carry enough entropy that a fast hash is sufficient
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryCodeHash([u8; 32]);
+
+impl RecoveryCodeHash {
+    /// Hashes user input, ignoring case and the separating dash.
+    pub fn of(code: &str) -> Self {
+        let normalized: String = code
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        Self(Sha256::digest(normalized.as_bytes()).into())
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// A one-time code emailed at login to users with email 2FA, good for that login attempt only.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EmailCode(String);
+
+impl EmailCode {
+    pub fn generate() -> Self {
+        let code = rand::rng().random_range(0..10u32.pow(EMAIL_CODE_DIGITS as u32));
+        Self(format!("{code:0EMAIL_CODE_DIGITS$}"))
+    }
+
+    pub fn matches(&self, code: &str) -> bool {
+        self.0 == code.trim()
+    }
+}
+
+impl AsRef<str> for EmailCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// like the TOTP secret, only the user's inbox should see it
+impl fmt::Debug for EmailCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EmailCode(..)")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoginAttemptId(String);
+
+impl LoginAttemptId {
+    pub fn parse(id: &str) -> Option<Self> {
+        Uuid::parse_str(id).ok().map(|id| Self(id.to_string()))
+    }
+}
+
+impl Default for LoginAttemptId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for LoginAttemptId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B, truncated to 6 digits
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_generate_code_matches_rfc_vectors() {
+        let secret = TotpSecret(RFC_SECRET.to_vec());
+
+        assert_eq!(secret.generate_code(59), "287082");
+        assert_eq!(secret.generate_code(1111111109), "081804");
+        assert_eq!(secret.generate_code(2000000000), "279037");
+    }
+
+    #[test]
+    fn test_base32_round_trip() {
+        let secret = TotpSecret::generate();
+
+        assert!(TotpSecret::from_base32(&secret.to_base32()) == Some(secret));
+        assert!(TotpSecret::from_base32("not base32!").is_none());
+    }
+
+    #[test]
+    fn test_verify_accepts_adjacent_steps() {
+        let secret = TotpSecret::generate();
+        let now = 1_700_000_000;
+        let step = now / TOTP_STEP_SECONDS;
+
+        let previous = secret.generate_code(now - TOTP_STEP_SECONDS);
+        let current = secret.generate_code(now);
+        let next = secret.generate_code(now + TOTP_STEP_SECONDS);
+
+        assert_eq!(secret.verify(&previous, now), Some(step - 1));
+        assert_eq!(secret.verify(&current, now), Some(step));
+        assert_eq!(secret.verify(&next, now), Some(step + 1));
+    }
+
+    #[test]
+    fn test_verify_rejects_distant_steps() {
+        let secret = TotpSecret::generate();
+        let now = 1_700_000_000;
+
+        let stale = secret.generate_code(now - 2 * TOTP_STEP_SECONDS);
+
+        assert_eq!(secret.verify(&stale, now), None);
+        assert_eq!(secret.verify("not a code", now), None);
+    }
+
+    #[test]
+    fn test_recovery_codes_are_unique_and_formatted() {
+        let codes = RecoveryCode::generate_set();
+
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in &codes {
+            let (head, tail) = code.as_ref().split_once('-').unwrap();
+            assert_eq!(head.len() + tail.len(), RECOVERY_CODE_LENGTH);
+            assert_eq!(codes.iter().filter(|other| *other == code).count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_recovery_code_hash_ignores_case_and_dash() {
+        let code = RecoveryCode("abcde-fghij".to_string());
+
+        assert_eq!(code.hash(), RecoveryCodeHash::of("ABCDEFGHIJ"));
+        assert_eq!(code.hash(), RecoveryCodeHash::of(" abcde fghij "));
+        assert_ne!(code.hash(), RecoveryCodeHash::of("abcde-fghik"));
+    }
+
+    #[test]
+    fn test_email_code() {
+        let code = EmailCode::generate();
+
+        assert_eq!(code.as_ref().len(), EMAIL_CODE_DIGITS);
+        assert!(code.as_ref().chars().all(|c| c.is_ascii_digit()));
+        assert!(code.matches(&format!(" {} ", code.as_ref())));
+        assert!(!code.matches(""));
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let secret = TotpSecret::generate();
+        let email: Email = "a@b.com".parse().unwrap();
+
+        let uri = secret.provisioning_uri(&email);
+
+        assert!(uri.starts_with("otpauth://totp/auth-service:a%40b.com?"));
+        assert!(uri.contains(&format!("secret={}", secret.to_base32())));
+    }
+}
@@ -1,18 +1,260 @@
-use crate::domain::{Email, Password};
+use crate::domain::{
+    Email, ExternalIdentity, HashedPassword, LegacyPasswordHash, Password, Permission,
+    RecoveryCode, RecoveryCodeHash, Role, TotpSecret, TwoFactorMethod,
+};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct User {
     pub email: Email,
-    pub password: Password,
-    pub requires_2fa: bool,
+    pub password: HashedPassword,
+    // set for users imported from other systems, until they log in again
+    pub legacy_password: Option<LegacyPasswordHash>,
+    pub two_factor: Option<TwoFactorMethod>,
+    // an authenticator app secret awaiting its first code
+    pub pending_totp: Option<TotpSecret>,
+    pub recovery_codes: Vec<RecoveryCodeHash>,
+    pub external_identities: Vec<ExternalIdentity>,
+    // embedded in auth tokens, which stop validating once it moves on
+    pub token_version: u64,
+    pub roles: Vec<Role>,
+    // locked users can't log in, their tokens are revoked when locking
+    pub locked: bool,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
+    /// Hashing the password takes a while, so better run off the async workers.
+    pub fn new(email: Email, password: Password, two_factor: Option<TwoFactorMethod>) -> Self {
         Self {
             email,
-            password,
-            requires_2fa,
+            password: HashedPassword::new(&password),
+            legacy_password: None,
+            two_factor,
+            pending_totp: None,
+            recovery_codes: Vec::new(),
+            external_identities: Vec::new(),
+            token_version: 0,
+            roles: Vec::new(),
+            locked: false,
         }
     }
+
+    /// A user imported with only a hash of their password from another system.
+    pub fn with_legacy_password(email: Email, hash: LegacyPasswordHash) -> Self {
+        let mut user = Self::new(email, Password::unusable(), None);
+        user.legacy_password = Some(hash);
+        user
+    }
+
+    /// Replaces the password, along with any legacy hash still standing in for it.
+    pub fn set_password(&mut self, password: HashedPassword) {
+        self.password = password;
+        self.legacy_password = None;
+    }
+
+    pub fn requires_2fa(&self) -> bool {
+        self.two_factor.is_some()
+    }
+
+    /// Replaces any existing recovery codes, returning the new ones in plaintext.
+    pub fn reset_recovery_codes(&mut self) -> Vec<RecoveryCode> {
+        let codes = RecoveryCode::generate_set();
+        self.recovery_codes = codes.iter().map(RecoveryCode::hash).collect();
+        codes
+    }
+
+    /// Accepts an authenticator code for a later step than the last one accepted, returning
+    /// whether it was.
+    pub fn use_totp_code(&mut self, code: &str, unix_time: u64) -> bool {
+        let Some(TwoFactorMethod::Totp {
+            secret,
+            last_used_step,
+        }) = self.two_factor.as_mut()
+        else {
+            return false;
+        };
+
+        match secret
+            .verify(code, unix_time)
+            .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        {
+            Some(step) => {
+                *last_used_step = Some(step);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the matching recovery code, returning whether there was one.
+    pub fn consume_recovery_code(&mut self, code: &str) -> bool {
+        let hash = RecoveryCodeHash::of(code);
+        match self
+            .recovery_codes
+            .iter()
+            .position(|stored| *stored == hash)
+        {
+            Some(idx) => {
+                self.recovery_codes.swap_remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Invalidates every auth token issued to the user so far.
+    pub fn revoke_tokens(&mut self) {
+        self.token_version += 1;
+    }
+
+    /// Everything the user's roles allow, without duplicates.
+    pub fn permissions(&self) -> Vec<Permission> {
+        let mut permissions: Vec<Permission> = Vec::new();
+        for permission in self.roles.iter().flat_map(Role::permissions) {
+            if !permissions.contains(permission) {
+                permissions.push(*permission);
+            }
+        }
+        permissions
+    }
+
+    /// Replaces the user's roles. Their tokens carry the old ones, so they're revoked.
+    pub fn set_roles(&mut self, roles: Vec<Role>) {
+        if self.roles != roles {
+            self.roles = roles;
+            self.revoke_tokens();
+        }
+    }
+
+    /// Locks the user out, ending all their sessions.
+    pub fn lock(&mut self) {
+        self.locked = true;
+        self.revoke_tokens();
+    }
+
+    pub fn unlock(&mut self) {
+        self.locked = false;
+    }
+
+    /// Links an identity provider login, unless another one from that provider already is.
+    pub fn link_identity(&mut self, identity: ExternalIdentity) -> bool {
+        match self
+            .external_identities
+            .iter()
+            .find(|linked| linked.provider == identity.provider)
+        {
+            Some(linked) => *linked == identity,
+            None => {
+                self.external_identities.push(identity);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_codes_are_single_use() {
+        let mut user = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            None,
+        );
+        let codes = user.reset_recovery_codes();
+
+        assert!(user.consume_recovery_code(codes[0].as_ref()));
+        assert!(!user.consume_recovery_code(codes[0].as_ref()));
+        assert_eq!(user.recovery_codes.len(), codes.len() - 1);
+        assert!(!user.consume_recovery_code("not-a-code"));
+    }
+
+    #[test]
+    fn test_totp_codes_are_single_use() {
+        let secret = TotpSecret::generate();
+        let mut user = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            Some(TwoFactorMethod::totp(secret.clone())),
+        );
+        let now = 1_700_000_000;
+
+        assert!(user.use_totp_code(&secret.generate_code(now), now));
+        assert!(!user.use_totp_code(&secret.generate_code(now), now));
+        // an older step is no good either, once a later one was used
+        assert!(!user.use_totp_code(&secret.generate_code(now - 30), now));
+    }
+
+    #[test]
+    fn test_reset_recovery_codes_invalidates_old_ones() {
+        let mut user = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            None,
+        );
+        let old_codes = user.reset_recovery_codes();
+        user.reset_recovery_codes();
+
+        assert!(!user.consume_recovery_code(old_codes[0].as_ref()));
+    }
+
+    #[test]
+    fn test_link_identity_once_per_provider() {
+        let mut user = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            None,
+        );
+        let identity = |provider: &str, subject: &str| ExternalIdentity {
+            provider: provider.to_string(),
+            subject: subject.to_string(),
+        };
+
+        assert!(user.link_identity(identity("google", "123")));
+        assert!(user.link_identity(identity("google", "123")));
+        assert!(!user.link_identity(identity("google", "456")));
+        assert!(user.link_identity(identity("github", "456")));
+        assert_eq!(user.external_identities.len(), 2);
+    }
+
+    #[test]
+    fn test_permissions_are_merged_across_roles() {
+        let mut user = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            None,
+        );
+        assert!(user.permissions().is_empty());
+
+        user.set_roles(vec![Role::Support, Role::Admin]);
+
+        assert_eq!(user.token_version, 1);
+        assert_eq!(
+            user.permissions(),
+            vec![
+                Permission::ReadUsers,
+                Permission::ManageUsers,
+                Permission::ManageClients
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lock_revokes_tokens() {
+        let mut user = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            None,
+        );
+
+        user.lock();
+        assert!(user.locked);
+        assert_eq!(user.token_version, 1);
+
+        user.unlock();
+        assert!(!user.locked);
+        // unlocking doesn't bring old tokens back
+        assert_eq!(user.token_version, 1);
+    }
 }
@@ -1,12 +1,21 @@
-use std::error::Error;
+use std::{error::Error, io, net::SocketAddr, pin::Pin, sync::Arc, time::Duration};
 
-use axum::{Router, http::Method, routing::post, serve::Serve};
+use axum::{
+    Router,
+    http::Method,
+    middleware,
+    routing::{delete, get, post},
+    serve::{Listener, ListenerExt},
+};
 use serde::{Deserialize, Serialize};
 use tokio::net::TcpListener;
 use tower_http::{
     cors::CorsLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
     services::{ServeDir, ServeFile},
+    trace::{DefaultOnResponse, TraceLayer},
 };
+use tracing::Level;
 
 pub mod app_state;
 pub mod domain;
@@ -16,15 +25,47 @@ pub mod utils;
 
 use app_state::AppState;
 
-use crate::utils::constants::DROPLET_IP;
+use crate::{
+    domain::FieldError,
+    utils::{
+        constants::{DEFAULT_DRAIN_TIMEOUT, DEFAULT_SWEEP_INTERVAL, DROPLET_IP},
+        security_headers::SecurityHeaders,
+        shutdown::ShutdownHandle,
+        tls::{CertificateResolver, TlsListener, TlsSettings},
+    },
+};
 
 pub struct Application {
-    server: Serve<TcpListener, Router, Router>,
+    router: Router,
+    listener: AppListener,
     pub address: String,
+    // where plain HTTP is redirected from, when serving HTTPS
+    pub redirect_address: Option<String>,
+    // swept for expired entries while serving, its audit sink flushed once the server has stopped
+    app_state: AppState,
+    drain_timeout: Duration,
+    sweep_interval: Duration,
+    security_headers: SecurityHeaders,
+    shutdown: ShutdownHandle,
+}
+
+enum AppListener {
+    Http(TcpListener),
+    Https {
+        listener: TlsListener,
+        certificates: Arc<CertificateResolver>,
+        redirect_listener: Option<TcpListener>,
+    },
 }
 
 impl Application {
-    pub async fn build(app_state: AppState, address: &str) -> Result<Self, Box<dyn Error>> {
+    /// Binds to `address`, serving HTTPS rather than plain HTTP when given `tls`.
+    pub async fn build(
+        mut app_state: AppState,
+        address: &str,
+        tls: Option<TlsSettings>,
+    ) -> Result<Self, Box<dyn Error>> {
+        app_state.secure_cookies = tls.is_some();
         let assets_dir =
             ServeDir::new("assets").not_found_service(ServeFile::new("assets/index.html"));
 
@@ -34,34 +75,252 @@ impl Application {
         ];
 
         let cors = CorsLayer::new()
-            .allow_methods([Method::GET, Method::POST])
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
+        let swept_state = app_state.clone();
+        let shutdown = ShutdownHandle::default();
+
         let router = Router::new()
             .fallback_service(assets_dir)
             .route("/signup", post(routes::signup))
             .route("/login", post(routes::login))
+            .route("/login/magic-link", post(routes::request_magic_link))
+            .route(
+                "/login/magic-link/callback",
+                get(routes::magic_link_callback),
+            )
+            .route("/login/oidc", get(routes::list_identity_providers))
+            .route("/login/oidc/{provider}", get(routes::start_federated_login))
+            .route(
+                "/login/oidc/{provider}/callback",
+                get(routes::federated_login_callback),
+            )
             .route("/logout", post(routes::logout))
             .route("/verify-2fa", post(routes::verify_2fa))
+            .route("/2fa/totp", post(routes::enroll_totp))
+            .route("/2fa/totp/confirm", post(routes::confirm_totp))
+            .route(
+                "/2fa/recovery-codes",
+                post(routes::regenerate_recovery_codes),
+            )
             .route("/verify-token", post(routes::verify_token))
+            .route(
+                "/passkeys/register/start",
+                post(routes::start_passkey_registration),
+            )
+            .route(
+                "/passkeys/register/finish",
+                post(routes::finish_passkey_registration),
+            )
+            .route("/passkeys/login/start", post(routes::start_passkey_login))
+            .route("/passkeys/login/finish", post(routes::finish_passkey_login))
+            .route(
+                "/sessions",
+                get(routes::list_sessions).delete(routes::delete_all_sessions),
+            )
+            .route("/sessions/{id}", delete(routes::delete_session))
+            .route("/account", delete(routes::delete_account))
+            .route("/admin/users", get(routes::list_users))
+            .route("/admin/users/{email}", delete(routes::delete_user))
+            .route("/admin/users/{email}/lock", post(routes::lock_user))
+            .route("/admin/users/{email}/unlock", post(routes::unlock_user))
+            .route("/account/export", get(routes::export_account))
+            .route(
+                "/.well-known/openid-configuration",
+                get(routes::openid_configuration),
+            )
+            .route("/clients", post(routes::register_client))
+            .route("/authorize", get(routes::authorize).post(routes::consent))
+            .route("/jwks.json", get(routes::jwks))
+            .route("/token", post(routes::token))
+            .route("/introspect", post(routes::introspect))
+            .route("/revoke", post(routes::revoke))
+            .route("/userinfo", get(routes::userinfo).post(routes::userinfo))
+            .route("/metrics", get(utils::metrics::metrics))
+            .route("/health/live", get(routes::liveness))
+            .route("/health/ready", get(routes::readiness))
+            .layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                utils::audit::record_audit_events,
+            ))
+            .layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                utils::metrics::track_requests,
+            ))
             .with_state(app_state)
-            .layer(cors);
+            .layer(middleware::from_fn(
+                utils::problem_details::negotiate_problem_details,
+            ))
+            .layer(middleware::from_fn_with_state(
+                shutdown.clone(),
+                utils::shutdown::abort_when_drained,
+            ))
+            .layer(cors)
+            .layer(PropagateRequestIdLayer::x_request_id())
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(utils::telemetry::request_span)
+                    .on_response(DefaultOnResponse::new().level(Level::INFO)),
+            )
+            // outermost, so everything further in sees the id, callers' ids are kept
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
 
         let listener = TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        let (listener, redirect_address) = match tls {
+            None => (AppListener::Http(listener), None),
+            Some(tls) => {
+                let redirect_listener = match &tls.redirect_address {
+                    Some(address) => Some(TcpListener::bind(address).await?),
+                    None => None,
+                };
+                let redirect_address = redirect_listener
+                    .as_ref()
+                    .map(|listener| listener.local_addr())
+                    .transpose()?
+                    .map(|address| address.to_string());
+                let certificates = Arc::new(CertificateResolver::load(tls)?);
+                let listener = TlsListener::new(listener, certificates.server_config()?)?;
+                let listener = AppListener::Https {
+                    listener,
+                    certificates,
+                    redirect_listener,
+                };
+                (listener, redirect_address)
+            }
+        };
+
+        Ok(Self {
+            router,
+            listener,
+            address,
+            redirect_address,
+            app_state: swept_state,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            sweep_interval: DEFAULT_SWEEP_INTERVAL,
+            security_headers: SecurityHeaders::default(),
+            shutdown,
+        })
+    }
+
+    /// How long requests in flight get to finish once shutdown is triggered, before their
+    /// connections are dropped.
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
 
-        Ok(Self { server, address })
+    /// How often expired passkey challenges and pending logins are dropped from the stores.
+    pub fn with_sweep_interval(mut self, sweep_interval: Duration) -> Self {
+        self.sweep_interval = sweep_interval;
+        self
     }
 
+    /// Replaces the default security headers set on every response, assets included.
+    pub fn with_security_headers(mut self, security_headers: SecurityHeaders) -> Self {
+        self.security_headers = security_headers;
+        self
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Serves until shutdown is triggered through `shutdown_handle`, then drains and flushes.
     pub async fn run(self) -> Result<(), std::io::Error> {
-        println!("server started! listening on {}.", &self.address);
-        self.server.await
+        tracing::info!("server started! listening on {}.", &self.address);
+        tokio::spawn(utils::sweeper::sweep_expired(
+            self.app_state.clone(),
+            self.sweep_interval,
+            self.shutdown.clone(),
+        ));
+
+        let shutdown = self.shutdown.clone();
+        let graceful_shutdown = async move { shutdown.requested().await };
+        // sessions record the address they were started from
+        let service = self
+            .router
+            .layer(middleware::from_fn_with_state(
+                self.security_headers,
+                utils::security_headers::set_security_headers,
+            ))
+            .into_make_service_with_connect_info::<SocketAddr>();
+        let mut server: Pin<Box<dyn Future<Output = io::Result<()>> + Send>> = match self.listener {
+            AppListener::Http(listener) => Box::pin(
+                axum::serve(listener, service)
+                    .with_graceful_shutdown(graceful_shutdown)
+                    .into_future(),
+            ),
+            AppListener::Https {
+                listener,
+                certificates,
+                redirect_listener,
+            } => {
+                tokio::spawn(certificates.watch(self.shutdown.clone()));
+                if let Some(redirect_listener) = redirect_listener {
+                    let https_port = listener.local_addr()?.port();
+                    tokio::spawn(serve_https_redirect(
+                        redirect_listener,
+                        https_port,
+                        self.shutdown.clone(),
+                    ));
+                }
+                // tapping is how axum gets the client's address out of any listener
+                Box::pin(
+                    axum::serve(listener.tap_io(|_| {}), service)
+                        .with_graceful_shutdown(graceful_shutdown)
+                        .into_future(),
+                )
+            }
+        };
+        let result = tokio::select! {
+            result = &mut server => result,
+            // requests still running are aborted now, so their connections close too
+            _ = self.shutdown.drain(self.drain_timeout) => server.await,
+        };
+
+        if self.app_state.audit_sink.flush().await.is_err() {
+            tracing::error!("failed to flush the audit log");
+        }
+        self.shutdown.finish();
+        result
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+async fn serve_https_redirect(listener: TcpListener, https_port: u16, shutdown: ShutdownHandle) {
+    let router = Router::new()
+        .fallback(utils::tls::redirect_to_https)
+        .with_state(https_port);
+
+    let result = axum::serve(listener, router)
+        .with_graceful_shutdown(async move { shutdown.requested().await })
+        .await;
+    if let Err(e) = result {
+        tracing::error!(error = %e, "HTTP to HTTPS redirect stopped");
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
+    // unlike `error`, never reworded, so clients can match on it
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Vec<FieldError>>,
+}
+
+/// An `ErrorResponse` as RFC 7807 problem details, for clients accepting
+/// `application/problem+json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Vec<FieldError>>,
 }
@@ -1,21 +1,112 @@
+use std::sync::Arc;
+
 use auth_service::{
     Application,
     app_state::AppState,
-    services::{HashMapUserStore, HashSetTokenStore},
-    utils::constants::prod,
+    domain::{ClientStore, UserStore, UserStoreError},
+    services::{
+        FileAuditSink, HashMapClientStore, HashMapFederatedLoginStore, HashMapLoginAttemptStore,
+        HashMapPasskeyStore, HashMapSessionStore, HashMapUserStore, HashSetTokenStore,
+        PostmarkEmailClient, StateFile,
+    },
+    utils::{
+        constants::{
+            ADMIN, AUDIT_LOG_FILE, DRAIN_TIMEOUT, IDENTITY_PROVIDERS, OTLP_ENDPOINT, POSTMARK,
+            SECURITY_HEADERS, SERVICE_CLIENTS, STATE_FILE, TLS, prod,
+        },
+        shutdown::os_signal,
+        telemetry::{init_tracing, tracer_provider},
+    },
 };
 
 #[tokio::main]
 async fn main() {
+    let tracer_provider = OTLP_ENDPOINT
+        .as_deref()
+        .map(|endpoint| tracer_provider(endpoint).expect("failed to set up OTLP export!"));
+    init_tracing(tracer_provider.as_ref());
+
     let user_store = HashMapUserStore::default();
     let banned_token_store = HashSetTokenStore::default();
-    let app_state = AppState::new(user_store, banned_token_store);
+    // held until exit, as the file is written back then and changes made to it meanwhile
+    // would be lost
+    let _state_file_lock = match STATE_FILE.as_deref() {
+        Some(path) => {
+            let lock = StateFile::lock(path).expect("failed to lock state file!");
+            StateFile::read(path)
+                .expect("failed to read state file!")
+                .restore(&user_store, &banned_token_store)
+                .await
+                .expect("failed to load state file!");
+            Some(lock)
+        }
+        None => None,
+    };
+    if let Some(admin) = ADMIN.clone() {
+        // the state file may already have it
+        match user_store.add_user(admin).await {
+            Ok(()) | Err(UserStoreError::UserAlreadyExists) => {}
+            Err(e) => panic!("failed to add admin: {e:?}"),
+        }
+    }
+    let login_attempt_store = HashMapLoginAttemptStore::default();
+    let passkey_store = HashMapPasskeyStore::default();
+    let email_client =
+        PostmarkEmailClient::new(POSTMARK.clone()).expect("failed to set up the email client!");
+    let client_store = HashMapClientStore::default();
+    for client in SERVICE_CLIENTS.iter() {
+        client_store
+            .add_service_client(client.clone())
+            .await
+            .expect("service client ids must be unique!");
+    }
+    let federated_login_store = HashMapFederatedLoginStore::default();
+    let session_store = HashMapSessionStore::default();
+    let audit_sink = Arc::new(
+        FileAuditSink::open(&*AUDIT_LOG_FILE)
+            .await
+            .expect("failed to open audit log!"),
+    );
+    let app_state = AppState::new(
+        user_store,
+        banned_token_store,
+        login_attempt_store,
+        passkey_store,
+        email_client,
+        client_store,
+        federated_login_store,
+        session_store,
+        audit_sink,
+        IDENTITY_PROVIDERS.clone(),
+    );
     // Here we are using ip 0.0.0.0 so the service is listening on all the configured network interfaces.
     // This is needed for Docker to work, which we will add later on.
     // See: https://stackoverflow.com/questions/39525820/docker-port-forwarding-not-working
-    let app = Application::build(app_state, prod::APP_ADDRESS)
+    let app = Application::build(app_state.clone(), prod::APP_ADDRESS, TLS.clone())
         .await
-        .expect("failed to build app!");
+        .expect("failed to build app!")
+        .with_drain_timeout(*DRAIN_TIMEOUT)
+        .with_security_headers(SECURITY_HEADERS.clone());
+
+    let shutdown = app.shutdown_handle();
+    tokio::spawn(async move {
+        os_signal().await;
+        shutdown.trigger();
+    });
+
+    let result = app.run().await;
+    // the stores only live in memory, so what changed while running goes back into the file
+    // for the next start, and for auth-admin
+    if let Some(path) = STATE_FILE.as_deref() {
+        StateFile::snapshot(&*app_state.user_store, &*app_state.banned_token_store)
+            .await
+            .and_then(|state| state.write(path))
+            .expect("failed to save state file!");
+    }
+    result.expect("app crashed trying to run!");
 
-    app.run().await.expect("app crashed trying to run!");
+    // export whatever spans are still batched up
+    if let Some(provider) = tracer_provider {
+        provider.shutdown().ok();
+    }
 }
@@ -0,0 +1,95 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::{CookieJar, cookie::Cookie};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEvent, AuthAPIError, ExternalIdentity, Role, SessionId, TwoFactorKind, User},
+    routes::sessions::{SessionSummary, end_all_sessions},
+    utils::{audit::Audit, constants::JWT_COOKIE_NAME, extractors::AuthenticatedUser},
+};
+
+pub async fn export_account(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let record = state.user_store.get_user(&user.email).await?;
+    let passkeys = state.passkey_store.get_credentials(&user.email).await?;
+    let sessions = state.session_store.get_sessions(&user.email).await?;
+    let auth_history = state.audit_sink.events_for(&user.email).await?;
+    let current = SessionId::from_token_id(user.claims.id.clone());
+
+    let response = Json(AccountExport {
+        user: UserRecord::from(&record),
+        passkeys: passkeys
+            .iter()
+            .map(|credential| credential.encoded_id())
+            .collect(),
+        sessions: sessions
+            .iter()
+            .map(|session| SessionSummary::new(session, &current))
+            .collect(),
+        auth_history,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn delete_account(
+    State(state): State<AppState>,
+    audit: Audit,
+    jar: CookieJar,
+    user: AuthenticatedUser,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    // the account goes last, so if anything before it fails nothing is left that can log in
+    // or act as the user, and trying again finishes the job
+    state.banned_token_store.add_token(&user.token).await?;
+    end_all_sessions(&state, &user.email).await?;
+    state.passkey_store.delete_credentials(&user.email).await?;
+    // emails aren't verified, so the address may well be someone else's next
+    state.audit_sink.forget(&user.email).await?;
+    audit.forget_subject();
+    state.user_store.delete_user(&user.email).await?;
+
+    let updated_jar = jar.remove(Cookie::from(JWT_COOKIE_NAME));
+
+    Ok((updated_jar, StatusCode::NO_CONTENT))
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccountExport {
+    pub user: UserRecord,
+    // credential ids only, public keys are of no use to the user
+    pub passkeys: Vec<String>,
+    pub sessions: Vec<SessionSummary>,
+    #[serde(rename = "authHistory")]
+    pub auth_history: Vec<AuditEvent>,
+}
+
+// everything stored about a user, minus their credentials
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserRecord {
+    pub email: String,
+    pub roles: Vec<Role>,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    #[serde(rename = "twoFactorMethod")]
+    pub two_factor_method: Option<TwoFactorKind>,
+    #[serde(rename = "recoveryCodesRemaining")]
+    pub recovery_codes_remaining: usize,
+    #[serde(rename = "linkedIdentities")]
+    pub linked_identities: Vec<ExternalIdentity>,
+}
+
+impl From<&User> for UserRecord {
+    fn from(user: &User) -> Self {
+        Self {
+            email: user.email.as_ref().to_string(),
+            roles: user.roles.clone(),
+            requires_2fa: user.requires_2fa(),
+            two_factor_method: user.two_factor.as_ref().map(|method| method.kind()),
+            recovery_codes_remaining: user.recovery_codes.len(),
+            linked_identities: user.external_identities.clone(),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, ManageUsers, ReadUsers, Role, User, UserStoreError},
+    routes::sessions::end_all_sessions,
+    utils::{audit::Audit, extractors::AuthorizedUser},
+};
+
+pub async fn list_users(
+    State(state): State<AppState>,
+    _admin: AuthorizedUser<ReadUsers>,
+    Query(query): Query<UserSearchQuery>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let users = match query.q.as_deref().map(str::trim) {
+        Some(q) if !q.is_empty() => state.user_store.search_users(q).await?,
+        _ => state.user_store.list_users().await?,
+    };
+
+    let response = Json(users.iter().map(UserSummary::from).collect::<Vec<_>>());
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn lock_user(
+    State(state): State<AppState>,
+    audit: Audit,
+    admin: AuthorizedUser<ManageUsers>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.actor(&admin.user.email);
+    let email: Email = email.parse()?;
+    audit.subject(&email);
+
+    // locking revokes their tokens, the sessions only need tidying up
+    state
+        .user_store
+        .set_locked(&email, true)
+        .await
+        .map_err(target_error)?;
+    end_all_sessions(&state, &email).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unlock_user(
+    State(state): State<AppState>,
+    audit: Audit,
+    admin: AuthorizedUser<ManageUsers>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.actor(&admin.user.email);
+    let email: Email = email.parse()?;
+    audit.subject(&email);
+
+    state
+        .user_store
+        .set_locked(&email, false)
+        .await
+        .map_err(target_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_user(
+    State(state): State<AppState>,
+    audit: Audit,
+    admin: AuthorizedUser<ManageUsers>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.actor(&admin.user.email);
+    let email: Email = email.parse()?;
+    audit.subject(&email);
+
+    // the account goes last, as in `delete_account`
+    end_all_sessions(&state, &email).await?;
+    state.passkey_store.delete_credentials(&email).await?;
+    state.audit_sink.forget(&email).await?;
+    audit.forget_subject();
+    state
+        .user_store
+        .delete_user(&email)
+        .await
+        .map_err(target_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// unlike at login, admins may know which users exist
+fn target_error(error: UserStoreError) -> AuthAPIError {
+    match error {
+        UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+        e => e.into(),
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct UserSearchQuery {
+    // matched against emails, case insensitively
+    pub q: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserSummary {
+    pub email: String,
+    pub roles: Vec<Role>,
+    pub locked: bool,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+}
+
+impl From<&User> for UserSummary {
+    fn from(user: &User) -> Self {
+        Self {
+            email: user.email.as_ref().to_string(),
+            roles: user.roles.clone(),
+            locked: user.locked,
+            requires_2fa: user.requires_2fa(),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::{
+    CookieJar,
+    cookie::{Cookie, SameSite},
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, DeviceInfo, FEDERATED_LOGIN_TTL_SECONDS, FederationState, IdentityProvider,
+        Password, PendingFederatedLogin, User, UserStoreError,
+    },
+    routes::login::{handle_no_2fa, redirect_to_2fa},
+    utils::{
+        audit::Audit,
+        constants::{FEDERATION_STATE_COOKIE_NAME, PUBLIC_URL},
+        federation,
+    },
+};
+
+const FEDERATION_PATH: &str = "/login/oidc";
+
+pub async fn list_identity_providers(State(state): State<AppState>) -> impl IntoResponse {
+    let response = Json(
+        state
+            .identity_providers
+            .iter()
+            .map(|provider| IdentityProviderSummary {
+                id: provider.id.clone(),
+                name: provider.name.clone(),
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    (StatusCode::OK, response)
+}
+
+pub async fn start_federated_login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(provider_id): Path<String>,
+    Query(query): Query<StartFederatedLoginQuery>,
+) -> Result<(CookieJar, Redirect), AuthAPIError> {
+    let provider = find_provider(&state, &provider_id)?;
+    let document = federation::discover(provider).await?;
+
+    let federation_state = FederationState::generate();
+    let pending = PendingFederatedLogin::new(
+        provider.id.clone(),
+        query.return_to.filter(|path| is_return_path(path)),
+        Utc::now().timestamp() + FEDERATED_LOGIN_TTL_SECONDS,
+    );
+    let url = federation::authorization_url(
+        provider,
+        &document,
+        &redirect_uri(provider),
+        &federation_state,
+        &pending,
+    )?;
+    state
+        .federated_login_store
+        .add_login(federation_state.clone(), pending)
+        .await?;
+
+    let jar = jar.add(state_cookie(
+        federation_state.as_ref().to_string(),
+        state.secure_cookies,
+    ));
+    Ok((jar, Redirect::to(url.as_str())))
+}
+
+pub async fn federated_login_callback(
+    State(state): State<AppState>,
+    audit: Audit,
+    jar: CookieJar,
+    device: DeviceInfo,
+    Path(provider_id): Path<String>,
+    Query(query): Query<FederatedLoginCallbackQuery>,
+) -> Result<(CookieJar, Response), AuthAPIError> {
+    // the state has to come back to the browser that started the login
+    let expected_state = jar
+        .get(FEDERATION_STATE_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?
+        .value()
+        .to_string();
+    let (Some(code), Some(returned_state)) = (query.code, query.state) else {
+        return Err(AuthAPIError::AuthenticationError);
+    };
+    if returned_state != expected_state {
+        return Err(AuthAPIError::AuthenticationError);
+    }
+
+    let pending = state
+        .federated_login_store
+        .take_login(&FederationState::from_request(returned_state))
+        .await?;
+    if pending.provider != provider_id || pending.expires_at < Utc::now().timestamp() {
+        return Err(AuthAPIError::AuthenticationError);
+    }
+
+    let provider = find_provider(&state, &provider_id)?;
+    let document = federation::discover(provider).await?;
+    let verified = federation::exchange_code(
+        provider,
+        &document,
+        &code,
+        &redirect_uri(provider),
+        &pending,
+    )
+    .await?;
+    audit.subject(&verified.email);
+
+    // accounts are matched up by verified email, and created if there isn't one yet
+    let mut linked = false;
+    let identity = verified.identity.clone();
+    let modified = state
+        .user_store
+        .modify_user(
+            &verified.email,
+            Box::new(|user| linked = user.link_identity(identity)),
+        )
+        .await;
+    let user = match modified {
+        Ok(user) => {
+            if !linked {
+                return Err(AuthAPIError::AuthenticationError);
+            }
+            user
+        }
+        Err(UserStoreError::UserNotFound) => {
+            // leaving the provider and magic links as ways in
+            let email = verified.email.clone();
+            let mut user =
+                tokio::task::spawn_blocking(move || User::new(email, Password::unusable(), None))
+                    .await
+                    .map_err(|_| AuthAPIError::UnexpectedError)?;
+            user.link_identity(verified.identity);
+            state.user_store.add_user(user.clone()).await?;
+            user
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let jar = jar.remove(state_cookie(String::new(), state.secure_cookies));
+
+    match &user.two_factor {
+        // the identity provider stands in for the password, not the second factor
+        Some(method) => {
+            let return_to = pending.return_to.as_deref();
+            let redirect = redirect_to_2fa(&state, user.email.clone(), method, return_to).await?;
+            Ok((jar, redirect.into_response()))
+        }
+        None => {
+            let (jar, _) = handle_no_2fa(&state, &user.email, device, jar).await?;
+            let return_to = pending.return_to.as_deref().unwrap_or("/");
+            Ok((jar, Redirect::to(return_to).into_response()))
+        }
+    }
+}
+
+fn find_provider<'a>(
+    state: &'a AppState,
+    provider_id: &str,
+) -> Result<&'a IdentityProvider, AuthAPIError> {
+    state
+        .identity_providers
+        .iter()
+        .find(|provider| provider.id == provider_id)
+        .ok_or(AuthAPIError::UnknownProvider)
+}
+
+fn redirect_uri(provider: &IdentityProvider) -> String {
+    format!("{}{FEDERATION_PATH}/{}/callback", *PUBLIC_URL, provider.id)
+}
+
+// only ever back into an authorization request, never off site
+fn is_return_path(path: &str) -> bool {
+    path.starts_with("/authorize?")
+}
+
+fn state_cookie(state: String, secure: bool) -> Cookie<'static> {
+    Cookie::build((FEDERATION_STATE_COOKIE_NAME, state))
+        .path(FEDERATION_PATH)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(secure)
+        .build()
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct IdentityProviderSummary {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct StartFederatedLoginQuery {
+    pub return_to: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct FederatedLoginCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+}
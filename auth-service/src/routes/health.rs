@@ -0,0 +1,109 @@
+use std::{fmt::Debug, time::Duration};
+
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::AppState;
+
+// a backend that hangs is as good as down
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Up for as long as the server answers at all.
+pub async fn liveness() -> impl IntoResponse {
+    let response = Json(LivenessResponse {
+        status: HealthStatus::Up,
+    });
+
+    (StatusCode::OK, response)
+}
+
+/// Up once every backend the service needs answers its health check.
+pub async fn readiness(State(state): State<AppState>) -> impl IntoResponse {
+    let (user_store, banned_token_store, email_client) = tokio::join!(
+        check(state.user_store.health_check()),
+        check(state.banned_token_store.health_check()),
+        check(state.email_client.health_check()),
+    );
+    let checks = ReadinessChecks {
+        user_store,
+        banned_token_store,
+        email_client,
+    };
+
+    let (status_code, status) = if checks.all_up() {
+        (StatusCode::OK, HealthStatus::Up)
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, HealthStatus::Down)
+    };
+    let response = Json(ReadinessResponse { status, checks });
+
+    (status_code, response)
+}
+
+async fn check<E: Debug>(health_check: impl Future<Output = Result<(), E>>) -> DependencyHealth {
+    let error = match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, health_check).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(format!("{e:?}")),
+        Err(_) => Some("TimedOut".to_string()),
+    };
+    if let Some(error) = &error {
+        tracing::warn!(error, "health check failed");
+    }
+
+    DependencyHealth {
+        status: if error.is_none() {
+            HealthStatus::Up
+        } else {
+            HealthStatus::Down
+        },
+        error,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LivenessResponse {
+    pub status: HealthStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    pub status: HealthStatus,
+    pub checks: ReadinessChecks,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessChecks {
+    #[serde(rename = "userStore")]
+    pub user_store: DependencyHealth,
+    #[serde(rename = "bannedTokenStore")]
+    pub banned_token_store: DependencyHealth,
+    #[serde(rename = "emailClient")]
+    pub email_client: DependencyHealth,
+}
+
+impl ReadinessChecks {
+    fn all_up(&self) -> bool {
+        [
+            &self.user_store,
+            &self.banned_token_store,
+            &self.email_client,
+        ]
+        .iter()
+        .all(|dependency| dependency.status == HealthStatus::Up)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DependencyHealth {
+    pub status: HealthStatus,
+    // the error's name, e.g. `UnexpectedError`, when down
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -0,0 +1,175 @@
+use axum::{
+    Form, Json,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{OAuthError, RevokeTokens, ServiceClient, ServiceScope, VerifyTokens},
+    routes::oauth::authenticate_service_client,
+    utils::{
+        audit::Audit,
+        auth::{self, GenerateTokenError},
+        constants::PUBLIC_URL,
+    },
+};
+
+pub async fn introspect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<TokenManagementRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    authenticate::<VerifyTokens>(&state, &headers, &request).await?;
+    let token = request.token.ok_or(OAuthError::InvalidRequest)?;
+
+    // nothing more is said about tokens that aren't active (RFC 7662 §2.2)
+    let response = check_token(&state, &token).await?.unwrap_or_default();
+
+    let headers = [
+        (header::CACHE_CONTROL, "no-store"),
+        (header::PRAGMA, "no-cache"),
+    ];
+
+    Ok((StatusCode::OK, headers, Json(response)))
+}
+
+pub async fn revoke(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<TokenManagementRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    authenticate::<RevokeTokens>(&state, &headers, &request).await?;
+    let token = request.token.ok_or(OAuthError::InvalidRequest)?;
+
+    // invalid or already revoked tokens aren't an error (RFC 7009 §2.2), and aren't worth keeping
+    if check_token(&state, &token).await?.is_some() {
+        state
+            .banned_token_store
+            .add_token(&token)
+            .await
+            .map_err(|_| OAuthError::ServerError)?;
+    }
+
+    Ok(StatusCode::OK)
+}
+
+async fn authenticate<S: ServiceScope>(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: &TokenManagementRequest,
+) -> Result<ServiceClient, OAuthError> {
+    let client = authenticate_service_client(
+        state,
+        headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_deref(),
+    )
+    .await?;
+
+    if !client.allows_scope(S::NAME) {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+    Ok(client)
+}
+
+// any kind of token we issue, `None` if it isn't one or isn't active
+async fn check_token(
+    state: &AppState,
+    token: &str,
+) -> Result<Option<IntrospectionResponse>, OAuthError> {
+    // each kind is tried in turn, so rejections here mean nothing
+    let user_token = auth::validate_token(
+        state.banned_token_store.clone(),
+        state.user_store.clone(),
+        &Audit::default(),
+        token,
+    )
+    .await;
+    match user_token {
+        Ok(claims) => {
+            return Ok(Some(IntrospectionResponse {
+                active: true,
+                subject: Some(claims.subject),
+                expirary: Some(claims.expirary),
+                ..IntrospectionResponse::issued()
+            }));
+        }
+        Err(GenerateTokenError::UnexpectedError) => return Err(OAuthError::ServerError),
+        Err(_) => {}
+    }
+
+    let access_token = auth::validate_access_token(
+        state.banned_token_store.clone(),
+        state.user_store.clone(),
+        &Audit::default(),
+        token,
+    )
+    .await;
+    match access_token {
+        Ok(claims) => {
+            return Ok(Some(IntrospectionResponse {
+                active: true,
+                client_id: Some(claims.client_id),
+                scope: Some(claims.scope),
+                subject: Some(claims.claims.subject),
+                expirary: Some(claims.claims.expirary),
+                ..IntrospectionResponse::issued()
+            }));
+        }
+        Err(GenerateTokenError::UnexpectedError) => return Err(OAuthError::ServerError),
+        Err(_) => {}
+    }
+
+    match auth::validate_service_token(state.banned_token_store.clone(), token).await {
+        Ok(claims) => Ok(Some(IntrospectionResponse {
+            active: true,
+            client_id: claims.client_id().map(String::from),
+            scope: Some(claims.scope),
+            subject: Some(claims.claims.subject),
+            expirary: Some(claims.claims.expirary),
+            ..IntrospectionResponse::issued()
+        })),
+        Err(GenerateTokenError::UnexpectedError) => Err(OAuthError::ServerError),
+        Err(_) => Ok(None),
+    }
+}
+
+// the same form for both, RFC 7009 §2.1 and RFC 7662 §2.1
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct TokenManagementRequest {
+    pub token: Option<String>,
+    // we can tell our tokens apart, so the hint isn't needed
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(rename = "exp", skip_serializing_if = "Option::is_none")]
+    pub expirary: Option<usize>,
+    #[serde(rename = "sub", skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(rename = "iss", skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+}
+
+impl IntrospectionResponse {
+    fn issued() -> Self {
+        Self {
+            token_type: Some("Bearer".to_string()),
+            issuer: Some(PUBLIC_URL.clone()),
+            ..Self::default()
+        }
+    }
+}
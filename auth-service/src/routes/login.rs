@@ -1,25 +1,133 @@
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+};
 use axum_extra::extract::CookieJar;
-use serde::Deserialize;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, Password},
-    utils::auth,
+    domain::{
+        AuditEventKind, AuthAPIError, DeviceInfo, Email, EmailCode, LOGIN_ATTEMPT_TTL_SECONDS,
+        LoginAttemptId, Password, TwoFactorMethod,
+    },
+    routes::sessions::start_session,
+    utils::{audit::Audit, auth},
 };
 
 pub async fn login(
     State(state): State<AppState>,
+    audit: Audit,
     jar: CookieJar,
+    device: DeviceInfo,
     Json(request): Json<LoginRequest>,
-) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
-    let email: Email = request.email.parse()?;
-    let password: Password = request.password.parse()?;
+) -> Result<(CookieJar, Response), AuthAPIError> {
+    // which field is malformed is nobody's business until they've logged in
+    let email: Email = request
+        .email
+        .parse()
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    audit.subject(&email);
+    let password = Password::unchecked(&request.password);
 
     state.user_store.validate_user(&email, &password).await?;
+    let user = state.user_store.get_user(&email).await?;
+    // the password was right, so saying why is fine
+    if user.locked {
+        return Err(AuthAPIError::AccountLocked);
+    }
+
+    match &user.two_factor {
+        Some(method) => {
+            audit.kind(AuditEventKind::LoginPendingSecondFactor);
+            handle_2fa(&state, email, method, jar).await
+        }
+        None => handle_no_2fa(&state, &email, device, jar).await,
+    }
+}
+
+pub(crate) async fn handle_2fa(
+    state: &AppState,
+    email: Email,
+    method: &TwoFactorMethod,
+    jar: CookieJar,
+) -> Result<(CookieJar, Response), AuthAPIError> {
+    let login_attempt_id = start_2fa(state, email, method).await?;
+    let response = Json(TwoFactorAuthResponse {
+        message: String::from("2FA required"),
+        login_attempt_id: login_attempt_id.as_ref().to_string(),
+    });
+
+    Ok((jar, (StatusCode::PARTIAL_CONTENT, response).into_response()))
+}
+
+// for logins the browser navigates to, like magic links, where no script is waiting on the
+// response. The login page picks the attempt up from the query instead
+pub(crate) async fn redirect_to_2fa(
+    state: &AppState,
+    email: Email,
+    method: &TwoFactorMethod,
+    return_to: Option<&str>,
+) -> Result<Redirect, AuthAPIError> {
+    let login_attempt_id = start_2fa(state, email.clone(), method).await?;
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    query.append_pair("email", email.as_ref());
+    query.append_pair("loginAttemptId", login_attempt_id.as_ref());
+    if let Some(return_to) = return_to {
+        query.append_pair("return_to", return_to);
+    }
+
+    Ok(Redirect::to(&format!("/?{}", query.finish())))
+}
 
-    let auth_cookie = auth::generate_auth_cookie(&email)?;
-    let new_jar = jar.add(auth_cookie);
+async fn start_2fa(
+    state: &AppState,
+    email: Email,
+    method: &TwoFactorMethod,
+) -> Result<LoginAttemptId, AuthAPIError> {
+    let login_attempt_id = LoginAttemptId::default();
+    let expires_at = Utc::now().timestamp() + LOGIN_ATTEMPT_TTL_SECONDS;
+    let email_code = match method {
+        TwoFactorMethod::Email => Some(send_email_code(state, &email).await?),
+        TwoFactorMethod::Totp { .. } => None,
+    };
+    state
+        .login_attempt_store
+        .add_attempt(email, login_attempt_id.clone(), email_code, expires_at)
+        .await?;
+    state.metrics.two_factor_challenged();
+
+    Ok(login_attempt_id)
+}
+
+async fn send_email_code(state: &AppState, email: &Email) -> Result<EmailCode, AuthAPIError> {
+    let code = EmailCode::generate();
+    let content = format!(
+        "Your login code is {}\n\
+        It expires in {} minutes. If you didn't just log in, change your password.",
+        code.as_ref(),
+        LOGIN_ATTEMPT_TTL_SECONDS / 60
+    );
+
+    state
+        .email_client
+        .send_email(email, "Your login code", &content)
+        .await?;
+    Ok(code)
+}
+
+pub(crate) async fn handle_no_2fa(
+    state: &AppState,
+    email: &Email,
+    device: DeviceInfo,
+    jar: CookieJar,
+) -> Result<(CookieJar, Response), AuthAPIError> {
+    let token = start_session(state, email, device).await?;
+    let new_jar = jar.add(auth::create_auth_cookie(token, state.secure_cookies));
 
     Ok((new_jar, StatusCode::OK.into_response()))
 }
@@ -29,3 +137,10 @@ pub struct LoginRequest {
     pub email: String,
     pub password: String,
 }
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TwoFactorAuthResponse {
+    pub message: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+}
@@ -3,22 +3,40 @@ use axum_extra::extract::{CookieJar, cookie::Cookie};
 
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{AuthAPIError, SessionId, SessionStoreError},
+    utils::{audit::Audit, auth::validate_token, constants::JWT_COOKIE_NAME},
 };
 
 pub async fn logout(
     State(state): State<AppState>,
+    audit: Audit,
     jar: CookieJar,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
     let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
     let token = cookie.value().to_string();
 
     // asserts the token is still valid
-    let _ = validate_token(state.banned_token_store.clone(), &token).await?;
+    let claims = validate_token(
+        state.banned_token_store.clone(),
+        state.user_store.clone(),
+        &audit,
+        &token,
+    )
+    .await?;
+    if let Ok(email) = claims.subject.parse() {
+        audit.subject(&email);
+    }
 
     let updated_jar = jar.remove(Cookie::from(JWT_COOKIE_NAME));
     state.banned_token_store.add_token(&token).await?;
+    match state
+        .session_store
+        .remove_session(&SessionId::from_token_id(claims.id))
+        .await
+    {
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => {}
+        Err(e) => return Err(e.into()),
+    }
 
     Ok((updated_jar, StatusCode::OK))
 }
@@ -0,0 +1,148 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::{
+    CookieJar,
+    cookie::{Cookie, SameSite},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, DeviceInfo, Email, TwoFactorMethod, UserStoreError},
+    routes::login::{handle_no_2fa, redirect_to_2fa},
+    utils::{
+        audit::Audit,
+        auth::{self, MAGIC_LINK_TTL_SECONDS},
+        constants::{MAGIC_LINK_NONCE_COOKIE_NAME, PUBLIC_URL},
+    },
+};
+
+const MAGIC_LINK_PATH: &str = "/login/magic-link";
+
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    audit: Audit,
+    jar: CookieJar,
+    Json(request): Json<MagicLinkRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let email: Email = request
+        .email
+        .parse()
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    audit.subject(&email);
+
+    let mut nonce = [0; 32];
+    rand::rng().fill_bytes(&mut nonce);
+    let nonce = URL_SAFE_NO_PAD.encode(nonce);
+
+    // unknown emails get the same response, so accounts can't be probed for. That goes for
+    // how long it takes too, so the email is sent without waiting on the provider
+    match state.user_store.get_user(&email).await {
+        Ok(_) => {
+            let token = auth::generate_magic_link_token(&email, &nonce)?;
+            let state = state.clone();
+            tokio::spawn(async move {
+                if let Err(e) = send_magic_link(&state, &email, &token).await {
+                    tracing::warn!(error = ?e, "failed to send magic link");
+                }
+            });
+        }
+        Err(UserStoreError::UserNotFound) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let response = Json(MagicLinkResponse {
+        message: String::from("If the account exists, a login link has been sent!"),
+    });
+
+    Ok((
+        jar.add(nonce_cookie(nonce, state.secure_cookies)),
+        (StatusCode::OK, response),
+    ))
+}
+
+pub async fn magic_link_callback(
+    State(state): State<AppState>,
+    audit: Audit,
+    jar: CookieJar,
+    device: DeviceInfo,
+    Query(query): Query<MagicLinkCallbackQuery>,
+) -> Result<(CookieJar, Response), AuthAPIError> {
+    // a forwarded link arrives without the requesting browser's nonce
+    let nonce = jar
+        .get(MAGIC_LINK_NONCE_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?
+        .value()
+        .to_string();
+
+    let claims =
+        auth::validate_magic_link_token(state.banned_token_store.clone(), &query.token, &nonce)
+            .await?;
+    state.banned_token_store.add_token(&query.token).await?;
+
+    let email: Email = claims
+        .subject
+        .parse()
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    audit.subject(&email);
+    let user = state.user_store.get_user(&email).await?;
+    let jar = jar.remove(nonce_cookie(String::new(), state.secure_cookies));
+
+    match &user.two_factor {
+        // the link stands in for the password, not the second factor. It came by email
+        // though, so an emailed code would prove nothing more
+        Some(method @ TwoFactorMethod::Totp { .. }) => {
+            let redirect = redirect_to_2fa(&state, email, method, None).await?;
+            Ok((jar, redirect.into_response()))
+        }
+        _ => {
+            let (jar, _) = handle_no_2fa(&state, &email, device, jar).await?;
+            Ok((jar, Redirect::to("/").into_response()))
+        }
+    }
+}
+
+async fn send_magic_link(state: &AppState, email: &Email, token: &str) -> Result<(), AuthAPIError> {
+    let link = format!("{}{MAGIC_LINK_PATH}/callback?token={token}", *PUBLIC_URL);
+    let content = format!(
+        "Use this link to log in: {link}\n\
+        It expires in {} minutes, works once, and only in the browser it was requested from.",
+        MAGIC_LINK_TTL_SECONDS / 60
+    );
+
+    state
+        .email_client
+        .send_email(email, "Your login link", &content)
+        .await?;
+    Ok(())
+}
+
+fn nonce_cookie(nonce: String, secure: bool) -> Cookie<'static> {
+    Cookie::build((MAGIC_LINK_NONCE_COOKIE_NAME, nonce))
+        .path(MAGIC_LINK_PATH)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(secure)
+        .build()
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct MagicLinkRequest {
+    pub email: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct MagicLinkResponse {
+    pub message: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct MagicLinkCallbackQuery {
+    pub token: String,
+}
@@ -1,11 +1,31 @@
+mod account;
+mod admin;
+mod federation;
+mod health;
+mod introspection;
 mod login;
 mod logout;
+mod magic_link;
+mod oauth;
+mod passkeys;
+mod sessions;
 mod signup;
+mod totp;
 mod verify_2fa;
 mod verify_token;
 
+pub use account::*;
+pub use admin::*;
+pub use federation::*;
+pub use health::*;
+pub use introspection::*;
 pub use login::*;
 pub use logout::*;
+pub use magic_link::*;
+pub use oauth::*;
+pub use passkeys::*;
+pub use sessions::*;
 pub use signup::*;
+pub use totp::*;
 pub use verify_2fa::*;
 pub use verify_token::*;
@@ -0,0 +1,615 @@
+use axum::{
+    Form, Json,
+    extract::{OriginalUri, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use url::{Url, form_urlencoded};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AUTHORIZATION_CODE_TTL_SECONDS, AuditEventKind, AuthAPIError, AuthorizationCode, ClientId,
+        ClientStoreError, CodeChallenge, ConsentRequest, DeviceInfo, ManageClients, OAuthClient,
+        OAuthError, PendingAuthorization, SCOPE_EMAIL, SCOPE_OPENID, SUPPORTED_SCOPES,
+        ServiceClient,
+    },
+    routes::sessions::start_client_session,
+    utils::{
+        audit::Audit,
+        auth::{self, GenerateTokenError, TOKEN_TTL_SECONDS},
+        constants::{ID_TOKEN_KEY, PUBLIC_URL},
+        extractors::{AuthenticatedUser, AuthorizedUser, BearerUser},
+        signing_key::JwkSet,
+    },
+};
+
+// only admins, as a client's name is what users are asked to trust on the consent page
+pub async fn register_client(
+    State(state): State<AppState>,
+    AuthorizedUser { user: auth, .. }: AuthorizedUser<ManageClients>,
+    Json(request): Json<ClientRegistrationRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let name = request.client_name.trim();
+    if name.is_empty() {
+        return Err(OAuthError::InvalidClientMetadata);
+    }
+    if request.redirect_uris.is_empty()
+        || !request.redirect_uris.iter().all(|uri| is_redirect_uri(uri))
+    {
+        return Err(OAuthError::InvalidRedirectUri);
+    }
+
+    let client = OAuthClient {
+        id: ClientId::default(),
+        name: name.to_string(),
+        redirect_uris: request.redirect_uris,
+        owner: auth.email,
+    };
+    state.client_store.add_client(client.clone()).await?;
+
+    let response = Json(ClientRegistrationResponse {
+        client_id: client.id.as_ref().to_string(),
+        client_name: client.name,
+        redirect_uris: client.redirect_uris,
+        token_endpoint_auth_method: "none".to_string(),
+    });
+
+    Ok((StatusCode::CREATED, response))
+}
+
+pub async fn authorize(
+    State(state): State<AppState>,
+    auth: Result<AuthenticatedUser, AuthAPIError>,
+    OriginalUri(uri): OriginalUri,
+    Query(request): Query<AuthorizeRequest>,
+) -> Result<Response, OAuthError> {
+    // until the redirect uri checks out, errors can't be sent back to the client
+    let client_id = ClientId::parse(&request.client_id).ok_or(OAuthError::InvalidRequest)?;
+    let (client, redirect_uri) =
+        registered_redirect(&state, &client_id, &request.redirect_uri).await?;
+    let client_state = request.state.as_deref();
+
+    let (scopes, code_challenge) = match check_authorize_request(&request) {
+        Ok(checked) => checked,
+        Err(e) => return Ok(redirect_to_client(redirect_uri, client_state, e).into_response()),
+    };
+
+    // send the user to log in, then back here to finish
+    let Ok(auth) = auth else {
+        let return_to = form_urlencoded::Serializer::new(String::new())
+            .append_pair("return_to", &uri.to_string())
+            .finish();
+        return Ok(Redirect::to(&format!("/?{return_to}")).into_response());
+    };
+
+    // nothing is issued until the user allows it on the page, posting back to `consent`
+    let consent = ConsentRequest {
+        client_id: client_id.as_ref().to_string(),
+        redirect_uri: request.redirect_uri,
+        scope: scopes.join(" "),
+        state: request.state,
+        nonce: request.nonce,
+        code_challenge: code_challenge.as_ref().to_string(),
+    };
+    let consent_token = auth::generate_consent_token(&auth.email, &auth.claims.id, consent)
+        .map_err(|_| OAuthError::ServerError)?;
+
+    Ok(consent_page(
+        &client,
+        &auth,
+        &scopes,
+        &consent_token,
+        &redirect_uri,
+    ))
+}
+
+pub async fn consent(
+    State(state): State<AppState>,
+    audit: Audit,
+    auth: AuthenticatedUser,
+    Form(form): Form<ConsentForm>,
+) -> Result<Redirect, OAuthError> {
+    // only the page shown in this very session gets here, which keeps other sites out
+    let request = auth::validate_consent_token(&form.consent, &auth.email, &auth.claims.id)
+        .map_err(|e| match e {
+            GenerateTokenError::UnexpectedError => OAuthError::ServerError,
+            _ => OAuthError::InvalidRequest,
+        })?;
+    let client_id = ClientId::parse(&request.client_id).ok_or(OAuthError::InvalidRequest)?;
+    // the client may have changed since the page was shown
+    let (_, redirect_uri) = registered_redirect(&state, &client_id, &request.redirect_uri).await?;
+    let client_state = request.state.as_deref();
+
+    if form.decision != "allow" {
+        audit.kind(AuditEventKind::ConsentDenied);
+        return Ok(redirect_to_client(
+            redirect_uri,
+            client_state,
+            OAuthError::AccessDenied,
+        ));
+    }
+    let code_challenge =
+        CodeChallenge::parse(&request.code_challenge).ok_or(OAuthError::InvalidRequest)?;
+
+    let code = AuthorizationCode::generate();
+    let pending = PendingAuthorization {
+        client_id,
+        redirect_uri: request.redirect_uri.clone(),
+        email: auth.email,
+        scopes: request.scope.split_whitespace().map(String::from).collect(),
+        nonce: request.nonce.clone(),
+        code_challenge,
+        expires_at: Utc::now().timestamp() + AUTHORIZATION_CODE_TTL_SECONDS,
+    };
+    if let Err(e) = state.client_store.add_code(code.clone(), pending).await {
+        return Ok(redirect_to_client(redirect_uri, client_state, e.into()));
+    }
+
+    let mut redirect_uri = redirect_uri;
+    redirect_uri
+        .query_pairs_mut()
+        .append_pair("code", code.as_ref());
+    if let Some(client_state) = client_state {
+        redirect_uri
+            .query_pairs_mut()
+            .append_pair("state", client_state);
+    }
+
+    Ok(Redirect::to(redirect_uri.as_str()))
+}
+
+pub async fn token(
+    State(state): State<AppState>,
+    audit: Audit,
+    headers: HeaderMap,
+    device: DeviceInfo,
+    Form(request): Form<TokenRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let response = match request.grant_type.as_deref() {
+        Some("authorization_code") => {
+            Json(exchange_code(&state, &audit, device, request).await?).into_response()
+        }
+        Some("client_credentials") => {
+            Json(issue_service_token(&state, &headers, request).await?).into_response()
+        }
+        Some(_) => return Err(OAuthError::UnsupportedGrantType),
+        None => return Err(OAuthError::InvalidRequest),
+    };
+
+    // tokens must never be cached (RFC 6749 §5.1)
+    let headers = [
+        (header::CACHE_CONTROL, "no-store"),
+        (header::PRAGMA, "no-cache"),
+    ];
+
+    Ok((StatusCode::OK, headers, response))
+}
+
+pub async fn userinfo(user: BearerUser) -> impl IntoResponse {
+    let response = Json(UserInfo {
+        subject: user.claims.claims.subject,
+        email: user.email.as_ref().to_string(),
+    });
+
+    (StatusCode::OK, response)
+}
+
+pub async fn jwks() -> impl IntoResponse {
+    let response = Json(JwkSet {
+        keys: vec![ID_TOKEN_KEY.jwk().clone()],
+    });
+
+    (StatusCode::OK, response)
+}
+
+pub async fn openid_configuration() -> impl IntoResponse {
+    let base = PUBLIC_URL.as_str();
+    let response = Json(ProviderMetadata {
+        issuer: base.to_string(),
+        authorization_endpoint: format!("{base}/authorize"),
+        token_endpoint: format!("{base}/token"),
+        userinfo_endpoint: format!("{base}/userinfo"),
+        jwks_uri: format!("{base}/jwks.json"),
+        registration_endpoint: format!("{base}/clients"),
+        introspection_endpoint: format!("{base}/introspect"),
+        revocation_endpoint: format!("{base}/revoke"),
+        scopes_supported: SUPPORTED_SCOPES.map(String::from).to_vec(),
+        response_types_supported: vec!["code".to_string()],
+        grant_types_supported: ["authorization_code", "client_credentials"]
+            .map(String::from)
+            .to_vec(),
+        subject_types_supported: vec!["public".to_string()],
+        id_token_signing_alg_values_supported: vec!["ES256".to_string()],
+        // "none" for relying parties, secrets for service clients
+        token_endpoint_auth_methods_supported: [
+            "none",
+            "client_secret_basic",
+            "client_secret_post",
+        ]
+        .map(String::from)
+        .to_vec(),
+        code_challenge_methods_supported: vec!["S256".to_string()],
+        claims_supported: ["sub", "iss", "aud", "exp", "iat", "email", "nonce"]
+            .map(String::from)
+            .to_vec(),
+    });
+
+    (StatusCode::OK, response)
+}
+
+async fn exchange_code(
+    state: &AppState,
+    audit: &Audit,
+    device: DeviceInfo,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    let (Some(code), Some(redirect_uri), Some(client_id), Some(code_verifier)) = (
+        request.code,
+        request.redirect_uri,
+        request.client_id,
+        request.code_verifier,
+    ) else {
+        return Err(OAuthError::InvalidRequest);
+    };
+
+    let pending = state
+        .client_store
+        .take_code(&AuthorizationCode::from_request(code))
+        .await?;
+    audit.subject(&pending.email);
+
+    // a code is only good for the client, redirect and browser it was issued to
+    if pending.client_id.as_ref() != client_id
+        || pending.redirect_uri != redirect_uri
+        || pending.expires_at < Utc::now().timestamp()
+        || !pending.code_challenge.matches(&code_verifier)
+    {
+        return Err(OAuthError::InvalidGrant);
+    }
+    state.user_store.get_user(&pending.email).await?;
+    let client = state.client_store.get_client(&pending.client_id).await?;
+
+    // the request comes from the client's backend, so it names the session better than its agent
+    let device = DeviceInfo {
+        name: client.name,
+        ..device
+    };
+    let access_token = start_client_session(
+        state,
+        &pending.email,
+        &pending.client_id,
+        &pending.scopes,
+        device,
+    )
+    .await
+    .map_err(|_| OAuthError::ServerError)?;
+    let id_token = auth::generate_id_token(&pending.email, &pending.client_id, pending.nonce)
+        .map_err(|_| OAuthError::ServerError)?;
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: TOKEN_TTL_SECONDS,
+        id_token,
+        scope: pending.scopes.join(" "),
+    })
+}
+
+async fn issue_service_token(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: TokenRequest,
+) -> Result<ServiceTokenResponse, OAuthError> {
+    let client = authenticate_service_client(
+        state,
+        headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_deref(),
+    )
+    .await?;
+
+    // defaults to everything the client is allowed, but never more
+    let scopes: Vec<String> = match request.scope.as_deref() {
+        Some(scope) => scope.split_whitespace().map(String::from).collect(),
+        None => client.scopes.clone(),
+    };
+    if !scopes.iter().all(|scope| client.allows_scope(scope)) {
+        return Err(OAuthError::InvalidScope);
+    }
+
+    let access_token =
+        auth::generate_service_token(&client.id, &scopes).map_err(|_| OAuthError::ServerError)?;
+
+    Ok(ServiceTokenResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: TOKEN_TTL_SECONDS,
+        scope: scopes.join(" "),
+    })
+}
+
+// HTTP Basic, or the credentials in the form body (RFC 6749 §2.3.1)
+pub(crate) async fn authenticate_service_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Result<ServiceClient, OAuthError> {
+    let basic = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "));
+    let (client_id, client_secret) = match (basic, client_id, client_secret) {
+        (Some(credentials), None, None) => {
+            let credentials = STANDARD
+                .decode(credentials)
+                .ok()
+                .and_then(|decoded| String::from_utf8(decoded).ok())
+                .ok_or(OAuthError::InvalidClient)?;
+            let (id, secret) = credentials
+                .split_once(':')
+                .ok_or(OAuthError::InvalidClient)?;
+            (id.to_string(), secret.to_string())
+        }
+        (None, Some(id), Some(secret)) => (id.to_string(), secret.to_string()),
+        // only one way of authenticating per request
+        _ => return Err(OAuthError::InvalidClient),
+    };
+
+    let client = state.client_store.get_service_client(&client_id).await?;
+    if !client.secret_hash.matches(&client_secret) {
+        return Err(OAuthError::InvalidClient);
+    }
+    Ok(client)
+}
+
+// a client's registered redirect uri, the only place errors may be sent from here on
+async fn registered_redirect(
+    state: &AppState,
+    client_id: &ClientId,
+    redirect_uri: &str,
+) -> Result<(OAuthClient, Url), OAuthError> {
+    let client = match state.client_store.get_client(client_id).await {
+        Ok(client) => client,
+        Err(ClientStoreError::ClientNotFound) => return Err(OAuthError::InvalidRequest),
+        Err(e) => return Err(e.into()),
+    };
+    if !client.allows_redirect(redirect_uri) {
+        return Err(OAuthError::InvalidRequest);
+    }
+    let redirect_uri = Url::parse(redirect_uri).map_err(|_| OAuthError::InvalidRequest)?;
+    Ok((client, redirect_uri))
+}
+
+fn consent_page(
+    client: &OAuthClient,
+    auth: &AuthenticatedUser,
+    scopes: &[String],
+    consent_token: &str,
+    redirect_uri: &Url,
+) -> Response {
+    let shared: String = scopes
+        .iter()
+        .map(|scope| match scope.as_str() {
+            SCOPE_OPENID => "<li>your account id</li>",
+            SCOPE_EMAIL => "<li>your email address</li>",
+            _ => "",
+        })
+        .collect();
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Allow {client}?</title>
+    <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/bootstrap@5.2.2/dist/css/bootstrap.min.css">
+</head>
+
+<body>
+    <section class="position-relative py-4 py-xl-5">
+        <div class="container">
+            <div class="row d-flex justify-content-center">
+                <div class="col-md-6 col-xl-4">
+                    <div class="card mb-5">
+                        <div class="card-body">
+                            <h2 class="h4">Allow {client} to log you in?</h2>
+                            <p>You're logged in as {email}. {client} will get:</p>
+                            <ul>{shared}</ul>
+                            <form method="post" action="/authorize">
+                                <input type="hidden" name="consent" value="{consent_token}">
+                                <button class="btn btn-dark d-block w-100 mb-2" type="submit" name="decision" value="allow">Allow</button>
+                                <button class="btn btn-outline-dark d-block w-100" type="submit" name="decision" value="deny">Deny</button>
+                            </form>
+                        </div>
+                    </div>
+                </div>
+            </div>
+        </div>
+    </section>
+</body>
+
+</html>
+"#,
+        client = escape_html(&client.name),
+        email = escape_html(auth.email.as_ref()),
+        consent_token = escape_html(consent_token),
+    );
+
+    // the form's answer redirects to the client, which `form-action` has to allow as well
+    let policy = format!(
+        "default-src 'none'; style-src https://cdn.jsdelivr.net; form-action 'self' {}; \
+        base-uri 'none'; frame-ancestors 'none'",
+        redirect_uri.origin().ascii_serialization()
+    );
+    let headers = [
+        (header::CONTENT_SECURITY_POLICY, policy),
+        // the page carries a token for this session only
+        (header::CACHE_CONTROL, "no-store".to_string()),
+    ];
+
+    (StatusCode::OK, headers, Html(page)).into_response()
+}
+
+fn escape_html(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&#39;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn check_authorize_request(
+    request: &AuthorizeRequest,
+) -> Result<(Vec<String>, CodeChallenge), OAuthError> {
+    if request.response_type.as_deref() != Some("code") {
+        return Err(OAuthError::UnsupportedResponseType);
+    }
+
+    // only S256, "plain" would leak the verifier to anything that sees the redirect
+    if request.code_challenge_method.as_deref() != Some("S256") {
+        return Err(OAuthError::InvalidRequest);
+    }
+    let code_challenge = request
+        .code_challenge
+        .as_deref()
+        .and_then(CodeChallenge::parse)
+        .ok_or(OAuthError::InvalidRequest)?;
+
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in request
+        .scope
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+    {
+        if !SUPPORTED_SCOPES.contains(&scope) {
+            return Err(OAuthError::InvalidScope);
+        }
+        if !scopes.iter().any(|existing| existing == scope) {
+            scopes.push(scope.to_string());
+        }
+    }
+    if !scopes.iter().any(|scope| scope == SCOPE_OPENID) {
+        return Err(OAuthError::InvalidScope);
+    }
+
+    Ok((scopes, code_challenge))
+}
+
+// errors after the redirect uri is known go back to the client (RFC 6749 §4.1.2.1)
+fn redirect_to_client(mut redirect_uri: Url, state: Option<&str>, error: OAuthError) -> Redirect {
+    redirect_uri
+        .query_pairs_mut()
+        .append_pair("error", &error.to_string());
+    if let Some(state) = state {
+        redirect_uri.query_pairs_mut().append_pair("state", state);
+    }
+    Redirect::to(redirect_uri.as_str())
+}
+
+fn is_redirect_uri(uri: &str) -> bool {
+    Url::parse(uri)
+        .is_ok_and(|uri| matches!(uri.scheme(), "http" | "https") && uri.fragment().is_none())
+}
+
+// OAuth messages use snake_case field names, unlike the rest of the API
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClientRegistrationRequest {
+    pub client_name: String,
+    pub redirect_uris: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClientRegistrationResponse {
+    pub client_id: String,
+    pub client_name: String,
+    pub redirect_uris: Vec<String>,
+    pub token_endpoint_auth_method: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct AuthorizeRequest {
+    pub response_type: Option<String>,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+}
+
+// what the consent page posts back
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct ConsentForm {
+    pub consent: String,
+    // "allow" or "deny"
+    pub decision: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub client_id: Option<String>,
+    pub code_verifier: Option<String>,
+    pub client_secret: Option<String>,
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub id_token: String,
+    pub scope: String,
+}
+
+// no ID token, there's no user behind a service client
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ServiceTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub scope: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserInfo {
+    #[serde(rename = "sub")]
+    pub subject: String,
+    pub email: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub registration_endpoint: String,
+    pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
+    pub scopes_supported: Vec<String>,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
@@ -0,0 +1,301 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Ceremony, DeviceInfo, Email, PASSKEY_CHALLENGE_TTL_SECONDS, PasskeyChallenge,
+        PendingCeremony,
+    },
+    routes::sessions::start_session,
+    utils::{
+        audit::Audit,
+        auth,
+        constants::{WEBAUTHN_RP_ID, WEBAUTHN_RP_NAME},
+        extractors::AuthenticatedUser,
+        webauthn::{self, COSE_ALG_ES256, ClientData},
+    },
+};
+
+pub async fn start_passkey_registration(
+    State(state): State<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let existing = state.passkey_store.get_credentials(&auth.email).await?;
+    let challenge = issue_challenge(&state, &auth.email, Ceremony::Registration).await?;
+
+    let response = Json(RegistrationOptions {
+        challenge: challenge.as_ref().to_string(),
+        rp: RelyingParty {
+            id: WEBAUTHN_RP_ID.clone(),
+            name: WEBAUTHN_RP_NAME.to_string(),
+        },
+        user: UserEntity {
+            id: user_handle(&auth.email),
+            name: auth.email.as_ref().to_string(),
+            display_name: auth.email.as_ref().to_string(),
+        },
+        pub_key_cred_params: vec![CredentialParameters {
+            kind: PUBLIC_KEY.to_string(),
+            alg: COSE_ALG_ES256,
+        }],
+        timeout: PASSKEY_CHALLENGE_TTL_SECONDS * 1000,
+        attestation: "none".to_string(),
+        exclude_credentials: existing
+            .iter()
+            .map(|credential| CredentialDescriptor::new(credential.encoded_id()))
+            .collect(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn finish_passkey_registration(
+    State(state): State<AppState>,
+    auth: AuthenticatedUser,
+    Json(request): Json<RegistrationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client_data_json = decode(&request.response.client_data_json)?;
+    let attestation_object = decode(&request.response.attestation_object)?;
+
+    let client_data = ClientData::parse(&client_data_json)?;
+    check_challenge(&state, &client_data, &auth.email, Ceremony::Registration).await?;
+
+    let credential = webauthn::verify_registration(&attestation_object)?;
+    if credential.encoded_id() != request.id {
+        return Err(AuthAPIError::AuthenticationError);
+    }
+    state
+        .passkey_store
+        .add_credential(auth.email, credential)
+        .await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+pub async fn start_passkey_login(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(request): Json<StartPasskeyLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email: Email = request
+        .email
+        .parse()
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    audit.subject(&email);
+    let user = state.user_store.get_user(&email).await?;
+
+    let credentials = state.passkey_store.get_credentials(&user.email).await?;
+    if credentials.is_empty() {
+        return Err(AuthAPIError::AuthenticationError);
+    }
+    let challenge = issue_challenge(&state, &email, Ceremony::Authentication).await?;
+
+    let response = Json(AuthenticationOptions {
+        challenge: challenge.as_ref().to_string(),
+        rp_id: WEBAUTHN_RP_ID.clone(),
+        timeout: PASSKEY_CHALLENGE_TTL_SECONDS * 1000,
+        user_verification: "preferred".to_string(),
+        allow_credentials: credentials
+            .iter()
+            .map(|credential| CredentialDescriptor::new(credential.encoded_id()))
+            .collect(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn finish_passkey_login(
+    State(state): State<AppState>,
+    audit: Audit,
+    jar: CookieJar,
+    device: DeviceInfo,
+    Json(request): Json<PasskeyLoginRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let email: Email = request
+        .email
+        .parse()
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    audit.subject(&email);
+    let client_data_json = decode(&request.response.client_data_json)?;
+    let authenticator_data = decode(&request.response.authenticator_data)?;
+    let signature = decode(&request.response.signature)?;
+
+    let client_data = ClientData::parse(&client_data_json)?;
+    check_challenge(&state, &client_data, &email, Ceremony::Authentication).await?;
+
+    let credential = state
+        .passkey_store
+        .get_credentials(&email)
+        .await?
+        .into_iter()
+        .find(|credential| credential.encoded_id() == request.id)
+        .ok_or(AuthAPIError::AuthenticationError)?;
+
+    let sign_count = webauthn::verify_assertion(
+        &credential,
+        &authenticator_data,
+        &client_data_json,
+        &signature,
+    )?;
+    state
+        .passkey_store
+        .update_sign_count(&email, &credential.id, sign_count)
+        .await?;
+
+    let token = start_session(&state, &email, device).await?;
+    let new_jar = jar.add(auth::create_auth_cookie(token, state.secure_cookies));
+
+    Ok((new_jar, StatusCode::OK))
+}
+
+async fn issue_challenge(
+    state: &AppState,
+    email: &Email,
+    ceremony: Ceremony,
+) -> Result<PasskeyChallenge, AuthAPIError> {
+    let challenge = PasskeyChallenge::generate();
+    let pending = PendingCeremony {
+        email: email.clone(),
+        ceremony,
+        expires_at: Utc::now().timestamp() + PASSKEY_CHALLENGE_TTL_SECONDS,
+    };
+    state
+        .passkey_store
+        .add_challenge(challenge.clone(), pending)
+        .await?;
+
+    Ok(challenge)
+}
+
+async fn check_challenge(
+    state: &AppState,
+    client_data: &ClientData,
+    email: &Email,
+    ceremony: Ceremony,
+) -> Result<(), AuthAPIError> {
+    let pending = state
+        .passkey_store
+        .take_challenge(&client_data.challenge())
+        .await?;
+
+    if pending.email != *email
+        || pending.ceremony != ceremony
+        || pending.expires_at < Utc::now().timestamp()
+    {
+        return Err(AuthAPIError::AuthenticationError);
+    }
+    Ok(client_data.verify(ceremony)?)
+}
+
+fn decode(field: &str) -> Result<Vec<u8>, AuthAPIError> {
+    URL_SAFE_NO_PAD
+        .decode(field)
+        .map_err(|_| AuthAPIError::InvalidCredentials)
+}
+
+// an opaque id for the authenticator to store instead of the email
+fn user_handle(email: &Email) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(email.as_ref().as_bytes()))
+}
+
+const PUBLIC_KEY: &str = "public-key";
+
+// binary fields are base64url encoded, as in the WebAuthn JSON serialization
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationOptions {
+    pub challenge: String,
+    pub rp: RelyingParty,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    pub timeout: i64,
+    pub attestation: String,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RelyingParty {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub alg: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: String,
+}
+
+impl CredentialDescriptor {
+    fn new(id: String) -> Self {
+        Self {
+            kind: PUBLIC_KEY.to_string(),
+            id,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub timeout: i64,
+    pub user_verification: String,
+    pub allow_credentials: Vec<CredentialDescriptor>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct RegistrationRequest {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub attestation_object: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct StartPasskeyLoginRequest {
+    pub email: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct PasskeyLoginRequest {
+    pub email: String,
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
+}
@@ -0,0 +1,196 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use axum_extra::extract::{CookieJar, cookie::Cookie};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, ClientId, DeviceInfo, Email, Session, SessionId, User},
+    utils::{
+        auth::{self, Claims},
+        constants::JWT_COOKIE_NAME,
+        extractors::AuthenticatedUser,
+    },
+};
+
+/// Issues an auth token for `email` and records the session it starts.
+pub(crate) async fn start_session(
+    state: &AppState,
+    email: &Email,
+    device: DeviceInfo,
+) -> Result<String, AuthAPIError> {
+    let user = unlocked_user(state, email).await?;
+    let (token, claims) = auth::issue_auth_token(&user)?;
+    record_session(state, email, &token, &claims, device).await?;
+
+    Ok(token)
+}
+
+/// Like `start_session`, for an OAuth access token issued to `client_id`.
+pub(crate) async fn start_client_session(
+    state: &AppState,
+    email: &Email,
+    client_id: &ClientId,
+    scopes: &[String],
+    device: DeviceInfo,
+) -> Result<String, AuthAPIError> {
+    let user = unlocked_user(state, email).await?;
+    let (token, claims) = auth::issue_access_token(&user, client_id, scopes)?;
+    record_session(state, email, &token, &claims.claims, device).await?;
+
+    Ok(token)
+}
+
+async fn unlocked_user(state: &AppState, email: &Email) -> Result<User, AuthAPIError> {
+    let user = state.user_store.get_user(email).await?;
+    if user.locked {
+        return Err(AuthAPIError::AccountLocked);
+    }
+    Ok(user)
+}
+
+async fn record_session(
+    state: &AppState,
+    email: &Email,
+    token: &str,
+    claims: &Claims,
+    device: DeviceInfo,
+) -> Result<(), AuthAPIError> {
+    let now = Utc::now().timestamp();
+    state
+        .session_store
+        .add_session(Session {
+            id: SessionId::from_token_id(claims.id.clone()),
+            email: email.clone(),
+            token: token.to_string(),
+            device,
+            created_at: now,
+            last_seen_at: now,
+            expires_at: claims.expirary as i64,
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Ends every session of the user, banning their tokens.
+pub(crate) async fn end_all_sessions(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    let now = Utc::now().timestamp();
+    for session in state.session_store.remove_sessions(email).await? {
+        // expired tokens are rejected anyway
+        if !session.is_expired(now) {
+            state.banned_token_store.add_token(&session.token).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let now = Utc::now().timestamp();
+    let mut sessions: Vec<Session> = state
+        .session_store
+        .get_sessions(&user.email)
+        .await?
+        .into_iter()
+        .filter(|session| !session.is_expired(now))
+        .collect();
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+
+    let current = SessionId::from_token_id(user.claims.id);
+    let response = Json(
+        sessions
+            .iter()
+            .map(|session| SessionSummary::new(session, &current))
+            .collect::<Vec<_>>(),
+    );
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn delete_session(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let id = SessionId::from_token_id(id);
+
+    // other users' sessions are as good as missing
+    let owned = state
+        .session_store
+        .get_sessions(&user.email)
+        .await?
+        .iter()
+        .any(|session| session.id == id);
+    if !owned {
+        return Err(AuthAPIError::SessionNotFound);
+    }
+
+    let session = state.session_store.remove_session(&id).await?;
+    state.banned_token_store.add_token(&session.token).await?;
+
+    let jar = if id.as_ref() == user.claims.id {
+        // the path has to match the auth cookie's, this one isn't served from the root
+        jar.remove(Cookie::build(JWT_COOKIE_NAME).path("/"))
+    } else {
+        jar
+    };
+
+    Ok((jar, StatusCode::NO_CONTENT))
+}
+
+/// Logs the user out everywhere, including the browser asking.
+pub async fn delete_all_sessions(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    user: AuthenticatedUser,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    end_all_sessions(&state, &user.email).await?;
+    // also catches tokens issued without a session being recorded
+    state
+        .user_store
+        .modify_user(&user.email, Box::new(|record| record.revoke_tokens()))
+        .await?;
+
+    let updated_jar = jar.remove(Cookie::from(JWT_COOKIE_NAME));
+
+    Ok((updated_jar, StatusCode::NO_CONTENT))
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub id: String,
+    pub device: String,
+    pub ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "lastSeenAt")]
+    pub last_seen_at: i64,
+    // whether this is the session making the request
+    pub current: bool,
+}
+
+impl SessionSummary {
+    pub(crate) fn new(session: &Session, current: &SessionId) -> Self {
+        Self {
+            id: session.id.as_ref().to_string(),
+            device: session.device.name.clone(),
+            ip: session.device.ip.clone(),
+            user_agent: session.device.user_agent.clone(),
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            current: session.id == *current,
+        }
+    }
+}
@@ -3,17 +3,39 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, User},
+    domain::{AuthAPIError, Email, Password, TwoFactorMethod, User},
+    utils::audit::Audit,
 };
 
 pub async fn signup(
     State(state): State<AppState>,
+    audit: Audit,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    let email = request.email;
-    let password = request.password;
-
-    let user = User::new(email.parse()?, password.parse()?, request.requires_2fa);
+    // every field that's wrong, so the form can point them all out at once
+    let (email, password) = match (
+        request.email.parse::<Email>(),
+        request.password.parse::<Password>(),
+    ) {
+        (Ok(email), Ok(password)) => (email, password),
+        (email, password) => {
+            let fields = [email.err(), password.err()]
+                .into_iter()
+                .flatten()
+                .flat_map(|error| match error {
+                    AuthAPIError::ValidationFailed(fields) => fields,
+                    _ => Vec::new(),
+                })
+                .collect();
+            return Err(AuthAPIError::ValidationFailed(fields));
+        }
+    };
+    audit.subject(&email);
+
+    let two_factor = request.requires_2fa.then_some(TwoFactorMethod::Email);
+    let user = tokio::task::spawn_blocking(move || User::new(email, password, two_factor))
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
 
     state.user_store.add_user(user).await?;
 
@@ -0,0 +1,133 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, RecoveryCode, TotpSecret, TwoFactorMethod},
+    utils::extractors::AuthenticatedUser,
+};
+
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    // 2FA stays as it was until the user proves their app has the secret
+    let secret = TotpSecret::generate();
+    let response = Json(TotpEnrollmentResponse {
+        secret: secret.to_base32(),
+        otpauth_uri: secret.provisioning_uri(&auth.email),
+    });
+
+    state
+        .user_store
+        .modify_user(
+            &auth.email,
+            Box::new(|user| user.pending_totp = Some(secret)),
+        )
+        .await?;
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    auth: AuthenticatedUser,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let now = Utc::now().timestamp() as u64;
+    let mut outcome = Err(AuthAPIError::MissingEnrollment);
+    state
+        .user_store
+        .modify_user(
+            &auth.email,
+            Box::new(|user| {
+                let Some(secret) = &user.pending_totp else {
+                    return;
+                };
+                let Some(step) = secret.verify(&request.code, now) else {
+                    outcome = Err(AuthAPIError::AuthenticationError);
+                    return;
+                };
+                // replacing an authenticator takes the one it replaces, or a recovery code
+                if matches!(user.two_factor, Some(TwoFactorMethod::Totp { .. }))
+                    && !request.current_code.as_deref().is_some_and(|code| {
+                        user.use_totp_code(code, now) || user.consume_recovery_code(code)
+                    })
+                {
+                    outcome = Err(AuthAPIError::AuthenticationError);
+                    return;
+                }
+
+                // the confirmation code counts as used
+                user.two_factor = user
+                    .pending_totp
+                    .take()
+                    .map(|secret| TwoFactorMethod::Totp {
+                        secret,
+                        last_used_step: Some(step),
+                    });
+                outcome = Ok(user.reset_recovery_codes());
+            }),
+        )
+        .await?;
+    let recovery_codes = outcome?;
+
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesResponse::from(recovery_codes)),
+    ))
+}
+
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let mut recovery_codes = None;
+    state
+        .user_store
+        .modify_user(
+            &auth.email,
+            Box::new(|user| {
+                if matches!(user.two_factor, Some(TwoFactorMethod::Totp { .. })) {
+                    recovery_codes = Some(user.reset_recovery_codes());
+                }
+            }),
+        )
+        .await?;
+    let recovery_codes = recovery_codes.ok_or(AuthAPIError::TwoFactorNotEnabled)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesResponse::from(recovery_codes)),
+    ))
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct ConfirmTotpRequest {
+    pub code: String,
+    // a code from the authenticator being replaced, or a recovery code
+    #[serde(rename = "currentCode")]
+    pub current_code: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TotpEnrollmentResponse {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+impl From<Vec<RecoveryCode>> for RecoveryCodesResponse {
+    fn from(codes: Vec<RecoveryCode>) -> Self {
+        Self {
+            recovery_codes: codes.iter().map(|code| code.as_ref().to_string()).collect(),
+        }
+    }
+}
@@ -1,5 +1,73 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::Deserialize;
 
-pub async fn verify_2fa() -> impl IntoResponse {
-    StatusCode::OK.into_response()
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, DeviceInfo, Email, LoginAttemptId, UserStoreError},
+    routes::sessions::start_session,
+    utils::{audit::Audit, auth},
+};
+
+pub async fn verify_2fa(
+    State(state): State<AppState>,
+    audit: Audit,
+    jar: CookieJar,
+    device: DeviceInfo,
+    Json(request): Json<Verify2FARequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let email: Email = request
+        .email
+        .parse()
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    audit.subject(&email);
+    let login_attempt_id =
+        LoginAttemptId::parse(&request.login_attempt_id).ok_or(AuthAPIError::InvalidCredentials)?;
+
+    let now = Utc::now().timestamp();
+    let email_code = state
+        .login_attempt_store
+        .check_attempt(&email, &login_attempt_id, now)
+        .await?;
+
+    match email_code {
+        // email 2FA has no authenticator or recovery codes to fall back on
+        Some(email_code) => {
+            if !email_code.matches(&request.two_fa_code) {
+                return Err(AuthAPIError::AuthenticationError);
+            }
+        }
+        None => match state
+            .user_store
+            .use_totp_code(&email, &request.two_fa_code, now as u64)
+            .await
+        {
+            Ok(()) => {}
+            // a recovery code can stand in for a lost authenticator
+            Err(UserStoreError::InvalidCredentials) => {
+                state
+                    .user_store
+                    .use_recovery_code(&email, &request.two_fa_code)
+                    .await?
+            }
+            Err(e) => return Err(e.into()),
+        },
+    }
+
+    state.login_attempt_store.remove_attempt(&email).await?;
+
+    let token = start_session(&state, &email, device).await?;
+    let new_jar = jar.add(auth::create_auth_cookie(token, state.secure_cookies));
+
+    Ok((new_jar, StatusCode::OK))
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Verify2FARequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
 }
@@ -1,18 +1,70 @@
 use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::validate_token};
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Role, TwoFactorKind, UserStoreError, VerifyTokens},
+    utils::{
+        audit::Audit,
+        auth::validate_token,
+        extractors::{AuthenticatedService, touch_session},
+    },
+};
 
+// only services get to check a user's token, so one that's leaked can't be probed by anyone
 pub async fn verify_token(
     State(state): State<AppState>,
+    _service: AuthenticatedService<VerifyTokens>,
+    audit: Audit,
     Json(request): Json<VerifyTokenRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    let _ = validate_token(state.banned_token_store, &request.token).await?;
+    let claims = validate_token(
+        state.banned_token_store.clone(),
+        state.user_store.clone(),
+        &audit,
+        &request.token,
+    )
+    .await?;
+    touch_session(&state, &claims).await;
 
-    Ok(StatusCode::OK.into_response())
+    let email: Email = claims
+        .subject
+        .parse()
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    audit.subject(&email);
+    // a token outliving its account isn't valid anymore
+    let user = match state.user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(e.into()),
+    };
+
+    let response = Json(VerifyTokenResponse {
+        subject: claims.subject,
+        expires_at: claims.expirary,
+        // as stored, the token's may be out of date
+        roles: user.roles.clone(),
+        requires_2fa: user.requires_2fa(),
+        two_factor_method: user.two_factor.as_ref().map(|method| method.kind()),
+    });
+
+    Ok((StatusCode::OK, response))
 }
 
 #[derive(Debug, PartialEq, Deserialize)]
 pub struct VerifyTokenRequest {
     pub token: String,
 }
+
+// clients that only check the status can keep ignoring the body
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct VerifyTokenResponse {
+    pub subject: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: usize,
+    pub roles: Vec<Role>,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    #[serde(rename = "twoFactorMethod")]
+    pub two_factor_method: Option<TwoFactorKind>,
+}
@@ -0,0 +1,237 @@
+use std::{
+    io::{self, SeekFrom},
+    path::PathBuf,
+};
+
+use async_trait::async_trait;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{
+        AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, BufWriter,
+    },
+    sync::Mutex,
+};
+
+use crate::domain::{AuditEvent, AuditSink, AuditSinkError, Email};
+
+/// Appends events to a file, one JSON object per line.
+#[derive(Debug)]
+pub struct FileAuditSink {
+    path: PathBuf,
+    // appends are serialized so lines from concurrent requests can't interleave
+    file: Mutex<File>,
+    // one rewrite at a time, as each picks up where the log ended when it started
+    rewrite: Mutex<()>,
+}
+
+impl FileAuditSink {
+    pub async fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            rewrite: Mutex::new(()),
+        })
+    }
+
+    // lines are written whole under the lock, so up to here there are only whole lines
+    async fn committed_len(&self) -> io::Result<u64> {
+        Ok(self.file.lock().await.metadata().await?.len())
+    }
+
+    async fn rewrite_without(&self, email: &Email) -> io::Result<()> {
+        let _rewrite = self.rewrite.lock().await;
+        let temp_path = self
+            .path
+            .with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        let mut temp = BufWriter::new(File::create(&temp_path).await?);
+
+        // most of the log is copied while requests keep recording, only what they add
+        // meanwhile is copied with the lock held
+        let len = self.committed_len().await?;
+        let file = File::open(&self.path).await?;
+        copy_anonymizing(file.take(len), &mut temp, email).await?;
+
+        let mut file = self.file.lock().await;
+        let mut rest = File::open(&self.path).await?;
+        rest.seek(SeekFrom::Start(len)).await?;
+        copy_anonymizing(rest, &mut temp, email).await?;
+        temp.flush().await?;
+        temp.get_ref().sync_all().await?;
+
+        tokio::fs::rename(&temp_path, &self.path).await?;
+        *file = OpenOptions::new().append(true).open(&self.path).await?;
+        Ok(())
+    }
+}
+
+async fn copy_anonymizing(
+    from: impl AsyncRead + Unpin,
+    to: &mut BufWriter<File>,
+    email: &Email,
+) -> io::Result<()> {
+    let mut lines = BufReader::new(from).lines();
+    while let Some(line) = lines.next_line().await? {
+        // only the user's lines are rewritten, everything else is kept as it was
+        let line = match serde_json::from_str::<AuditEvent>(&line) {
+            Ok(mut event) if event.email.as_deref() == Some(email.as_ref()) => {
+                event.anonymize();
+                serde_json::to_string(&event).map_err(io::Error::other)?
+            }
+            _ => line,
+        };
+        to.write_all(line.as_bytes()).await?;
+        to.write_all(b"\n").await?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl AuditSink for FileAuditSink {
+    async fn record(&self, event: AuditEvent) -> Result<(), AuditSinkError> {
+        let mut line = serde_json::to_vec(&event).map_err(|_| AuditSinkError::UnexpectedError)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().await;
+        file.write_all(&line)
+            .await
+            .map_err(|_| AuditSinkError::UnexpectedError)?;
+        file.flush()
+            .await
+            .map_err(|_| AuditSinkError::UnexpectedError)
+    }
+
+    async fn events_for(&self, email: &Email) -> Result<Vec<AuditEvent>, AuditSinkError> {
+        // the lock is let go of before reading, so requests can keep recording meanwhile
+        let len = self
+            .committed_len()
+            .await
+            .map_err(|_| AuditSinkError::UnexpectedError)?;
+        let file = File::open(&self.path)
+            .await
+            .map_err(|_| AuditSinkError::UnexpectedError)?;
+
+        // a line at a time, as the log only grows between rewrites, and a rewrite swaps in a
+        // new file rather than changing the one open here
+        let mut lines = BufReader::new(file.take(len)).lines();
+        let mut events = Vec::new();
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|_| AuditSinkError::UnexpectedError)?
+        {
+            // a line that doesn't parse, e.g. from an older version, isn't worth failing over
+            if let Ok(event) = serde_json::from_str::<AuditEvent>(&line)
+                && event.email.as_deref() == Some(email.as_ref())
+            {
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+
+    async fn forget(&self, email: &Email) -> Result<(), AuditSinkError> {
+        self.rewrite_without(email)
+            .await
+            .map_err(|_| AuditSinkError::UnexpectedError)
+    }
+
+    // writes already reach the OS as they're made, this gets them onto the disk too
+    async fn flush(&self) -> Result<(), AuditSinkError> {
+        self.file
+            .lock()
+            .await
+            .sync_all()
+            .await
+            .map_err(|_| AuditSinkError::UnexpectedError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{AuditEventKind, AuditOutcome};
+
+    use super::*;
+
+    fn event(email: &str) -> AuditEvent {
+        AuditEvent {
+            timestamp: 1_700_000_000,
+            kind: AuditEventKind::Login,
+            email: Some(email.to_string()),
+            actor: None,
+            request_id: None,
+            ip: Some("127.0.0.1".to_string()),
+            user_agent: None,
+            outcome: AuditOutcome::Success,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_are_appended_and_read_back() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let sink = FileAuditSink::open(&path).await.unwrap();
+
+        sink.record(event("a@b.com")).await.unwrap();
+        sink.record(event("c@d.com")).await.unwrap();
+        sink.record(event("a@b.com")).await.unwrap();
+        // reopening keeps what's there
+        let sink = FileAuditSink::open(&path).await.unwrap();
+        sink.record(event("a@b.com")).await.unwrap();
+
+        let events = sink.events_for(&"a@b.com".parse().unwrap()).await.unwrap();
+        let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(events, vec![event("a@b.com"); 3]);
+        assert_eq!(lines, 4);
+    }
+
+    #[tokio::test]
+    async fn test_events_for_skips_unparseable_lines() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "not json\n{\"kind\":\"unknown\"}\n").unwrap();
+        let sink = FileAuditSink::open(&path).await.unwrap();
+
+        sink.record(event("a@b.com")).await.unwrap();
+
+        let events = sink.events_for(&"a@b.com".parse().unwrap()).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(events, vec![event("a@b.com")]);
+    }
+
+    #[tokio::test]
+    async fn test_forget_anonymizes_only_that_users_events() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "not json\n").unwrap();
+        let sink = FileAuditSink::open(&path).await.unwrap();
+        sink.record(event("a@b.com")).await.unwrap();
+        sink.record(event("c@d.com")).await.unwrap();
+
+        sink.forget(&"a@b.com".parse().unwrap()).await.unwrap();
+        // recording carries on into the rewritten log
+        sink.record(event("a@b.com")).await.unwrap();
+
+        let forgotten = sink.events_for(&"a@b.com".parse().unwrap()).await.unwrap();
+        let kept = sink.events_for(&"c@d.com".parse().unwrap()).await.unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut anonymized = event("a@b.com");
+        anonymized.anonymize();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "not json");
+        assert_eq!(
+            serde_json::from_str::<AuditEvent>(lines[1]).unwrap(),
+            anonymized
+        );
+        assert_eq!(forgotten, vec![event("a@b.com")]);
+        assert_eq!(kept, vec![event("c@d.com")]);
+    }
+}
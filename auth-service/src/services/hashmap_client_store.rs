@@ -0,0 +1,177 @@
+use async_trait::async_trait;
+use dashmap::DashMap;
+
+use crate::domain::{
+    AuthorizationCode, ClientId, ClientStore, ClientStoreError, OAuthClient, PendingAuthorization,
+    ServiceClient,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct HashMapClientStore {
+    clients: DashMap<ClientId, OAuthClient>,
+    codes: DashMap<AuthorizationCode, PendingAuthorization>,
+    service_clients: DashMap<String, ServiceClient>,
+}
+
+#[async_trait]
+impl ClientStore for HashMapClientStore {
+    async fn add_client(&self, client: OAuthClient) -> Result<(), ClientStoreError> {
+        if self.clients.contains_key(&client.id) {
+            return Err(ClientStoreError::ClientAlreadyExists);
+        }
+
+        self.clients.insert(client.id.clone(), client);
+        Ok(())
+    }
+
+    async fn get_client(&self, id: &ClientId) -> Result<OAuthClient, ClientStoreError> {
+        self.clients
+            .get(id)
+            .map(|client| client.clone())
+            .ok_or(ClientStoreError::ClientNotFound)
+    }
+
+    async fn add_code(
+        &self,
+        code: AuthorizationCode,
+        pending: PendingAuthorization,
+    ) -> Result<(), ClientStoreError> {
+        self.codes.insert(code, pending);
+        Ok(())
+    }
+
+    async fn take_code(
+        &self,
+        code: &AuthorizationCode,
+    ) -> Result<PendingAuthorization, ClientStoreError> {
+        self.codes
+            .remove(code)
+            .map(|(_, pending)| pending)
+            .ok_or(ClientStoreError::CodeNotFound)
+    }
+
+    async fn add_service_client(&self, client: ServiceClient) -> Result<(), ClientStoreError> {
+        if self.service_clients.contains_key(&client.id) {
+            return Err(ClientStoreError::ClientAlreadyExists);
+        }
+
+        self.service_clients.insert(client.id.clone(), client);
+        Ok(())
+    }
+
+    async fn get_service_client(&self, id: &str) -> Result<ServiceClient, ClientStoreError> {
+        self.service_clients
+            .get(id)
+            .map(|client| client.clone())
+            .ok_or(ClientStoreError::ClientNotFound)
+    }
+
+    async fn remove_expired_codes(&self, now: i64) -> Result<usize, ClientStoreError> {
+        let mut removed = 0;
+        self.codes.retain(|_, pending| {
+            let expired = pending.expires_at < now;
+            removed += usize::from(expired);
+            !expired
+        });
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{ClientSecretHash, CodeChallenge};
+
+    use super::*;
+
+    fn client() -> OAuthClient {
+        OAuthClient {
+            id: ClientId::default(),
+            name: "app".to_string(),
+            redirect_uris: vec!["https://app.example.com/callback".to_string()],
+            owner: "a@b.com".parse().unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_client() {
+        let store = HashMapClientStore::default();
+        let client = client();
+
+        assert_eq!(
+            Err(ClientStoreError::ClientNotFound),
+            store.get_client(&client.id).await
+        );
+        assert_eq!(Ok(()), store.add_client(client.clone()).await);
+        assert_eq!(Ok(client.clone()), store.get_client(&client.id).await);
+        assert_eq!(
+            Err(ClientStoreError::ClientAlreadyExists),
+            store.add_client(client).await
+        );
+    }
+
+    fn pending(expires_at: i64) -> PendingAuthorization {
+        PendingAuthorization {
+            client_id: ClientId::default(),
+            redirect_uri: "https://app.example.com/callback".to_string(),
+            email: "a@b.com".parse().unwrap(),
+            scopes: vec!["openid".to_string()],
+            nonce: None,
+            code_challenge: CodeChallenge::of("verifier"),
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_codes_are_single_use() {
+        let store = HashMapClientStore::default();
+        let code = AuthorizationCode::generate();
+        let pending = pending(0);
+        store.add_code(code.clone(), pending.clone()).await.unwrap();
+
+        assert_eq!(Ok(pending), store.take_code(&code).await);
+        assert_eq!(
+            Err(ClientStoreError::CodeNotFound),
+            store.take_code(&code).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_expired_codes() {
+        let store = HashMapClientStore::default();
+        let expired = AuthorizationCode::generate();
+        let current = AuthorizationCode::generate();
+        store.add_code(expired.clone(), pending(99)).await.unwrap();
+        store.add_code(current.clone(), pending(100)).await.unwrap();
+
+        assert_eq!(Ok(1), store.remove_expired_codes(100).await);
+        assert_eq!(
+            Err(ClientStoreError::CodeNotFound),
+            store.take_code(&expired).await
+        );
+        assert!(store.take_code(&current).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_service_client() {
+        let store = HashMapClientStore::default();
+        let client = ServiceClient {
+            id: "app-service".to_string(),
+            secret_hash: ClientSecretHash::of("secret"),
+            scopes: vec![],
+        };
+
+        assert_eq!(
+            Err(ClientStoreError::ClientNotFound),
+            store.get_service_client("app-service").await
+        );
+        assert_eq!(Ok(()), store.add_service_client(client.clone()).await);
+        assert_eq!(
+            Ok(client.clone()),
+            store.get_service_client("app-service").await
+        );
+        assert_eq!(
+            Err(ClientStoreError::ClientAlreadyExists),
+            store.add_service_client(client).await
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use async_trait::async_trait;
+use dashmap::DashMap;
+
+use crate::domain::{
+    FederatedLoginStore, FederatedLoginStoreError, FederationState, PendingFederatedLogin,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct HashMapFederatedLoginStore {
+    logins: DashMap<FederationState, PendingFederatedLogin>,
+}
+
+#[async_trait]
+impl FederatedLoginStore for HashMapFederatedLoginStore {
+    async fn add_login(
+        &self,
+        state: FederationState,
+        pending: PendingFederatedLogin,
+    ) -> Result<(), FederatedLoginStoreError> {
+        self.logins.insert(state, pending);
+        Ok(())
+    }
+
+    async fn take_login(
+        &self,
+        state: &FederationState,
+    ) -> Result<PendingFederatedLogin, FederatedLoginStoreError> {
+        self.logins
+            .remove(state)
+            .map(|(_, pending)| pending)
+            .ok_or(FederatedLoginStoreError::LoginNotFound)
+    }
+
+    async fn remove_expired_logins(&self, now: i64) -> Result<usize, FederatedLoginStoreError> {
+        let mut removed = 0;
+        self.logins.retain(|_, pending| {
+            let expired = pending.expires_at < now;
+            removed += usize::from(expired);
+            !expired
+        });
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_logins_are_single_use() {
+        let store = HashMapFederatedLoginStore::default();
+        let state = FederationState::generate();
+        let pending = PendingFederatedLogin::new("google".to_string(), None, 0);
+        store
+            .add_login(state.clone(), pending.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(Ok(pending), store.take_login(&state).await);
+        assert_eq!(
+            Err(FederatedLoginStoreError::LoginNotFound),
+            store.take_login(&state).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_expired_logins() {
+        let store = HashMapFederatedLoginStore::default();
+        let expired = FederationState::generate();
+        let current = FederationState::generate();
+        store
+            .add_login(
+                expired.clone(),
+                PendingFederatedLogin::new("google".to_string(), None, 99),
+            )
+            .await
+            .unwrap();
+        store
+            .add_login(
+                current.clone(),
+                PendingFederatedLogin::new("google".to_string(), None, 100),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(Ok(1), store.remove_expired_logins(100).await);
+        assert_eq!(
+            Err(FederatedLoginStoreError::LoginNotFound),
+            store.take_login(&expired).await
+        );
+        assert!(store.take_login(&current).await.is_ok());
+    }
+}
@@ -0,0 +1,212 @@
+use async_trait::async_trait;
+use dashmap::{DashMap, mapref::entry::Entry};
+
+use crate::domain::{
+    Email, EmailCode, LoginAttemptId, LoginAttemptStore, LoginAttemptStoreError,
+    MAX_SECOND_FACTOR_GUESSES,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct HashMapLoginAttemptStore {
+    attempts: DashMap<Email, PendingAttempt>,
+}
+
+#[derive(Clone, Debug)]
+struct PendingAttempt {
+    id: LoginAttemptId,
+    email_code: Option<EmailCode>,
+    expires_at: i64,
+    guesses: u32,
+}
+
+#[async_trait]
+impl LoginAttemptStore for HashMapLoginAttemptStore {
+    async fn add_attempt(
+        &self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        email_code: Option<EmailCode>,
+        expires_at: i64,
+    ) -> Result<(), LoginAttemptStoreError> {
+        // a newer login replaces any older pending attempt, and the code sent for it
+        let attempt = PendingAttempt {
+            id: login_attempt_id,
+            email_code,
+            expires_at,
+            guesses: 0,
+        };
+        self.attempts.insert(email, attempt);
+        Ok(())
+    }
+
+    async fn get_attempt(&self, email: &Email) -> Result<LoginAttemptId, LoginAttemptStoreError> {
+        self.attempts
+            .get(email)
+            .map(|attempt| attempt.id.clone())
+            .ok_or(LoginAttemptStoreError::LoginAttemptNotFound)
+    }
+
+    async fn check_attempt(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        now: i64,
+    ) -> Result<Option<EmailCode>, LoginAttemptStoreError> {
+        let Entry::Occupied(mut entry) = self.attempts.entry(email.clone()) else {
+            return Err(LoginAttemptStoreError::LoginAttemptNotFound);
+        };
+        let attempt = entry.get_mut();
+        if attempt.expires_at <= now || attempt.guesses >= MAX_SECOND_FACTOR_GUESSES {
+            entry.remove();
+            return Err(LoginAttemptStoreError::LoginAttemptNotFound);
+        }
+        // only whoever got past the password knows the id, so anyone else's guesses don't
+        // use the attempt up
+        if attempt.id != *login_attempt_id {
+            return Err(LoginAttemptStoreError::LoginAttemptNotFound);
+        }
+
+        attempt.guesses += 1;
+        Ok(attempt.email_code.clone())
+    }
+
+    async fn remove_attempt(&self, email: &Email) -> Result<(), LoginAttemptStoreError> {
+        self.attempts
+            .remove(email)
+            .map(|_| ())
+            .ok_or(LoginAttemptStoreError::LoginAttemptNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_and_get_attempt() {
+        let store = HashMapLoginAttemptStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        let id = LoginAttemptId::default();
+
+        assert_eq!(
+            Ok(()),
+            store
+                .add_attempt(email.clone(), id.clone(), None, 600)
+                .await
+        );
+        assert_eq!(Ok(id), store.get_attempt(&email).await);
+    }
+
+    #[tokio::test]
+    async fn test_newer_attempt_replaces_older() {
+        let store = HashMapLoginAttemptStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        let newer = LoginAttemptId::default();
+
+        store
+            .add_attempt(email.clone(), LoginAttemptId::default(), None, 600)
+            .await
+            .unwrap();
+        store
+            .add_attempt(email.clone(), newer.clone(), None, 600)
+            .await
+            .unwrap();
+
+        assert_eq!(Ok(newer), store.get_attempt(&email).await);
+    }
+
+    #[tokio::test]
+    async fn test_remove_attempt() {
+        let store = HashMapLoginAttemptStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        store
+            .add_attempt(email.clone(), LoginAttemptId::default(), None, 600)
+            .await
+            .unwrap();
+
+        assert_eq!(Ok(()), store.remove_attempt(&email).await);
+        assert_eq!(
+            Err(LoginAttemptStoreError::LoginAttemptNotFound),
+            store.get_attempt(&email).await
+        );
+        assert_eq!(
+            Err(LoginAttemptStoreError::LoginAttemptNotFound),
+            store.remove_attempt(&email).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_attempt_runs_out_of_guesses() {
+        let store = HashMapLoginAttemptStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        let id = LoginAttemptId::default();
+        store
+            .add_attempt(email.clone(), id.clone(), None, 600)
+            .await
+            .unwrap();
+
+        for _ in 0..MAX_SECOND_FACTOR_GUESSES {
+            assert_eq!(Ok(None), store.check_attempt(&email, &id, 0).await);
+        }
+        assert_eq!(
+            Err(LoginAttemptStoreError::LoginAttemptNotFound),
+            store.check_attempt(&email, &id, 0).await
+        );
+        assert_eq!(
+            Err(LoginAttemptStoreError::LoginAttemptNotFound),
+            store.get_attempt(&email).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_attempt_expires() {
+        let store = HashMapLoginAttemptStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        let id = LoginAttemptId::default();
+        store
+            .add_attempt(email.clone(), id.clone(), None, 600)
+            .await
+            .unwrap();
+
+        assert_eq!(Ok(None), store.check_attempt(&email, &id, 599).await);
+        assert_eq!(
+            Err(LoginAttemptStoreError::LoginAttemptNotFound),
+            store.check_attempt(&email, &id, 600).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_attempt_returns_email_code() {
+        let store = HashMapLoginAttemptStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        let id = LoginAttemptId::default();
+        let code = EmailCode::generate();
+        store
+            .add_attempt(email.clone(), id.clone(), Some(code.clone()), 600)
+            .await
+            .unwrap();
+
+        assert_eq!(Ok(Some(code)), store.check_attempt(&email, &id, 0).await);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_id_doesnt_use_up_guesses() {
+        let store = HashMapLoginAttemptStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        let id = LoginAttemptId::default();
+        store
+            .add_attempt(email.clone(), id.clone(), None, 600)
+            .await
+            .unwrap();
+
+        for _ in 0..=MAX_SECOND_FACTOR_GUESSES {
+            assert_eq!(
+                Err(LoginAttemptStoreError::LoginAttemptNotFound),
+                store
+                    .check_attempt(&email, &LoginAttemptId::default(), 0)
+                    .await
+            );
+        }
+        assert_eq!(Ok(None), store.check_attempt(&email, &id, 0).await);
+    }
+}
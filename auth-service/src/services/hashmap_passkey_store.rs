@@ -0,0 +1,233 @@
+use async_trait::async_trait;
+use dashmap::DashMap;
+
+use crate::domain::{
+    Email, PasskeyChallenge, PasskeyCredential, PasskeyStore, PasskeyStoreError, PendingCeremony,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct HashMapPasskeyStore {
+    credentials: DashMap<Email, Vec<PasskeyCredential>>,
+    challenges: DashMap<PasskeyChallenge, PendingCeremony>,
+}
+
+#[async_trait]
+impl PasskeyStore for HashMapPasskeyStore {
+    async fn add_credential(
+        &self,
+        email: Email,
+        credential: PasskeyCredential,
+    ) -> Result<(), PasskeyStoreError> {
+        // credential ids are unique across every user, not just this one
+        let taken = self.credentials.iter().any(|entry| {
+            entry
+                .value()
+                .iter()
+                .any(|existing| existing.id == credential.id)
+        });
+        if taken {
+            return Err(PasskeyStoreError::CredentialAlreadyExists);
+        }
+
+        self.credentials.entry(email).or_default().push(credential);
+        Ok(())
+    }
+
+    async fn get_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PasskeyCredential>, PasskeyStoreError> {
+        Ok(self
+            .credentials
+            .get(email)
+            .map(|credentials| credentials.clone())
+            .unwrap_or_default())
+    }
+
+    async fn update_sign_count(
+        &self,
+        email: &Email,
+        credential_id: &[u8],
+        sign_count: u32,
+    ) -> Result<(), PasskeyStoreError> {
+        let mut credentials = self
+            .credentials
+            .get_mut(email)
+            .ok_or(PasskeyStoreError::CredentialNotFound)?;
+        let credential = credentials
+            .iter_mut()
+            .find(|credential| credential.id == credential_id)
+            .ok_or(PasskeyStoreError::CredentialNotFound)?;
+
+        credential.sign_count = sign_count;
+        Ok(())
+    }
+
+    async fn delete_credentials(&self, email: &Email) -> Result<(), PasskeyStoreError> {
+        self.credentials.remove(email);
+        Ok(())
+    }
+
+    async fn add_challenge(
+        &self,
+        challenge: PasskeyChallenge,
+        pending: PendingCeremony,
+    ) -> Result<(), PasskeyStoreError> {
+        self.challenges.insert(challenge, pending);
+        Ok(())
+    }
+
+    async fn take_challenge(
+        &self,
+        challenge: &PasskeyChallenge,
+    ) -> Result<PendingCeremony, PasskeyStoreError> {
+        self.challenges
+            .remove(challenge)
+            .map(|(_, pending)| pending)
+            .ok_or(PasskeyStoreError::ChallengeNotFound)
+    }
+
+    async fn remove_expired_challenges(&self, now: i64) -> Result<usize, PasskeyStoreError> {
+        let mut removed = 0;
+        self.challenges.retain(|_, pending| {
+            let expired = pending.expires_at < now;
+            removed += usize::from(expired);
+            !expired
+        });
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::Ceremony;
+
+    use super::*;
+
+    fn credential(id: &[u8]) -> PasskeyCredential {
+        PasskeyCredential {
+            id: id.to_vec(),
+            public_key: vec![4; 65],
+            sign_count: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_credentials() {
+        let store = HashMapPasskeyStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+
+        assert_eq!(Ok(vec![]), store.get_credentials(&email).await);
+
+        store
+            .add_credential(email.clone(), credential(b"one"))
+            .await
+            .unwrap();
+        store
+            .add_credential(email.clone(), credential(b"two"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Ok(vec![credential(b"one"), credential(b"two")]),
+            store.get_credentials(&email).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_duplicate_credential_fails() {
+        let store = HashMapPasskeyStore::default();
+        store
+            .add_credential("a@b.com".parse().unwrap(), credential(b"one"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Err(PasskeyStoreError::CredentialAlreadyExists),
+            store
+                .add_credential("b@a.com".parse().unwrap(), credential(b"one"))
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_sign_count() {
+        let store = HashMapPasskeyStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        store
+            .add_credential(email.clone(), credential(b"one"))
+            .await
+            .unwrap();
+
+        assert_eq!(Ok(()), store.update_sign_count(&email, b"one", 7).await);
+        assert_eq!(
+            7,
+            store.get_credentials(&email).await.unwrap()[0].sign_count
+        );
+        assert_eq!(
+            Err(PasskeyStoreError::CredentialNotFound),
+            store.update_sign_count(&email, b"two", 7).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_credentials() {
+        let store = HashMapPasskeyStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        store
+            .add_credential(email.clone(), credential(b"one"))
+            .await
+            .unwrap();
+
+        assert_eq!(Ok(()), store.delete_credentials(&email).await);
+        assert_eq!(Ok(vec![]), store.get_credentials(&email).await);
+    }
+
+    #[tokio::test]
+    async fn test_challenges_are_single_use() {
+        let store = HashMapPasskeyStore::default();
+        let challenge = PasskeyChallenge::generate();
+        let pending = PendingCeremony {
+            email: "a@b.com".parse().unwrap(),
+            ceremony: Ceremony::Registration,
+            expires_at: 0,
+        };
+        store
+            .add_challenge(challenge.clone(), pending.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(Ok(pending), store.take_challenge(&challenge).await);
+        assert_eq!(
+            Err(PasskeyStoreError::ChallengeNotFound),
+            store.take_challenge(&challenge).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_expired_challenges() {
+        let store = HashMapPasskeyStore::default();
+        let pending = |expires_at| PendingCeremony {
+            email: "a@b.com".parse().unwrap(),
+            ceremony: Ceremony::Authentication,
+            expires_at,
+        };
+        let expired = PasskeyChallenge::generate();
+        let current = PasskeyChallenge::generate();
+        store
+            .add_challenge(expired.clone(), pending(99))
+            .await
+            .unwrap();
+        store
+            .add_challenge(current.clone(), pending(100))
+            .await
+            .unwrap();
+
+        assert_eq!(Ok(1), store.remove_expired_challenges(100).await);
+        assert_eq!(
+            Err(PasskeyStoreError::ChallengeNotFound),
+            store.take_challenge(&expired).await
+        );
+        assert!(store.take_challenge(&current).await.is_ok());
+    }
+}
@@ -0,0 +1,166 @@
+use async_trait::async_trait;
+use dashmap::DashMap;
+
+use crate::domain::{Email, Session, SessionId, SessionStore, SessionStoreError};
+
+#[derive(Clone, Debug, Default)]
+pub struct HashMapSessionStore {
+    sessions: DashMap<SessionId, Session>,
+}
+
+#[async_trait]
+impl SessionStore for HashMapSessionStore {
+    async fn add_session(&self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        Ok(self
+            .sessions
+            .iter()
+            .filter(|session| session.email == *email)
+            .map(|session| session.clone())
+            .collect())
+    }
+
+    async fn touch_session(&self, id: &SessionId, now: i64) -> Result<(), SessionStoreError> {
+        let mut session = self
+            .sessions
+            .get_mut(id)
+            .ok_or(SessionStoreError::SessionNotFound)?;
+        session.last_seen_at = now;
+        Ok(())
+    }
+
+    async fn remove_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .remove(id)
+            .map(|(_, session)| session)
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn remove_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let ids: Vec<SessionId> = self
+            .sessions
+            .iter()
+            .filter(|session| session.email == *email)
+            .map(|session| session.id.clone())
+            .collect();
+
+        Ok(ids
+            .iter()
+            .filter_map(|id| self.sessions.remove(id).map(|(_, session)| session))
+            .collect())
+    }
+
+    async fn remove_expired_sessions(&self, now: i64) -> Result<usize, SessionStoreError> {
+        let mut removed = 0;
+        self.sessions.retain(|_, session| {
+            let expired = session.is_expired(now);
+            removed += usize::from(expired);
+            !expired
+        });
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::DeviceInfo;
+
+    use super::*;
+
+    fn session(id: &str, email: &str) -> Session {
+        Session {
+            id: SessionId::from_token_id(id.to_string()),
+            email: email.parse().unwrap(),
+            token: format!("token-{id}"),
+            device: DeviceInfo::default(),
+            created_at: 0,
+            last_seen_at: 0,
+            expires_at: 600,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_sessions() {
+        let store = HashMapSessionStore::default();
+        store.add_session(session("1", "a@b.com")).await.unwrap();
+        store.add_session(session("2", "c@d.com")).await.unwrap();
+
+        assert_eq!(
+            Ok(vec![session("1", "a@b.com")]),
+            store.get_sessions(&"a@b.com".parse().unwrap()).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_touch_session() {
+        let store = HashMapSessionStore::default();
+        let id = SessionId::from_token_id("1".to_string());
+        store.add_session(session("1", "a@b.com")).await.unwrap();
+
+        assert_eq!(Ok(()), store.touch_session(&id, 42).await);
+        let sessions = store
+            .get_sessions(&"a@b.com".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(sessions[0].last_seen_at, 42);
+
+        let unknown = SessionId::from_token_id("2".to_string());
+        assert_eq!(
+            Err(SessionStoreError::SessionNotFound),
+            store.touch_session(&unknown, 42).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_sessions() {
+        let store = HashMapSessionStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        store.add_session(session("1", "a@b.com")).await.unwrap();
+        store.add_session(session("2", "a@b.com")).await.unwrap();
+        store.add_session(session("3", "c@d.com")).await.unwrap();
+
+        let id = SessionId::from_token_id("1".to_string());
+        assert_eq!(Ok(session("1", "a@b.com")), store.remove_session(&id).await);
+        assert_eq!(
+            Err(SessionStoreError::SessionNotFound),
+            store.remove_session(&id).await
+        );
+
+        assert_eq!(
+            Ok(vec![session("2", "a@b.com")]),
+            store.remove_sessions(&email).await
+        );
+        assert_eq!(Ok(vec![]), store.get_sessions(&email).await);
+        assert_eq!(
+            1,
+            store
+                .get_sessions(&"c@d.com".parse().unwrap())
+                .await
+                .unwrap()
+                .len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_expired_sessions() {
+        let store = HashMapSessionStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        store.add_session(session("1", "a@b.com")).await.unwrap();
+        store
+            .add_session(Session {
+                expires_at: 601,
+                ..session("2", "a@b.com")
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(Ok(1), store.remove_expired_sessions(600).await);
+        let sessions = store.get_sessions(&email).await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].expires_at, 601);
+    }
+}
@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use dashmap::DashMap;
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use crate::domain::{Email, HashedPassword, Password, User, UserChange, UserStore, UserStoreError};
 
 #[derive(Clone, Default)]
 pub struct HashMapUserStore {
@@ -10,15 +10,17 @@ pub struct HashMapUserStore {
 
 #[async_trait]
 impl UserStore for HashMapUserStore {
+    #[tracing::instrument(skip_all, fields(email = user.email.as_ref()))]
     async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
         if self.users.contains_key(&user.email) {
             return Err(UserStoreError::UserAlreadyExists);
         } else {
-            self.users.insert((&user.email).clone(), user);
+            self.users.insert(user.email.clone(), user);
             Ok(())
         }
     }
 
+    #[tracing::instrument(skip_all, fields(email = email.as_ref()))]
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         self.users
             .get(email)
@@ -26,22 +28,131 @@ impl UserStore for HashMapUserStore {
             .ok_or_else(|| UserStoreError::UserNotFound)
     }
 
+    #[tracing::instrument(skip_all, fields(email = email.as_ref()))]
+    async fn modify_user(
+        &self,
+        email: &Email,
+        change: UserChange<'_>,
+    ) -> Result<User, UserStoreError> {
+        // holding the entry, so other writes wait until the change is in
+        let mut user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        change(&mut user);
+        Ok(user.clone())
+    }
+
+    #[tracing::instrument(skip_all, fields(email = email.as_ref()))]
     async fn validate_user(
         &self,
         email: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
         let user = self.get_user(email).await?;
-        if user.password != *password {
-            Err(UserStoreError::InvalidCredentials)
+        let candidate = password.clone();
+        let (valid, rehashed) = tokio::task::spawn_blocking(move || match user.legacy_password {
+            None => (user.password.verify(&candidate), None),
+            // now that the password is known, it can be hashed the way native ones are
+            Some(hash) => {
+                let valid = hash.verify(&candidate);
+                (valid, valid.then(|| HashedPassword::new(&candidate)))
+            }
+        })
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+        if !valid {
+            return Err(UserStoreError::InvalidCredentials);
+        }
+
+        if let Some(rehashed) = rehashed
+            && let Some(mut user) = self.users.get_mut(email)
+            && user.legacy_password.is_some()
+        {
+            user.set_password(rehashed);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(email = email.as_ref()))]
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.users
+            .remove(email)
+            .map(|_| ())
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn list_users(&self) -> Result<Vec<User>, UserStoreError> {
+        let mut users: Vec<User> = self.users.iter().map(|user| user.clone()).collect();
+        users.sort_by(|a, b| a.email.as_ref().cmp(b.email.as_ref()));
+        Ok(users)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn search_users(&self, query: &str) -> Result<Vec<User>, UserStoreError> {
+        let query = query.to_lowercase();
+        let mut users = self.list_users().await?;
+        users.retain(|user| user.email.as_ref().to_lowercase().contains(&query));
+        Ok(users)
+    }
+
+    #[tracing::instrument(skip(self, email), fields(email = email.as_ref()))]
+    async fn set_locked(&self, email: &Email, locked: bool) -> Result<(), UserStoreError> {
+        let mut user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        if locked {
+            user.lock();
         } else {
+            user.unlock();
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(email = email.as_ref()))]
+    async fn use_totp_code(
+        &self,
+        email: &Email,
+        code: &str,
+        unix_time: u64,
+    ) -> Result<(), UserStoreError> {
+        // holding the entry, so no other request sees the step before it's marked used
+        let mut user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        if user.use_totp_code(code, unix_time) {
             Ok(())
+        } else {
+            Err(UserStoreError::InvalidCredentials)
         }
     }
+
+    #[tracing::instrument(skip_all, fields(email = email.as_ref()))]
+    async fn use_recovery_code(&self, email: &Email, code: &str) -> Result<(), UserStoreError> {
+        let mut user = self
+            .users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        if user.consume_recovery_code(code) {
+            Ok(())
+        } else {
+            Err(UserStoreError::InvalidCredentials)
+        }
+    }
+
+    // in memory, so always there
+    async fn health_check(&self) -> Result<(), UserStoreError> {
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::domain::{LegacyPasswordHash, TwoFactorMethod};
+
     use super::*;
 
     #[tokio::test]
@@ -49,11 +160,11 @@ mod tests {
         let store = HashMapUserStore {
             ..Default::default()
         };
-        let user1 = User {
-            email: "a@b.com".parse().unwrap(),
-            password: "password".parse().unwrap(),
-            requires_2fa: true,
-        };
+        let user1 = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            Some(TwoFactorMethod::Email),
+        );
 
         assert_eq!(Ok(()), store.add_user(user1.clone()).await);
         assert_eq!(
@@ -67,11 +178,11 @@ mod tests {
         let store = HashMapUserStore {
             ..Default::default()
         };
-        let user1 = User {
-            email: "a@b.com".parse().unwrap(),
-            password: "password".parse().unwrap(),
-            requires_2fa: true,
-        };
+        let user1 = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            Some(TwoFactorMethod::Email),
+        );
         store.add_user(user1.clone()).await.unwrap();
 
         assert_eq!(Ok(user1), store.get_user(&"a@b.com".parse().unwrap()).await);
@@ -82,20 +193,82 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_validate_user() {
+    async fn test_modify_user() {
         let store = HashMapUserStore {
             ..Default::default()
         };
-        let user1 = User {
-            email: "a@b.com".parse().unwrap(),
-            password: "password".parse().unwrap(),
-            requires_2fa: true,
+        let user1 = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            None,
+        );
+
+        assert_eq!(
+            Err(UserStoreError::UserNotFound),
+            store
+                .modify_user(&user1.email, Box::new(|user| user.revoke_tokens()))
+                .await
+        );
+
+        store.add_user(user1.clone()).await.unwrap();
+        let modified = store
+            .modify_user(&user1.email, Box::new(|user| user.revoke_tokens()))
+            .await
+            .unwrap();
+
+        assert_eq!(modified.token_version, user1.token_version + 1);
+        assert_eq!(Ok(modified), store.get_user(&user1.email).await);
+    }
+
+    #[tokio::test]
+    async fn test_modify_user_keeps_concurrent_changes() {
+        let store = std::sync::Arc::new(HashMapUserStore::default());
+        let user = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            None,
+        );
+        store.add_user(user.clone()).await.unwrap();
+
+        let tasks = (0..20).map(|i| {
+            let (store, email) = (store.clone(), user.email.clone());
+            tokio::spawn(async move {
+                if i % 2 == 0 {
+                    store.set_locked(&email, true).await
+                } else {
+                    store
+                        .modify_user(&email, Box::new(|user| user.revoke_tokens()))
+                        .await
+                        .map(|_| ())
+                }
+            })
+        });
+        for task in tasks.collect::<Vec<_>>() {
+            task.await.unwrap().unwrap();
+        }
+
+        let user_after = store.get_user(&user.email).await.unwrap();
+        assert!(user_after.locked);
+        assert_eq!(user_after.token_version, user.token_version + 20);
+    }
+
+    #[tokio::test]
+    async fn test_validate_user() {
+        let store = HashMapUserStore {
+            ..Default::default()
         };
+        let user1 = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            Some(TwoFactorMethod::Email),
+        );
         store.add_user(user1.clone()).await.unwrap();
 
         assert_eq!(
             Ok(()),
-            store.validate_user(&user1.email, &user1.password).await
+            store
+                .validate_user(&user1.email, &"password".parse().unwrap())
+                .await
         );
         assert_eq!(
             Err(UserStoreError::InvalidCredentials),
@@ -104,4 +277,149 @@ mod tests {
                 .await
         )
     }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let store = HashMapUserStore {
+            ..Default::default()
+        };
+        let user1 = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            Some(TwoFactorMethod::Email),
+        );
+        store.add_user(user1.clone()).await.unwrap();
+
+        assert_eq!(Ok(()), store.delete_user(&user1.email).await);
+        assert_eq!(
+            Err(UserStoreError::UserNotFound),
+            store.get_user(&user1.email).await
+        );
+        assert_eq!(
+            Err(UserStoreError::UserNotFound),
+            store.delete_user(&user1.email).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_and_search_users() {
+        let store = HashMapUserStore::default();
+        for email in ["c@example.com", "a@example.com", "b@other.com"] {
+            let user = User::new(email.parse().unwrap(), "password".parse().unwrap(), None);
+            store.add_user(user).await.unwrap();
+        }
+
+        let emails = |users: Vec<User>| {
+            users
+                .iter()
+                .map(|user| user.email.as_ref().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            emails(store.list_users().await.unwrap()),
+            ["a@example.com", "b@other.com", "c@example.com"]
+        );
+        assert_eq!(
+            emails(store.search_users("EXAMPLE").await.unwrap()),
+            ["a@example.com", "c@example.com"]
+        );
+        assert!(store.search_users("nobody").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_locked() {
+        let store = HashMapUserStore::default();
+        let user = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            None,
+        );
+        store.add_user(user.clone()).await.unwrap();
+
+        store.set_locked(&user.email, true).await.unwrap();
+        let locked = store.get_user(&user.email).await.unwrap();
+        assert!(locked.locked);
+        assert_eq!(locked.token_version, user.token_version + 1);
+
+        store.set_locked(&user.email, false).await.unwrap();
+        assert!(!store.get_user(&user.email).await.unwrap().locked);
+
+        assert_eq!(
+            Err(UserStoreError::UserNotFound),
+            store.set_locked(&"c@d.com".parse().unwrap(), true).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_user_upgrades_legacy_password() {
+        let store = HashMapUserStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        let hash = LegacyPasswordHash::parse(&bcrypt::hash("password123", 4).unwrap()).unwrap();
+        store
+            .add_user(User::with_legacy_password(email.clone(), hash))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Err(UserStoreError::InvalidCredentials),
+            store
+                .validate_user(&email, &"password124".parse().unwrap())
+                .await
+        );
+        assert!(
+            store
+                .get_user(&email)
+                .await
+                .unwrap()
+                .legacy_password
+                .is_some()
+        );
+
+        let password: Password = "password123".parse().unwrap();
+        assert_eq!(Ok(()), store.validate_user(&email, &password).await);
+        let user = store.get_user(&email).await.unwrap();
+        assert_eq!(user.legacy_password, None);
+        assert!(user.password.verify(&password));
+        assert_eq!(Ok(()), store.validate_user(&email, &password).await);
+    }
+
+    #[tokio::test]
+    async fn test_use_recovery_code() {
+        let store = HashMapUserStore::default();
+        let mut user = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            None,
+        );
+        let codes = user.reset_recovery_codes();
+        store.add_user(user.clone()).await.unwrap();
+
+        assert_eq!(
+            Ok(()),
+            store
+                .use_recovery_code(&user.email, codes[0].as_ref())
+                .await
+        );
+        assert_eq!(
+            Err(UserStoreError::InvalidCredentials),
+            store
+                .use_recovery_code(&user.email, codes[0].as_ref())
+                .await
+        );
+        assert_eq!(
+            store
+                .get_user(&user.email)
+                .await
+                .unwrap()
+                .recovery_codes
+                .len(),
+            codes.len() - 1
+        );
+        assert_eq!(
+            Err(UserStoreError::UserNotFound),
+            store
+                .use_recovery_code(&"c@d.com".parse().unwrap(), codes[1].as_ref())
+                .await
+        );
+    }
 }
@@ -18,6 +18,8 @@ impl HashSetTokenStore {
 
 #[async_trait]
 impl BannedTokenStore for HashSetTokenStore {
+    // tokens are credentials, so never recorded
+    #[tracing::instrument(skip_all)]
     async fn add_token(&self, token: &str) -> Result<(), TokenStoreError> {
         if token.is_empty() {
             Err(TokenStoreError::MissingToken)
@@ -27,6 +29,7 @@ impl BannedTokenStore for HashSetTokenStore {
         }
     }
 
+    #[tracing::instrument(skip_all)]
     async fn check_token(&self, token: &str) -> Result<bool, TokenStoreError> {
         if token.is_empty() {
             Err(TokenStoreError::MissingToken)
@@ -34,6 +37,20 @@ impl BannedTokenStore for HashSetTokenStore {
             Ok(self.banned_tokens.contains(token))
         }
     }
+
+    #[tracing::instrument(skip_all)]
+    async fn list_tokens(&self) -> Result<Vec<String>, TokenStoreError> {
+        Ok(self
+            .banned_tokens
+            .iter()
+            .map(|token| token.clone())
+            .collect())
+    }
+
+    // in memory, so always there
+    async fn health_check(&self) -> Result<(), TokenStoreError> {
+        Ok(())
+    }
 }
 
 // for tests, this token store should not validate tokens - that duplicates responsibility
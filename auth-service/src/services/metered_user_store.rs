@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    domain::{Email, Password, User, UserChange, UserStore, UserStoreError},
+    utils::metrics::Metrics,
+};
+
+/// Counts the errors another user store returns, by type, leaving the results untouched.
+pub struct MeteredUserStore {
+    inner: Arc<dyn UserStore + Send + Sync>,
+    metrics: Arc<Metrics>,
+}
+
+impl MeteredUserStore {
+    pub fn new(inner: Arc<dyn UserStore + Send + Sync>, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    fn observe<T>(&self, result: Result<T, UserStoreError>) -> Result<T, UserStoreError> {
+        if let Err(error) = &result {
+            self.metrics.user_store_error(error);
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl UserStore for MeteredUserStore {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        self.observe(self.inner.add_user(user).await)
+    }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        self.observe(self.inner.get_user(email).await)
+    }
+
+    async fn modify_user(
+        &self,
+        email: &Email,
+        change: UserChange<'_>,
+    ) -> Result<User, UserStoreError> {
+        self.observe(self.inner.modify_user(email, change).await)
+    }
+
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        self.observe(self.inner.validate_user(email, password).await)
+    }
+
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.observe(self.inner.delete_user(email).await)
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, UserStoreError> {
+        self.observe(self.inner.list_users().await)
+    }
+
+    async fn search_users(&self, query: &str) -> Result<Vec<User>, UserStoreError> {
+        self.observe(self.inner.search_users(query).await)
+    }
+
+    async fn set_locked(&self, email: &Email, locked: bool) -> Result<(), UserStoreError> {
+        self.observe(self.inner.set_locked(email, locked).await)
+    }
+
+    async fn use_totp_code(
+        &self,
+        email: &Email,
+        code: &str,
+        unix_time: u64,
+    ) -> Result<(), UserStoreError> {
+        self.observe(self.inner.use_totp_code(email, code, unix_time).await)
+    }
+
+    async fn use_recovery_code(&self, email: &Email, code: &str) -> Result<(), UserStoreError> {
+        self.observe(self.inner.use_recovery_code(email, code).await)
+    }
+
+    async fn health_check(&self) -> Result<(), UserStoreError> {
+        self.observe(self.inner.health_check().await)
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use crate::domain::{Email, EmailClient, EmailClientError};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SentEmail {
+    pub recipient: Email,
+    pub subject: String,
+    pub content: String,
+}
+
+// enough for any test, without growing for as long as the process runs
+const MAX_OUTBOX_LEN: usize = 100;
+
+/// Keeps emails instead of delivering them, so tests can read them back. Only the latest
+/// `MAX_OUTBOX_LEN` are kept.
+#[derive(Debug, Default)]
+pub struct MockEmailClient {
+    outbox: Mutex<Vec<SentEmail>>,
+}
+
+impl MockEmailClient {
+    pub fn sent_emails(&self) -> Vec<SentEmail> {
+        self.outbox
+            .lock()
+            .map(|outbox| outbox.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl EmailClient for MockEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<(), EmailClientError> {
+        // the content carries login links, so it's never logged
+        tracing::info!(subject, "sending email");
+
+        let mut outbox = self
+            .outbox
+            .lock()
+            .map_err(|_| EmailClientError::UnexpectedError)?;
+        if outbox.len() >= MAX_OUTBOX_LEN {
+            outbox.remove(0);
+        }
+        outbox.push(SentEmail {
+            recipient: recipient.clone(),
+            subject: subject.to_string(),
+            content: content.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<(), EmailClientError> {
+        // a panic while sending leaves the outbox unusable
+        self.outbox
+            .lock()
+            .map(|_| ())
+            .map_err(|_| EmailClientError::UnexpectedError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_email_is_recorded() {
+        let client = MockEmailClient::default();
+        let recipient: Email = "a@b.com".parse().unwrap();
+
+        assert_eq!(
+            Ok(()),
+            client.send_email(&recipient, "subject", "content").await
+        );
+        assert_eq!(
+            client.sent_emails(),
+            vec![SentEmail {
+                recipient,
+                subject: "subject".to_string(),
+                content: "content".to_string(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_outbox_keeps_latest_emails() {
+        let client = MockEmailClient::default();
+        let recipient: Email = "a@b.com".parse().unwrap();
+
+        for i in 0..=MAX_OUTBOX_LEN {
+            client
+                .send_email(&recipient, &i.to_string(), "content")
+                .await
+                .unwrap();
+        }
+
+        let sent = client.sent_emails();
+        assert_eq!(sent.len(), MAX_OUTBOX_LEN);
+        assert_eq!(sent[0].subject, "1");
+        assert_eq!(sent[MAX_OUTBOX_LEN - 1].subject, MAX_OUTBOX_LEN.to_string());
+    }
+}
@@ -1,5 +1,27 @@
+mod file_audit_sink;
+mod hashmap_client_store;
+mod hashmap_federated_login_store;
+mod hashmap_login_attempt_store;
+mod hashmap_passkey_store;
+mod hashmap_session_store;
 mod hashmap_user_store;
 mod hashset_token_store;
+mod metered_user_store;
+mod mock_email_client;
+mod postmark_email_client;
+mod state_file;
+mod vec_audit_sink;
 
+pub use file_audit_sink::*;
+pub use hashmap_client_store::*;
+pub use hashmap_federated_login_store::*;
+pub use hashmap_login_attempt_store::*;
+pub use hashmap_passkey_store::*;
+pub use hashmap_session_store::*;
 pub use hashmap_user_store::*;
 pub use hashset_token_store::*;
+pub use metered_user_store::*;
+pub use mock_email_client::*;
+pub use postmark_email_client::*;
+pub use state_file::*;
+pub use vec_audit_sink::*;
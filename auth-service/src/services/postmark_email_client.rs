@@ -0,0 +1,136 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use url::Url;
+
+use crate::domain::{Email, EmailClient, EmailClientError};
+
+pub const DEFAULT_POSTMARK_BASE_URL: &str = "https://api.postmarkapp.com";
+
+const SEND_EMAIL_PATH: &str = "/email";
+const SERVER_PATH: &str = "/server";
+const TOKEN_HEADER: &str = "X-Postmark-Server-Token";
+// login links go out in the middle of a request, which shouldn't hang on a slow provider
+const TIMEOUT: Duration = Duration::from_secs(10);
+// readiness is probed every few seconds by every replica, which shouldn't each cost an API call
+const HEALTH_CHECK_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+pub struct PostmarkSettings {
+    pub base_url: Url,
+    pub sender: Email,
+    pub server_token: String,
+}
+
+// the token never ends up in logs
+impl fmt::Debug for PostmarkSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PostmarkSettings")
+            .field("base_url", &self.base_url)
+            .field("sender", &self.sender)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Delivers emails through Postmark's HTTP API.
+#[derive(Debug)]
+pub struct PostmarkEmailClient {
+    http_client: Client,
+    settings: PostmarkSettings,
+    // when Postmark last answered a health check and whether it was healthy. Held while
+    // asking, so concurrent checks share the one request
+    last_health_check: Mutex<Option<(Instant, bool)>>,
+}
+
+impl PostmarkEmailClient {
+    pub fn new(settings: PostmarkSettings) -> Result<Self, reqwest::Error> {
+        let http_client = Client::builder().timeout(TIMEOUT).build()?;
+        Ok(Self {
+            http_client,
+            settings,
+            last_health_check: Mutex::new(None),
+        })
+    }
+
+    fn url(&self, path: &str) -> Result<Url, EmailClientError> {
+        self.settings
+            .base_url
+            .join(path)
+            .map_err(|_| EmailClientError::UnexpectedError)
+    }
+}
+
+#[async_trait]
+impl EmailClient for PostmarkEmailClient {
+    // emails carry login links, so nothing about them but the subject is recorded
+    #[tracing::instrument(skip_all, fields(subject = subject))]
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<(), EmailClientError> {
+        let body = SendEmailRequest {
+            from: self.settings.sender.as_ref(),
+            to: recipient.as_ref(),
+            subject,
+            text_body: content,
+            message_stream: "outbound",
+        };
+
+        self.http_client
+            .post(self.url(SEND_EMAIL_PATH)?)
+            .header(TOKEN_HEADER, &self.settings.server_token)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                tracing::error!(status = ?e.status(), "failed to send email");
+                EmailClientError::UnexpectedError
+            })?;
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<(), EmailClientError> {
+        let mut last_health_check = self.last_health_check.lock().await;
+        let healthy = match *last_health_check {
+            Some((checked_at, healthy)) if checked_at.elapsed() < HEALTH_CHECK_TTL => healthy,
+            _ => {
+                // only answers with a valid token, unlike a bare ping
+                let healthy = self
+                    .http_client
+                    .get(self.url(SERVER_PATH)?)
+                    .header(TOKEN_HEADER, &self.settings.server_token)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .is_ok();
+                *last_health_check = Some((Instant::now(), healthy));
+                healthy
+            }
+        };
+
+        if healthy {
+            Ok(())
+        } else {
+            Err(EmailClientError::UnexpectedError)
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    text_body: &'a str,
+    message_stream: &'a str,
+}
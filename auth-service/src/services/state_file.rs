@@ -0,0 +1,374 @@
+use std::{
+    fs::{self, File, TryLockError},
+    io,
+    path::{Path, PathBuf},
+};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::domain::{
+    BannedTokenStore, Email, ExternalIdentity, HashedPassword, LegacyPasswordHash,
+    RecoveryCodeHash, Role, TotpSecret, TwoFactorMethod, User, UserStore,
+};
+
+/// The contents of the user and banned token stores, as saved by `auth-admin`.
+///
+/// The server loads it at startup and writes it back when shut down gracefully, so changes
+/// made since the last start are lost if it crashes. Whoever has it loaded holds its
+/// `StateFileLock`, which keeps `auth-admin` from editing it under a running server.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateFile {
+    pub users: Vec<StoredUser>,
+    #[serde(rename = "bannedTokens")]
+    pub banned_tokens: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum StateFileError {
+    #[error("failed to access the state file: {0}")]
+    Io(#[from] io::Error),
+    #[error("malformed state file: {0}")]
+    Malformed(#[from] serde_json::Error),
+    #[error("invalid stored user {0}")]
+    InvalidUser(String),
+    #[error("the state file is in use, e.g. by a running auth service")]
+    InUse,
+    #[error("unexpected store error")]
+    UnexpectedError,
+}
+
+/// Exclusive use of a state file, let go of when dropped or when its process exits.
+#[derive(Debug)]
+pub struct StateFileLock {
+    _file: File,
+}
+
+impl StateFile {
+    /// Reads the state file at `path`, a missing file being an empty one.
+    pub fn read(path: &Path) -> Result<Self, StateFileError> {
+        match fs::read(path) {
+            Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Locks the state file at `path`, through a `.lock` file next to it as the file itself
+    /// gets swapped out by writes.
+    pub fn lock(path: &Path) -> Result<StateFileLock, StateFileError> {
+        let mut lock_path = PathBuf::from(path);
+        lock_path.set_extension("lock");
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(StateFileLock { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(StateFileError::InUse),
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), StateFileError> {
+        // swapped in whole, so a failed write can't leave half a file behind
+        let mut temp_path = PathBuf::from(path);
+        temp_path.set_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    pub async fn snapshot(
+        user_store: &(dyn UserStore + Send + Sync),
+        banned_token_store: &(dyn BannedTokenStore + Send + Sync),
+    ) -> Result<Self, StateFileError> {
+        let users = user_store
+            .list_users()
+            .await
+            .map_err(|_| StateFileError::UnexpectedError)?;
+        let mut banned_tokens = banned_token_store
+            .list_tokens()
+            .await
+            .map_err(|_| StateFileError::UnexpectedError)?;
+        // keeps the file stable between runs
+        banned_tokens.sort();
+
+        Ok(Self {
+            users: users.iter().map(StoredUser::from).collect(),
+            banned_tokens,
+        })
+    }
+
+    /// Adds everything in the file to the (empty) stores.
+    pub async fn restore(
+        self,
+        user_store: &(dyn UserStore + Send + Sync),
+        banned_token_store: &(dyn BannedTokenStore + Send + Sync),
+    ) -> Result<(), StateFileError> {
+        for stored in self.users {
+            let user = User::try_from(stored)?;
+            user_store
+                .add_user(user)
+                .await
+                .map_err(|_| StateFileError::UnexpectedError)?;
+        }
+        for token in self.banned_tokens {
+            banned_token_store
+                .add_token(&token)
+                .await
+                .map_err(|_| StateFileError::UnexpectedError)?;
+        }
+        Ok(())
+    }
+}
+
+/// A `User` with everything needed to restore it, secrets included.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredUser {
+    pub email: String,
+    #[serde(rename = "passwordHash")]
+    pub password_hash: HashedPassword,
+    #[serde(
+        rename = "legacyPassword",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub legacy_password: Option<LegacyPasswordHash>,
+    #[serde(rename = "twoFactor")]
+    pub two_factor: Option<StoredTwoFactor>,
+    // base32, like the authenticator apps were given
+    #[serde(rename = "pendingTotp")]
+    pub pending_totp: Option<String>,
+    // SHA-256 hashes, base64 encoded
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+    #[serde(rename = "externalIdentities")]
+    pub external_identities: Vec<ExternalIdentity>,
+    #[serde(rename = "tokenVersion")]
+    pub token_version: u64,
+    pub roles: Vec<Role>,
+    pub locked: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum StoredTwoFactor {
+    Email,
+    Totp {
+        secret: String,
+        #[serde(rename = "lastUsedStep")]
+        last_used_step: Option<u64>,
+    },
+}
+
+impl From<&User> for StoredUser {
+    fn from(user: &User) -> Self {
+        Self {
+            email: user.email.as_ref().to_string(),
+            password_hash: user.password.clone(),
+            legacy_password: user.legacy_password.clone(),
+            two_factor: user.two_factor.as_ref().map(|method| match method {
+                TwoFactorMethod::Email => StoredTwoFactor::Email,
+                TwoFactorMethod::Totp {
+                    secret,
+                    last_used_step,
+                } => StoredTwoFactor::Totp {
+                    secret: secret.to_base32(),
+                    last_used_step: *last_used_step,
+                },
+            }),
+            pending_totp: user.pending_totp.as_ref().map(TotpSecret::to_base32),
+            recovery_codes: user
+                .recovery_codes
+                .iter()
+                .map(|hash| URL_SAFE_NO_PAD.encode(hash.as_bytes()))
+                .collect(),
+            external_identities: user.external_identities.clone(),
+            token_version: user.token_version,
+            roles: user.roles.clone(),
+            locked: user.locked,
+        }
+    }
+}
+
+impl TryFrom<StoredUser> for User {
+    type Error = StateFileError;
+
+    fn try_from(stored: StoredUser) -> Result<Self, Self::Error> {
+        let invalid = || StateFileError::InvalidUser(stored.email.clone());
+        let totp_secret = |encoded: &str| TotpSecret::from_base32(encoded).ok_or_else(invalid);
+
+        let email: Email = stored.email.parse().map_err(|_| invalid())?;
+        let two_factor = match &stored.two_factor {
+            Some(StoredTwoFactor::Email) => Some(TwoFactorMethod::Email),
+            Some(StoredTwoFactor::Totp {
+                secret,
+                last_used_step,
+            }) => Some(TwoFactorMethod::Totp {
+                secret: totp_secret(secret)?,
+                last_used_step: *last_used_step,
+            }),
+            None => None,
+        };
+        let pending_totp = stored
+            .pending_totp
+            .as_deref()
+            .map(totp_secret)
+            .transpose()?;
+        let recovery_codes = stored
+            .recovery_codes
+            .iter()
+            .map(|encoded| {
+                URL_SAFE_NO_PAD
+                    .decode(encoded)
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .map(RecoveryCodeHash::from_bytes)
+                    .ok_or_else(invalid)
+            })
+            .collect::<Result<_, _>>()?;
+
+        // built directly, as `User::new` would hash the password all over again
+        Ok(User {
+            email,
+            password: stored.password_hash,
+            legacy_password: stored.legacy_password,
+            two_factor,
+            pending_totp,
+            recovery_codes,
+            external_identities: stored.external_identities,
+            token_version: stored.token_version,
+            roles: stored.roles,
+            locked: stored.locked,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        domain::Password,
+        services::{HashMapUserStore, HashSetTokenStore},
+    };
+
+    use super::*;
+
+    fn user() -> User {
+        let mut user = User::new(
+            "a@b.com".parse().unwrap(),
+            "password123".parse().unwrap(),
+            Some(TwoFactorMethod::Totp {
+                secret: TotpSecret::generate(),
+                last_used_step: Some(42),
+            }),
+        );
+        user.reset_recovery_codes();
+        user.link_identity(ExternalIdentity {
+            provider: "google".to_string(),
+            subject: "123".to_string(),
+        });
+        user.set_roles(vec![Role::Admin]);
+        user.lock();
+        user
+    }
+
+    #[test]
+    fn test_stored_user_round_trip() {
+        let user = user();
+
+        let restored = User::try_from(StoredUser::from(&user)).unwrap();
+
+        assert_eq!(restored, user);
+    }
+
+    #[test]
+    fn test_stored_user_keeps_no_plaintext_password() {
+        let json = serde_json::to_string(&StoredUser::from(&user())).unwrap();
+
+        assert!(!json.contains("password123"));
+        assert!(json.contains("$argon2id$"));
+    }
+
+    #[test]
+    fn test_invalid_stored_user_is_rejected() {
+        let mut stored = StoredUser::from(&user());
+        stored.recovery_codes = vec!["too short".to_string()];
+
+        assert!(matches!(
+            User::try_from(stored),
+            Err(StateFileError::InvalidUser(email)) if email == "a@b.com"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_and_restore() {
+        let user_store = HashMapUserStore::default();
+        let banned_token_store = HashSetTokenStore::default();
+        user_store.add_user(user()).await.unwrap();
+        banned_token_store.add_token("token").await.unwrap();
+        let path = std::env::temp_dir().join(format!("auth-state-{}.json", uuid::Uuid::new_v4()));
+
+        StateFile::snapshot(&user_store, &banned_token_store)
+            .await
+            .unwrap()
+            .write(&path)
+            .unwrap();
+
+        let restored_users = HashMapUserStore::default();
+        let restored_tokens = HashSetTokenStore::default();
+        StateFile::read(&path)
+            .unwrap()
+            .restore(&restored_users, &restored_tokens)
+            .await
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            restored_users.list_users().await.unwrap(),
+            user_store.list_users().await.unwrap()
+        );
+        assert!(restored_tokens.check_token("token").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_keeps_upgraded_password() {
+        let user_store = HashMapUserStore::default();
+        let email: Email = "a@b.com".parse().unwrap();
+        let hash = LegacyPasswordHash::parse(&bcrypt::hash("hunter2", 4).unwrap()).unwrap();
+        user_store
+            .add_user(User::with_legacy_password(email.clone(), hash))
+            .await
+            .unwrap();
+
+        let password = Password::unchecked("hunter2");
+        user_store.validate_user(&email, &password).await.unwrap();
+        let state = StateFile::snapshot(&user_store, &HashSetTokenStore::default())
+            .await
+            .unwrap();
+
+        assert_eq!(state.users[0].legacy_password, None);
+        assert!(state.users[0].password_hash.verify(&password));
+    }
+
+    #[test]
+    fn test_missing_file_is_empty() {
+        let path = std::env::temp_dir().join("auth-state-that-does-not-exist.json");
+
+        assert_eq!(StateFile::read(&path).unwrap(), StateFile::default());
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let path = std::env::temp_dir().join(format!("auth-state-{}.json", uuid::Uuid::new_v4()));
+
+        let lock = StateFile::lock(&path).unwrap();
+        assert!(matches!(StateFile::lock(&path), Err(StateFileError::InUse)));
+
+        drop(lock);
+        drop(StateFile::lock(&path).unwrap());
+        fs::remove_file(path.with_extension("lock")).unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use crate::domain::{AuditEvent, AuditSink, AuditSinkError, Email};
+
+/// Keeps events in memory, so they're gone on restart. Only meant for tests.
+#[derive(Debug, Default)]
+pub struct VecAuditSink {
+    events: Mutex<Vec<AuditEvent>>,
+}
+
+impl VecAuditSink {
+    pub fn events(&self) -> Vec<AuditEvent> {
+        self.events
+            .lock()
+            .map(|events| events.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl AuditSink for VecAuditSink {
+    async fn record(&self, event: AuditEvent) -> Result<(), AuditSinkError> {
+        self.events
+            .lock()
+            .map_err(|_| AuditSinkError::UnexpectedError)?
+            .push(event);
+        Ok(())
+    }
+
+    async fn events_for(&self, email: &Email) -> Result<Vec<AuditEvent>, AuditSinkError> {
+        Ok(self
+            .events
+            .lock()
+            .map_err(|_| AuditSinkError::UnexpectedError)?
+            .iter()
+            .filter(|event| event.email.as_deref() == Some(email.as_ref()))
+            .cloned()
+            .collect())
+    }
+
+    async fn forget(&self, email: &Email) -> Result<(), AuditSinkError> {
+        self.events
+            .lock()
+            .map_err(|_| AuditSinkError::UnexpectedError)?
+            .iter_mut()
+            .filter(|event| event.email.as_deref() == Some(email.as_ref()))
+            .for_each(AuditEvent::anonymize);
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), AuditSinkError> {
+        Ok(())
+    }
+}
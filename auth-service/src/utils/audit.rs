@@ -0,0 +1,140 @@
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{FromRequestParts, MatchedPath, Request, State},
+    http::request::Parts,
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use tower_http::request_id::RequestId;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEvent, AuditEventKind, AuditOutcome, DeviceInfo, Email, FailureReason},
+};
+
+/// What the current request records in the audit log, filled in by its handler and
+/// extractors as they learn who it's about.
+#[derive(Clone, Default)]
+pub struct Audit(Arc<Mutex<AuditDraft>>);
+
+#[derive(Default)]
+struct AuditDraft {
+    kind: Option<AuditEventKind>,
+    email: Option<String>,
+    actor: Option<String>,
+    // recorded on top of the request's own event, e.g. a rejected token
+    extra: Vec<(AuditEventKind, Option<String>, AuditOutcome)>,
+}
+
+impl Audit {
+    /// Sets the account the request is about.
+    pub fn subject(&self, email: &Email) {
+        self.update(|draft| draft.email = Some(email.as_ref().to_string()));
+    }
+
+    /// Leaves the account out of the request's event after all, e.g. once it's deleted.
+    pub fn forget_subject(&self) {
+        self.update(|draft| draft.email = None);
+    }
+
+    /// Sets the user acting on someone else's account, like an admin locking it.
+    pub fn actor(&self, email: &Email) {
+        self.update(|draft| draft.actor = Some(email.as_ref().to_string()));
+    }
+
+    /// Overrides the event the route records, once the handler knows better.
+    pub fn kind(&self, kind: AuditEventKind) {
+        self.update(|draft| draft.kind = Some(kind));
+    }
+
+    /// Records another event with the request's, whatever its own outcome.
+    pub fn record(&self, kind: AuditEventKind, email: Option<&Email>, outcome: AuditOutcome) {
+        let email = email.map(|email| email.as_ref().to_string());
+        self.update(|draft| draft.extra.push((kind, email, outcome)));
+    }
+
+    fn update(&self, change: impl FnOnce(&mut AuditDraft)) {
+        if let Ok(mut draft) = self.0.lock() {
+            change(&mut draft);
+        }
+    }
+
+    fn take(&self) -> AuditDraft {
+        self.0
+            .lock()
+            .map(|mut draft| std::mem::take(&mut *draft))
+            .unwrap_or_default()
+    }
+}
+
+// requests outside `record_audit_events`, e.g. in unit tests, get one that's never recorded
+impl<S: Send + Sync> FromRequestParts<S> for Audit {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().cloned().unwrap_or_default())
+    }
+}
+
+/// Records an event for each request to a route that touches an account, along with any
+/// its handler added.
+pub async fn record_audit_events(
+    State(state): State<AppState>,
+    matched_path: Option<MatchedPath>,
+    device: DeviceInfo,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let route_kind =
+        matched_path.and_then(|path| AuditEventKind::for_route(request.method(), path.as_str()));
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .map(String::from);
+    let audit = Audit::default();
+    request.extensions_mut().insert(audit.clone());
+
+    let response = next.run(request).await;
+
+    let draft = audit.take();
+    let outcome = if response.status().is_success() || response.status().is_redirection() {
+        AuditOutcome::Success
+    } else {
+        let reason = match response.extensions().get::<FailureReason>() {
+            Some(FailureReason(reason)) => reason.clone(),
+            // rejected before reaching the handler, e.g. a malformed body
+            None => response.status().to_string(),
+        };
+        AuditOutcome::Failure { reason }
+    };
+    let own_event = draft
+        .kind
+        .or(route_kind)
+        .map(|kind| (kind, draft.email, outcome));
+
+    let timestamp = Utc::now().timestamp();
+    for (kind, email, outcome) in own_event.into_iter().chain(draft.extra) {
+        let event = AuditEvent {
+            timestamp,
+            kind,
+            email,
+            actor: draft.actor.clone(),
+            request_id: request_id.clone(),
+            ip: device.ip.clone(),
+            user_agent: device.user_agent.clone(),
+            outcome,
+        };
+        state.metrics.observe_audit_event(&event);
+        if state.audit_sink.record(event).await.is_err() {
+            tracing::error!(?kind, "failed to record audit event");
+        }
+    }
+
+    response
+}
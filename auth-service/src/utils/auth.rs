@@ -1,26 +1,42 @@
 use std::sync::Arc;
 
 use axum_extra::extract::cookie::{Cookie, SameSite};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use chrono::{Duration, Utc};
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
-use serde::{Deserialize, Serialize};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode, errors::ErrorKind,
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
+use uuid::Uuid;
 
 use crate::{
-    domain::{BannedTokenStore, Email},
-    utils::constants::{JWT_COOKIE_NAME, JWT_SECRET},
+    domain::{
+        AuditEventKind, AuditOutcome, BannedTokenStore, ClientId, ConsentRequest, Email,
+        Permission, Role, User, UserStore, UserStoreError,
+    },
+    utils::{
+        audit::Audit,
+        constants::{ID_TOKEN_KEY, JWT_COOKIE_NAME, JWT_SECRET, PUBLIC_URL},
+    },
 };
 
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
-    Ok(create_auth_cookie(token))
+pub fn generate_auth_cookie(
+    user: &User,
+    secure: bool,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_auth_token(user)?;
+    Ok(create_auth_cookie(token, secure))
 }
 
-fn create_auth_cookie(token: String) -> Cookie<'static> {
+/// The cookie holding the auth token, only sent back over HTTPS when `secure`.
+pub fn create_auth_cookie(token: String, secure: bool) -> Cookie<'static> {
     Cookie::build((JWT_COOKIE_NAME, token))
         .path("/")
         .http_only(true)
         .same_site(SameSite::Lax)
+        .secure(secure)
         .build()
 }
 
@@ -30,6 +46,10 @@ pub enum GenerateTokenError {
     TokenError(#[from] jsonwebtoken::errors::Error),
     #[error("banned jwt token")]
     BannedToken,
+    #[error("token was issued to another browser")]
+    NonceMismatch,
+    #[error("token was revoked")]
+    RevokedToken,
     #[error("unexpected error")]
     UnexpectedError,
 }
@@ -37,9 +57,266 @@ pub enum GenerateTokenError {
 // 10 min
 pub const TOKEN_TTL_SECONDS: i64 = 600;
 
-fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
+/// Signs the JWT set as the auth cookie, also handed out as the OAuth access token.
+pub fn generate_auth_token(user: &User) -> Result<String, GenerateTokenError> {
+    issue_auth_token(user).map(|(token, _)| token)
+}
+
+/// Like `generate_auth_token`, also returning the claims so a session can be kept for it.
+pub fn issue_auth_token(user: &User) -> Result<(String, Claims), GenerateTokenError> {
+    let claims = Claims {
+        token_version: user.token_version,
+        roles: user.roles.clone(),
+        permissions: user.permissions(),
+        ..auth_claims(&user.email, TOKEN_TTL_SECONDS)?
+    };
+    Ok((create_token(&claims)?, claims))
+}
+
+fn auth_claims(email: &Email, ttl_seconds: i64) -> Result<Claims, GenerateTokenError> {
+    Ok(Claims {
+        subject: email.as_ref().to_string(),
+        ..auth_claims_expiring(ttl_seconds)?
+    })
+}
+
+fn auth_claims_expiring(ttl_seconds: i64) -> Result<Claims, GenerateTokenError> {
+    let delta = Duration::try_seconds(ttl_seconds).ok_or(GenerateTokenError::UnexpectedError)?;
+
+    let expiration: usize = Utc::now()
+        .checked_add_signed(delta)
+        .ok_or(GenerateTokenError::UnexpectedError)?
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
+    Ok(Claims {
+        subject: String::new(),
+        expirary: expiration,
+        // also keeps tokens issued in the same second apart
+        id: Uuid::new_v4().to_string(),
+        token_version: 0,
+        roles: Vec::new(),
+        permissions: Vec::new(),
+    })
+}
+
+pub async fn validate_token(
+    banned_token_store: Arc<dyn BannedTokenStore + Send + Sync>,
+    user_store: Arc<dyn UserStore + Send + Sync>,
+    audit: &Audit,
+    token: &str,
+) -> Result<Claims, GenerateTokenError> {
+    validate_user_token(
+        banned_token_store,
+        user_store,
+        audit,
+        token,
+        &Validation::default(),
+    )
+    .await
+}
+
+// the checks auth and access tokens share, `validation` telling them apart
+async fn validate_user_token<T: DeserializeOwned + AsRef<Claims>>(
+    banned_token_store: Arc<dyn BannedTokenStore + Send + Sync>,
+    user_store: Arc<dyn UserStore + Send + Sync>,
+    audit: &Audit,
+    token: &str,
+    validation: &Validation,
+) -> Result<T, GenerateTokenError> {
+    let rejected = |reason: &str, email: Option<&Email>| {
+        let outcome = AuditOutcome::Failure {
+            reason: reason.to_string(),
+        };
+        audit.record(AuditEventKind::TokenRejected, email, outcome);
+    };
+
+    if banned_token_store
+        .check_token(token)
+        .await
+        .map_err(|_| GenerateTokenError::UnexpectedError)?
+    {
+        rejected("banned", None);
+        return Err(GenerateTokenError::BannedToken);
+    }
+
+    let decoded = match decode::<T>(
+        token,
+        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
+        validation,
+    ) {
+        Ok(data) => data.claims,
+        Err(e) => {
+            match e.kind() {
+                ErrorKind::ExpiredSignature => rejected("expired", None),
+                _ => rejected("invalid", None),
+            }
+            return Err(e.into());
+        }
+    };
+    let claims = decoded.as_ref();
+
+    let Ok(email) = claims.subject.parse::<Email>() else {
+        rejected("invalid", None);
+        return Err(jsonwebtoken::errors::Error::from(ErrorKind::InvalidSubject).into());
+    };
+    // a single lookup covers every token the user was ever issued
+    match user_store.get_user(&email).await {
+        Ok(user) if user.token_version == claims.token_version => Ok(decoded),
+        // older tokens, or ones outliving their account
+        Ok(_) | Err(UserStoreError::UserNotFound) => {
+            rejected("revoked", Some(&email));
+            Err(GenerateTokenError::RevokedToken)
+        }
+        Err(_) => Err(GenerateTokenError::UnexpectedError),
+    }
+}
+
+// keeps OAuth access tokens from passing as auth tokens, they're only good at `/userinfo`
+const ACCESS_TOKEN_AUDIENCE: &str = "userinfo";
+
+/// Signs an OAuth access token for `user`, issued to `client_id` and granted `scopes`.
+pub fn issue_access_token(
+    user: &User,
+    client_id: &ClientId,
+    scopes: &[String],
+) -> Result<(String, AccessTokenClaims), GenerateTokenError> {
+    let claims = AccessTokenClaims {
+        claims: Claims {
+            token_version: user.token_version,
+            ..auth_claims(&user.email, TOKEN_TTL_SECONDS)?
+        },
+        audience: ACCESS_TOKEN_AUDIENCE.to_string(),
+        client_id: client_id.as_ref().to_string(),
+        scope: scopes.join(" "),
+    };
+
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+    )?;
+    Ok((token, claims))
+}
+
+pub async fn validate_access_token(
+    banned_token_store: Arc<dyn BannedTokenStore + Send + Sync>,
+    user_store: Arc<dyn UserStore + Send + Sync>,
+    audit: &Audit,
+    token: &str,
+) -> Result<AccessTokenClaims, GenerateTokenError> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[ACCESS_TOKEN_AUDIENCE]);
+
+    validate_user_token(banned_token_store, user_store, audit, token, &validation).await
+}
+
+/// Signs an OpenID Connect ID token with `ID_TOKEN_KEY`, so clients can check it against
+/// `/jwks.json`. Its audience also keeps it from passing as an auth token.
+pub fn generate_id_token(
+    email: &Email,
+    client_id: &ClientId,
+    nonce: Option<String>,
+) -> Result<String, GenerateTokenError> {
+    let claims = IdTokenClaims {
+        claims: auth_claims(email, TOKEN_TTL_SECONDS)?,
+        issuer: PUBLIC_URL.clone(),
+        audience: client_id.as_ref().to_string(),
+        issued_at: Utc::now()
+            .timestamp()
+            .try_into()
+            .map_err(|_| GenerateTokenError::UnexpectedError)?,
+        email: email.as_ref().to_string(),
+        nonce,
+    };
+    let mut header = Header::new(Algorithm::ES256);
+    header.kid = Some(ID_TOKEN_KEY.jwk().kid.clone());
+
+    Ok(encode(&header, &claims, ID_TOKEN_KEY.encoding_key())?)
+}
+
+pub fn validate_id_token(
+    token: &str,
+    client_id: &ClientId,
+) -> Result<IdTokenClaims, GenerateTokenError> {
+    let mut validation = Validation::new(Algorithm::ES256);
+    validation.set_audience(&[client_id.as_ref()]);
+    validation.set_issuer(&[PUBLIC_URL.as_str()]);
+
+    Ok(decode::<IdTokenClaims>(token, ID_TOKEN_KEY.decoding_key(), &validation)?.claims)
+}
+
+// service tokens carry `client:<id>` as their subject, so they can never be mistaken for a user
+pub const SERVICE_SUBJECT_PREFIX: &str = "client:";
+// and an audience, so they're never accepted as auth tokens
+const SERVICE_AUDIENCE: &str = "service";
+
+/// Signs an access token for a service client, granted `scopes`.
+pub fn generate_service_token(
+    client_id: &str,
+    scopes: &[String],
+) -> Result<String, GenerateTokenError> {
+    let claims = ServiceClaims {
+        claims: Claims {
+            subject: format!("{SERVICE_SUBJECT_PREFIX}{client_id}"),
+            ..auth_claims_expiring(TOKEN_TTL_SECONDS)?
+        },
+        audience: SERVICE_AUDIENCE.to_string(),
+        scope: scopes.join(" "),
+    };
+
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+    )?)
+}
+
+pub async fn validate_service_token(
+    banned_token_store: Arc<dyn BannedTokenStore + Send + Sync>,
+    token: &str,
+) -> Result<ServiceClaims, GenerateTokenError> {
+    if banned_token_store
+        .check_token(token)
+        .await
+        .map_err(|_| GenerateTokenError::UnexpectedError)?
+    {
+        return Err(GenerateTokenError::BannedToken);
+    }
+
+    let mut validation = Validation::default();
+    validation.set_audience(&[SERVICE_AUDIENCE]);
+    let claims = decode::<ServiceClaims>(
+        token,
+        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
+        &validation,
+    )?
+    .claims;
+
+    if claims.client_id().is_none() {
+        return Err(jsonwebtoken::errors::Error::from(ErrorKind::InvalidSubject).into());
+    }
+    Ok(claims)
+}
+
+fn create_token(claims: &Claims) -> Result<String, GenerateTokenError> {
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+    )?)
+}
+
+// 10 min
+pub const MAGIC_LINK_TTL_SECONDS: i64 = 600;
+// keeps magic link tokens from ever being accepted as auth tokens
+const MAGIC_LINK_AUDIENCE: &str = "magic-link";
+
+/// Signs a single-use login token for `email`, only redeemable alongside `nonce`.
+pub fn generate_magic_link_token(email: &Email, nonce: &str) -> Result<String, GenerateTokenError> {
     let delta =
-        Duration::try_seconds(TOKEN_TTL_SECONDS).ok_or(GenerateTokenError::UnexpectedError)?;
+        Duration::try_seconds(MAGIC_LINK_TTL_SECONDS).ok_or(GenerateTokenError::UnexpectedError)?;
 
     let expiration: usize = Utc::now()
         .checked_add_signed(delta)
@@ -48,35 +325,69 @@ fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
         .try_into()
         .map_err(|_| GenerateTokenError::UnexpectedError)?;
 
-    let claims = Claims {
+    let claims = MagicLinkClaims {
         subject: email.as_ref().to_string(),
         expirary: expiration,
+        audience: MAGIC_LINK_AUDIENCE.to_string(),
+        nonce_hash: hash_nonce(nonce),
+        id: Uuid::new_v4().to_string(),
     };
 
-    create_token(&claims)
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+    )?)
 }
 
-pub async fn validate_token(
+/// Checks a magic link token hasn't been used and was issued to the browser holding `nonce`.
+pub async fn validate_magic_link_token(
     banned_token_store: Arc<dyn BannedTokenStore + Send + Sync>,
     token: &str,
-) -> Result<Claims, GenerateTokenError> {
+    nonce: &str,
+) -> Result<MagicLinkClaims, GenerateTokenError> {
     if banned_token_store
         .check_token(token)
         .await
         .map_err(|_| GenerateTokenError::UnexpectedError)?
     {
-        Err(GenerateTokenError::BannedToken)
-    } else {
-        Ok(decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-            &Validation::default(),
-        )
-        .map(|data| data.claims)?)
+        return Err(GenerateTokenError::BannedToken);
     }
+
+    let mut validation = Validation::default();
+    validation.set_audience(&[MAGIC_LINK_AUDIENCE]);
+    let claims = decode::<MagicLinkClaims>(
+        token,
+        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
+        &validation,
+    )?
+    .claims;
+
+    if claims.nonce_hash != hash_nonce(nonce) {
+        return Err(GenerateTokenError::NonceMismatch);
+    }
+    Ok(claims)
 }
 
-fn create_token(claims: &Claims) -> Result<String, GenerateTokenError> {
+// 10 min to decide on the consent page
+pub const CONSENT_TTL_SECONDS: i64 = 600;
+const CONSENT_AUDIENCE: &str = "oauth-consent";
+
+/// Signs the consent page's copy of an authorization request, only good in the session
+/// `session_id` it was shown in. Other sites can't read the page, so it doubles as a CSRF token.
+pub fn generate_consent_token(
+    email: &Email,
+    session_id: &str,
+    request: ConsentRequest,
+) -> Result<String, GenerateTokenError> {
+    let claims = ConsentClaims {
+        subject: email.as_ref().to_string(),
+        expirary: auth_claims_expiring(CONSENT_TTL_SECONDS)?.expirary,
+        audience: CONSENT_AUDIENCE.to_string(),
+        session_id: session_id.to_string(),
+        request,
+    };
+
     Ok(encode(
         &Header::default(),
         &claims,
@@ -84,24 +395,163 @@ fn create_token(claims: &Claims) -> Result<String, GenerateTokenError> {
     )?)
 }
 
+/// Checks a consent token was shown to `email` in session `session_id`.
+pub fn validate_consent_token(
+    token: &str,
+    email: &Email,
+    session_id: &str,
+) -> Result<ConsentRequest, GenerateTokenError> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[CONSENT_AUDIENCE]);
+    let claims = decode::<ConsentClaims>(
+        token,
+        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
+        &validation,
+    )?
+    .claims;
+
+    if claims.subject != email.as_ref() || claims.session_id != session_id {
+        return Err(GenerateTokenError::NonceMismatch);
+    }
+    Ok(claims.request)
+}
+
+// the nonce itself only ever lives in the requesting browser's cookie
+fn hash_nonce(nonce: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(nonce.as_bytes()))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MagicLinkClaims {
+    #[serde(rename = "sub")]
+    pub subject: String,
+    #[serde(rename = "exp")]
+    pub expirary: usize,
+    #[serde(rename = "aud")]
+    pub audience: String,
+    #[serde(rename = "nonce")]
+    pub nonce_hash: String,
+    #[serde(rename = "jti")]
+    pub id: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     #[serde(rename = "sub")]
     pub subject: String,
     #[serde(rename = "exp")]
     pub expirary: usize,
+    // names the token's session
+    #[serde(rename = "jti")]
+    pub id: String,
+    // the user's token version when issued, see `User::revoke_tokens`
+    #[serde(rename = "ver", default)]
+    pub token_version: u64,
+    // as of issuing, changing them revokes the user's tokens
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<Role>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permissions: Vec<Permission>,
+}
+
+impl AsRef<Claims> for Claims {
+    fn as_ref(&self) -> &Claims {
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessTokenClaims {
+    #[serde(flatten)]
+    pub claims: Claims,
+    #[serde(rename = "aud")]
+    pub audience: String,
+    // the client it was issued to (RFC 9068)
+    pub client_id: String,
+    pub scope: String,
+}
+
+impl AsRef<Claims> for AccessTokenClaims {
+    fn as_ref(&self) -> &Claims {
+        &self.claims
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConsentClaims {
+    #[serde(rename = "sub")]
+    pub subject: String,
+    #[serde(rename = "exp")]
+    pub expirary: usize,
+    #[serde(rename = "aud")]
+    pub audience: String,
+    // the auth token's id, which names its session
+    #[serde(rename = "sid")]
+    pub session_id: String,
+    #[serde(flatten)]
+    pub request: ConsentRequest,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServiceClaims {
+    #[serde(flatten)]
+    pub claims: Claims,
+    #[serde(rename = "aud")]
+    pub audience: String,
+    // space separated, as in OAuth
+    pub scope: String,
+}
+
+impl ServiceClaims {
+    pub fn client_id(&self) -> Option<&str> {
+        self.claims.subject.strip_prefix(SERVICE_SUBJECT_PREFIX)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .split_whitespace()
+            .any(|granted| granted == scope)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    #[serde(flatten)]
+    pub claims: Claims,
+    #[serde(rename = "iss")]
+    pub issuer: String,
+    #[serde(rename = "aud")]
+    pub audience: String,
+    #[serde(rename = "iat")]
+    pub issued_at: usize,
+    pub email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::services::HashSetTokenStore;
+    use crate::{
+        domain::User,
+        services::{HashMapUserStore, HashSetTokenStore},
+    };
 
     use super::*;
 
+    fn test_user(email: &Email) -> User {
+        User::new(email.clone(), "password123".parse().unwrap(), None)
+    }
+
+    async fn user_store_with(email: &Email) -> Arc<HashMapUserStore> {
+        let user_store = Arc::new(HashMapUserStore::default());
+        user_store.add_user(test_user(email)).await.unwrap();
+        user_store
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email: Email = "test@example.com".parse().unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&test_user(&email), false).unwrap();
 
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
@@ -113,19 +563,21 @@ mod tests {
     #[tokio::test]
     async fn test_create_auth_cookie() {
         let token = "test_token".to_string();
-        let cookie = create_auth_cookie(token.clone());
+        let cookie = create_auth_cookie(token.clone(), false);
 
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value(), token);
         assert_eq!(cookie.path(), Some("/"));
         assert_eq!(cookie.http_only(), Some(true));
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(cookie.secure(), Some(false));
+        assert_eq!(create_auth_cookie(token, true).secure(), Some(true));
     }
 
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email: Email = "test@example.com".parse().unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&test_user(&email)).unwrap();
 
         assert_eq!(result.split('.').count(), 3);
     }
@@ -133,11 +585,17 @@ mod tests {
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email: Email = "test@example.com".parse().unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let token = generate_auth_token(&test_user(&email)).unwrap();
         let banned_token_store = Arc::new(HashSetTokenStore::default());
-        let result = validate_token(banned_token_store.clone(), &token)
-            .await
-            .unwrap();
+        let user_store = user_store_with(&email).await;
+        let result = validate_token(
+            banned_token_store.clone(),
+            user_store,
+            &Audit::default(),
+            &token,
+        )
+        .await
+        .unwrap();
         assert_eq!(result.subject, "test@example.com");
 
         let exp = Utc::now()
@@ -152,7 +610,13 @@ mod tests {
     async fn test_validate_token_with_invalid_token() {
         let token = "invalid_token".to_string();
         let banned_token_store = Arc::new(HashSetTokenStore::default());
-        let result = validate_token(banned_token_store, &token).await;
+        let result = validate_token(
+            banned_token_store,
+            Arc::new(HashMapUserStore::default()),
+            &Audit::default(),
+            &token,
+        )
+        .await;
 
         assert!(result.is_err());
     }
@@ -160,12 +624,296 @@ mod tests {
     #[tokio::test]
     async fn test_validate_token_with_banned_token() {
         let email: Email = "test@example.com".parse().unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let token = generate_auth_token(&test_user(&email)).unwrap();
         let banned_token_store = Arc::new(HashSetTokenStore::default());
         banned_token_store.add_token(&token).await.unwrap();
 
-        let result = validate_token(banned_token_store, &token).await;
+        let result = validate_token(
+            banned_token_store,
+            Arc::new(HashMapUserStore::default()),
+            &Audit::default(),
+            &token,
+        )
+        .await;
+
+        assert!(matches!(result, Err(GenerateTokenError::BannedToken)));
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_carries_roles() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let mut user = test_user(&email);
+        user.roles = vec![Role::Support];
+        let user_store = Arc::new(HashMapUserStore::default());
+        user_store.add_user(user.clone()).await.unwrap();
+        let token = generate_auth_token(&user).unwrap();
+
+        let claims = validate_token(
+            Arc::new(HashSetTokenStore::default()),
+            user_store,
+            &Audit::default(),
+            &token,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(claims.roles, vec![Role::Support]);
+        assert_eq!(claims.permissions, vec![Permission::ReadUsers]);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_old_token_version() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let token = generate_auth_token(&test_user(&email)).unwrap();
+        let banned_token_store = Arc::new(HashSetTokenStore::default());
+        let user_store = user_store_with(&email).await;
+
+        let user = user_store
+            .modify_user(&email, Box::new(|user| user.revoke_tokens()))
+            .await
+            .unwrap();
+
+        let result = validate_token(
+            banned_token_store.clone(),
+            user_store.clone(),
+            &Audit::default(),
+            &token,
+        )
+        .await;
+        assert!(matches!(result, Err(GenerateTokenError::RevokedToken)));
+
+        // tokens issued afterwards are fine
+        let token = generate_auth_token(&user).unwrap();
+        let result =
+            validate_token(banned_token_store, user_store, &Audit::default(), &token).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_for_deleted_user() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let token = generate_auth_token(&test_user(&email)).unwrap();
+        let banned_token_store = Arc::new(HashSetTokenStore::default());
+
+        let result = validate_token(
+            banned_token_store,
+            Arc::new(HashMapUserStore::default()),
+            &Audit::default(),
+            &token,
+        )
+        .await;
+
+        assert!(matches!(result, Err(GenerateTokenError::RevokedToken)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_id_token() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let client_id = ClientId::default();
+        let token = generate_id_token(&email, &client_id, Some("nonce".to_string())).unwrap();
+
+        let claims = validate_id_token(&token, &client_id).unwrap();
+        assert_eq!(claims.claims.subject, "test@example.com");
+        assert_eq!(claims.issuer, *PUBLIC_URL);
+        assert_eq!(claims.nonce.as_deref(), Some("nonce"));
+
+        assert!(validate_id_token(&token, &ClientId::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_id_token_is_not_an_auth_token() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let token = generate_id_token(&email, &ClientId::default(), None).unwrap();
+        let banned_token_store = Arc::new(HashSetTokenStore::default());
+
+        let result = validate_token(
+            banned_token_store,
+            Arc::new(HashMapUserStore::default()),
+            &Audit::default(),
+            &token,
+        )
+        .await;
+
+        assert!(matches!(result, Err(GenerateTokenError::TokenError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_validate_access_token() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let client_id = ClientId::default();
+        let (token, _) =
+            issue_access_token(&test_user(&email), &client_id, &["openid".to_string()]).unwrap();
+
+        let claims = validate_access_token(
+            Arc::new(HashSetTokenStore::default()),
+            user_store_with(&email).await,
+            &Audit::default(),
+            &token,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(claims.claims.subject, "test@example.com");
+        assert_eq!(claims.client_id, client_id.as_ref());
+        assert_eq!(claims.scope, "openid");
+    }
+
+    #[tokio::test]
+    async fn test_access_token_is_not_an_auth_token() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let (token, _) = issue_access_token(&test_user(&email), &ClientId::default(), &[]).unwrap();
+
+        let result = validate_token(
+            Arc::new(HashSetTokenStore::default()),
+            user_store_with(&email).await,
+            &Audit::default(),
+            &token,
+        )
+        .await;
+
+        assert!(matches!(result, Err(GenerateTokenError::TokenError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_is_not_an_access_token() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let token = generate_auth_token(&test_user(&email)).unwrap();
+
+        let result = validate_access_token(
+            Arc::new(HashSetTokenStore::default()),
+            user_store_with(&email).await,
+            &Audit::default(),
+            &token,
+        )
+        .await;
+
+        assert!(matches!(result, Err(GenerateTokenError::TokenError(_))));
+    }
+
+    #[test]
+    fn test_validate_consent_token() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let request = ConsentRequest {
+            client_id: "client".to_string(),
+            redirect_uri: "http://localhost:9000/callback".to_string(),
+            scope: "openid email".to_string(),
+            state: Some("xyz".to_string()),
+            nonce: None,
+            code_challenge: "challenge".to_string(),
+        };
+        let token = generate_consent_token(&email, "session", request.clone()).unwrap();
+
+        assert_eq!(
+            validate_consent_token(&token, &email, "session").unwrap(),
+            request
+        );
+        assert!(matches!(
+            validate_consent_token(&token, &email, "other"),
+            Err(GenerateTokenError::NonceMismatch)
+        ));
+        let other: Email = "other@example.com".parse().unwrap();
+        assert!(matches!(
+            validate_consent_token(&token, &other, "session"),
+            Err(GenerateTokenError::NonceMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_auth_token_is_not_a_consent_token() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let token = generate_auth_token(&test_user(&email)).unwrap();
+
+        assert!(validate_consent_token(&token, &email, "session").is_err());
+    }
 
+    #[tokio::test]
+    async fn test_validate_magic_link_token() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let token = generate_magic_link_token(&email, "nonce").unwrap();
+        let banned_token_store = Arc::new(HashSetTokenStore::default());
+
+        let claims = validate_magic_link_token(banned_token_store.clone(), &token, "nonce")
+            .await
+            .unwrap();
+        assert_eq!(claims.subject, "test@example.com");
+
+        let result = validate_magic_link_token(banned_token_store.clone(), &token, "other").await;
+        assert!(matches!(result, Err(GenerateTokenError::NonceMismatch)));
+
+        banned_token_store.add_token(&token).await.unwrap();
+        let result = validate_magic_link_token(banned_token_store, &token, "nonce").await;
         assert!(matches!(result, Err(GenerateTokenError::BannedToken)));
     }
+
+    #[tokio::test]
+    async fn test_magic_link_token_is_not_an_auth_token() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let token = generate_magic_link_token(&email, "nonce").unwrap();
+        let banned_token_store = Arc::new(HashSetTokenStore::default());
+
+        let result = validate_token(
+            banned_token_store,
+            Arc::new(HashMapUserStore::default()),
+            &Audit::default(),
+            &token,
+        )
+        .await;
+
+        assert!(matches!(result, Err(GenerateTokenError::TokenError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_is_not_a_magic_link_token() {
+        let email: Email = "test@example.com".parse().unwrap();
+        let token = generate_auth_token(&test_user(&email)).unwrap();
+        let banned_token_store = Arc::new(HashSetTokenStore::default());
+
+        let result = validate_magic_link_token(banned_token_store, &token, "nonce").await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_service_token() {
+        let token = generate_service_token("app-service", &["tokens:verify".to_string()]).unwrap();
+        let banned_token_store = Arc::new(HashSetTokenStore::default());
+
+        let claims = validate_service_token(banned_token_store.clone(), &token)
+            .await
+            .unwrap();
+
+        assert_eq!(claims.claims.subject, "client:app-service");
+        assert_eq!(claims.client_id(), Some("app-service"));
+        assert!(claims.has_scope("tokens:verify"));
+        assert!(!claims.has_scope("tokens"));
+    }
+
+    #[tokio::test]
+    async fn test_service_token_is_not_an_auth_token() {
+        let token = generate_service_token("app-service", &[]).unwrap();
+        let banned_token_store = Arc::new(HashSetTokenStore::default());
+
+        assert!(
+            validate_token(
+                banned_token_store,
+                Arc::new(HashMapUserStore::default()),
+                &Audit::default(),
+                &token
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_is_not_a_service_token() {
+        let token = generate_auth_token(&test_user(&"test@example.com".parse().unwrap())).unwrap();
+        let banned_token_store = Arc::new(HashSetTokenStore::default());
+
+        assert!(
+            validate_service_token(banned_token_store, &token)
+                .await
+                .is_err()
+        );
+    }
 }
@@ -1,10 +1,58 @@
-use std::sync::LazyLock;
+use std::{path::PathBuf, sync::LazyLock, time::Duration};
 
+use axum::http::HeaderValue;
 use dotenvy::dotenv;
 
+use crate::{
+    domain::{
+        ClientSecretHash, IdentityProvider, MIN_CLIENT_SECRET_LENGTH, Role, SERVICE_SCOPES,
+        ServiceClient, User,
+    },
+    services::{DEFAULT_POSTMARK_BASE_URL, PostmarkSettings},
+    utils::{
+        security_headers::SecurityHeaders,
+        signing_key::SigningKey,
+        tls::{DEFAULT_RELOAD_INTERVAL, TlsSettings},
+    },
+};
+
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
     pub const DROPLET_IP_ENV_VAR: &str = "DROPLET_IP";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
+    pub const WEBAUTHN_ORIGIN_ENV_VAR: &str = "WEBAUTHN_ORIGIN";
+    pub const PUBLIC_URL_ENV_VAR: &str = "AUTH_SERVICE_PUBLIC_URL";
+    // comma separated provider ids, each configured with `OIDC_<ID>_*` variables
+    pub const OIDC_PROVIDERS_ENV_VAR: &str = "OIDC_PROVIDERS";
+    // comma separated service client ids, each configured with `SERVICE_CLIENT_<ID>_*` variables
+    pub const SERVICE_CLIENTS_ENV_VAR: &str = "SERVICE_CLIENTS";
+    // an admin account created at startup, as nothing else can grant roles
+    pub const ADMIN_EMAIL_ENV_VAR: &str = "ADMIN_EMAIL";
+    pub const ADMIN_PASSWORD_ENV_VAR: &str = "ADMIN_PASSWORD";
+    // a PEM P-256 key ID tokens are signed with, a new one each start unless set
+    pub const ID_TOKEN_KEY_FILE_ENV_VAR: &str = "ID_TOKEN_KEY_FILE";
+    // written by `auth-admin`, loaded by the server at startup
+    pub const STATE_FILE_ENV_VAR: &str = "AUTH_STATE_FILE";
+    // where audit events are appended, `DEFAULT_AUDIT_LOG_FILE` unless set
+    pub const AUDIT_LOG_FILE_ENV_VAR: &str = "AUDIT_LOG_FILE";
+    // login links are emailed through Postmark, from the given address
+    pub const POSTMARK_SERVER_TOKEN_ENV_VAR: &str = "POSTMARK_SERVER_TOKEN";
+    pub const POSTMARK_BASE_URL_ENV_VAR: &str = "POSTMARK_BASE_URL";
+    pub const EMAIL_SENDER_ENV_VAR: &str = "EMAIL_SENDER";
+    // spans are only exported when given a collector
+    pub const OTLP_ENDPOINT_ENV_VAR: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+    // seconds requests in flight get to finish when shutting down
+    pub const DRAIN_TIMEOUT_ENV_VAR: &str = "SHUTDOWN_DRAIN_TIMEOUT";
+    // PEM files, HTTPS is served when both are set, and reloaded when they change
+    pub const TLS_CERT_FILE_ENV_VAR: &str = "TLS_CERT_FILE";
+    pub const TLS_KEY_FILE_ENV_VAR: &str = "TLS_KEY_FILE";
+    // plain HTTP is redirected to HTTPS from here, e.g. `0.0.0.0:80`
+    pub const TLS_REDIRECT_ADDRESS_ENV_VAR: &str = "TLS_REDIRECT_ADDRESS";
+    // override the security headers' defaults, or turn them off when empty
+    pub const STRICT_TRANSPORT_SECURITY_ENV_VAR: &str = "STRICT_TRANSPORT_SECURITY";
+    pub const CONTENT_SECURITY_POLICY_ENV_VAR: &str = "CONTENT_SECURITY_POLICY";
+    pub const FRAME_OPTIONS_ENV_VAR: &str = "X_FRAME_OPTIONS";
+    pub const REFERRER_POLICY_ENV_VAR: &str = "REFERRER_POLICY";
 }
 
 pub mod prod {
@@ -16,6 +64,10 @@ pub mod test {
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const MAGIC_LINK_NONCE_COOKIE_NAME: &str = "magic_link_nonce";
+pub const FEDERATION_STATE_COOKIE_NAME: &str = "oidc_state";
+
+pub const WEBAUTHN_RP_NAME: &str = "auth-service";
 
 pub static JWT_SECRET: LazyLock<String> = LazyLock::new(|| {
     // load env vars
@@ -27,6 +79,24 @@ pub static JWT_SECRET: LazyLock<String> = LazyLock::new(|| {
     secret
 });
 
+pub static ID_TOKEN_KEY: LazyLock<SigningKey> = LazyLock::new(|| {
+    dotenv().ok();
+    match std::env::var(env::ID_TOKEN_KEY_FILE_ENV_VAR) {
+        Ok(path) if !path.is_empty() => {
+            let pem = std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("failed to read {path}: {e}"));
+            SigningKey::from_pem(&pem).unwrap_or_else(|_| {
+                panic!(
+                    "{} must be a PKCS#8 P-256 key!",
+                    env::ID_TOKEN_KEY_FILE_ENV_VAR
+                )
+            })
+        }
+        // ID tokens are checked as soon as they're issued, so losing the key on restart is fine
+        _ => SigningKey::generate(),
+    }
+});
+
 pub static DROPLET_IP: LazyLock<String> = LazyLock::new(|| {
     dotenv().ok();
     let ip = std::env::var(env::DROPLET_IP_ENV_VAR).expect("DROPLET_IP must be set!");
@@ -35,3 +105,234 @@ pub static DROPLET_IP: LazyLock<String> = LazyLock::new(|| {
     }
     ip
 });
+
+// passkeys are bound to a domain, so these can't just follow DROPLET_IP
+pub static WEBAUTHN_RP_ID: LazyLock<String> = LazyLock::new(|| {
+    dotenv().ok();
+    std::env::var(env::WEBAUTHN_RP_ID_ENV_VAR)
+        .ok()
+        .filter(|rp_id| !rp_id.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+});
+
+pub static WEBAUTHN_ORIGIN: LazyLock<String> = LazyLock::new(|| {
+    dotenv().ok();
+    std::env::var(env::WEBAUTHN_ORIGIN_ENV_VAR)
+        .ok()
+        .filter(|origin| !origin.is_empty())
+        .unwrap_or_else(|| "http://localhost:3000".to_string())
+});
+
+// where links in emails point back to
+pub static PUBLIC_URL: LazyLock<String> = LazyLock::new(|| {
+    dotenv().ok();
+    std::env::var(env::PUBLIC_URL_ENV_VAR)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| "http://localhost:3000".to_string())
+});
+
+pub static IDENTITY_PROVIDERS: LazyLock<Vec<IdentityProvider>> = LazyLock::new(|| {
+    dotenv().ok();
+    let ids = std::env::var(env::OIDC_PROVIDERS_ENV_VAR).unwrap_or_default();
+
+    ids.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            let var = |name: &str| {
+                let key = format!("OIDC_{}_{name}", id.to_uppercase());
+                std::env::var(&key).unwrap_or_else(|_| panic!("{key} must be set!"))
+            };
+
+            IdentityProvider {
+                id: id.to_lowercase(),
+                name: std::env::var(format!("OIDC_{}_NAME", id.to_uppercase()))
+                    .unwrap_or_else(|_| id.to_string()),
+                issuer: var("ISSUER"),
+                client_id: var("CLIENT_ID"),
+                client_secret: var("CLIENT_SECRET"),
+            }
+        })
+        .collect()
+});
+
+// only the hash of each secret is kept once loaded
+pub static SERVICE_CLIENTS: LazyLock<Vec<ServiceClient>> = LazyLock::new(|| {
+    dotenv().ok();
+    let ids = std::env::var(env::SERVICE_CLIENTS_ENV_VAR).unwrap_or_default();
+
+    ids.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            let key = |name: &str| {
+                format!(
+                    "SERVICE_CLIENT_{}_{name}",
+                    id.to_uppercase().replace('-', "_")
+                )
+            };
+
+            let secret_key = key("SECRET");
+            let secret =
+                std::env::var(&secret_key).unwrap_or_else(|_| panic!("{secret_key} must be set!"));
+            if secret.len() < MIN_CLIENT_SECRET_LENGTH {
+                panic!("{secret_key} must be at least {MIN_CLIENT_SECRET_LENGTH} characters!");
+            }
+
+            let scopes: Vec<String> = std::env::var(key("SCOPES"))
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(String::from)
+                .collect();
+            if let Some(scope) = scopes
+                .iter()
+                .find(|scope| !SERVICE_SCOPES.contains(&scope.as_str()))
+            {
+                panic!("{} has unknown scope {scope}!", key("SCOPES"));
+            }
+
+            ServiceClient {
+                id: id.to_lowercase(),
+                secret_hash: ClientSecretHash::of(&secret),
+                scopes,
+            }
+        })
+        .collect()
+});
+
+pub static ADMIN: LazyLock<Option<User>> = LazyLock::new(|| {
+    dotenv().ok();
+    let email = std::env::var(env::ADMIN_EMAIL_ENV_VAR).ok()?;
+    let password = std::env::var(env::ADMIN_PASSWORD_ENV_VAR)
+        .unwrap_or_else(|_| panic!("{} must be set!", env::ADMIN_PASSWORD_ENV_VAR));
+
+    let mut user = User::new(
+        email
+            .parse()
+            .unwrap_or_else(|_| panic!("{} must be an email!", env::ADMIN_EMAIL_ENV_VAR)),
+        password
+            .parse()
+            .unwrap_or_else(|_| panic!("{} is not a valid password!", env::ADMIN_PASSWORD_ENV_VAR)),
+        None,
+    );
+    user.roles = vec![Role::Admin];
+    Some(user)
+});
+
+pub static STATE_FILE: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+    dotenv().ok();
+    std::env::var(env::STATE_FILE_ENV_VAR)
+        .ok()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+});
+
+pub const DEFAULT_AUDIT_LOG_FILE: &str = "audit.jsonl";
+
+pub static AUDIT_LOG_FILE: LazyLock<PathBuf> = LazyLock::new(|| {
+    dotenv().ok();
+    std::env::var(env::AUDIT_LOG_FILE_ENV_VAR)
+        .ok()
+        .filter(|path| !path.is_empty())
+        .unwrap_or_else(|| DEFAULT_AUDIT_LOG_FILE.to_string())
+        .into()
+});
+
+pub static OTLP_ENDPOINT: LazyLock<Option<String>> = LazyLock::new(|| {
+    dotenv().ok();
+    std::env::var(env::OTLP_ENDPOINT_ENV_VAR)
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty())
+});
+
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+// how often expired challenges and pending logins are cleared out
+pub const DEFAULT_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+pub static DRAIN_TIMEOUT: LazyLock<Duration> = LazyLock::new(|| {
+    dotenv().ok();
+    match std::env::var(env::DRAIN_TIMEOUT_ENV_VAR) {
+        Ok(seconds) => seconds
+            .parse()
+            .map(Duration::from_secs)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "{} must be a number of seconds!",
+                    env::DRAIN_TIMEOUT_ENV_VAR
+                )
+            }),
+        Err(_) => DEFAULT_DRAIN_TIMEOUT,
+    }
+});
+
+pub static TLS: LazyLock<Option<TlsSettings>> = LazyLock::new(|| {
+    dotenv().ok();
+    let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let (cert_path, key_path) = match (
+        var(env::TLS_CERT_FILE_ENV_VAR),
+        var(env::TLS_KEY_FILE_ENV_VAR),
+    ) {
+        (Some(cert_path), Some(key_path)) => (cert_path, key_path),
+        (None, None) => return None,
+        _ => panic!(
+            "{} and {} must be set together!",
+            env::TLS_CERT_FILE_ENV_VAR,
+            env::TLS_KEY_FILE_ENV_VAR
+        ),
+    };
+
+    Some(TlsSettings {
+        cert_path: cert_path.into(),
+        key_path: key_path.into(),
+        redirect_address: var(env::TLS_REDIRECT_ADDRESS_ENV_VAR),
+        reload_interval: DEFAULT_RELOAD_INTERVAL,
+    })
+});
+
+pub static POSTMARK: LazyLock<PostmarkSettings> = LazyLock::new(|| {
+    dotenv().ok();
+    let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let server_token = var(env::POSTMARK_SERVER_TOKEN_ENV_VAR)
+        .unwrap_or_else(|| panic!("{} must be set!", env::POSTMARK_SERVER_TOKEN_ENV_VAR));
+    let sender = var(env::EMAIL_SENDER_ENV_VAR)
+        .and_then(|sender| sender.parse().ok())
+        .unwrap_or_else(|| panic!("{} must be an email address!", env::EMAIL_SENDER_ENV_VAR));
+    let base_url = var(env::POSTMARK_BASE_URL_ENV_VAR)
+        .unwrap_or_else(|| DEFAULT_POSTMARK_BASE_URL.to_string())
+        .parse()
+        .unwrap_or_else(|_| panic!("{} must be a URL!", env::POSTMARK_BASE_URL_ENV_VAR));
+
+    PostmarkSettings {
+        base_url,
+        sender,
+        server_token,
+    }
+});
+
+pub static SECURITY_HEADERS: LazyLock<SecurityHeaders> = LazyLock::new(|| {
+    dotenv().ok();
+    let header = |name: &str, default: Option<HeaderValue>| match std::env::var(name) {
+        Ok(value) if value.is_empty() => None,
+        Ok(value) => Some(
+            HeaderValue::try_from(value)
+                .unwrap_or_else(|_| panic!("{name} must be a valid header value!")),
+        ),
+        Err(_) => default,
+    };
+    let defaults = SecurityHeaders::default();
+
+    SecurityHeaders {
+        strict_transport_security: header(
+            env::STRICT_TRANSPORT_SECURITY_ENV_VAR,
+            defaults.strict_transport_security,
+        ),
+        content_security_policy: header(
+            env::CONTENT_SECURITY_POLICY_ENV_VAR,
+            defaults.content_security_policy,
+        ),
+        frame_options: header(env::FRAME_OPTIONS_ENV_VAR, defaults.frame_options),
+        referrer_policy: header(env::REFERRER_POLICY_ENV_VAR, defaults.referrer_policy),
+    }
+});
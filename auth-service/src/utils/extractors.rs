@@ -0,0 +1,280 @@
+use std::{convert::Infallible, marker::PhantomData, net::SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts},
+};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, DeviceInfo, Email, OAuthError, RequiredPermission, ServiceScope, SessionId,
+    },
+    utils::{
+        audit::Audit,
+        auth::{
+            AccessTokenClaims, Claims, ServiceClaims, validate_access_token,
+            validate_service_token, validate_token,
+        },
+        constants::JWT_COOKIE_NAME,
+    },
+};
+
+/// The user behind a valid, non-banned JWT cookie.
+pub struct AuthenticatedUser {
+    pub email: Email,
+    pub token: String,
+    pub claims: Claims,
+}
+
+impl FromRequestParts<AppState> for AuthenticatedUser {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let jar = CookieJar::from_headers(&parts.headers);
+        let token = jar
+            .get(JWT_COOKIE_NAME)
+            .ok_or(AuthAPIError::MissingToken)?
+            .value()
+            .to_string();
+
+        let Ok(audit) = Audit::from_request_parts(parts, state).await;
+        let claims = validate_token(
+            state.banned_token_store.clone(),
+            state.user_store.clone(),
+            &audit,
+            &token,
+        )
+        .await?;
+        let email = claims
+            .subject
+            .parse()
+            .map_err(|_| AuthAPIError::InvalidToken)?;
+        audit.subject(&email);
+        touch_session(state, &claims).await;
+
+        Ok(Self {
+            email,
+            token,
+            claims,
+        })
+    }
+}
+
+/// An `AuthenticatedUser` whose roles grant permission `P`, e.g. `AuthorizedUser<ManageUsers>`.
+pub struct AuthorizedUser<P> {
+    pub user: AuthenticatedUser,
+    permission: PhantomData<P>,
+}
+
+impl<P: RequiredPermission> FromRequestParts<AppState> for AuthorizedUser<P> {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let user = AuthenticatedUser::from_request_parts(parts, state).await?;
+        // the claims can be trusted, role changes revoke the user's tokens
+        if !user.claims.permissions.contains(&P::PERMISSION) {
+            return Err(AuthAPIError::Forbidden);
+        }
+
+        Ok(Self {
+            user,
+            permission: PhantomData,
+        })
+    }
+}
+
+/// The user behind a valid, non-banned `Authorization: Bearer` OAuth access token.
+pub struct BearerUser {
+    pub email: Email,
+    pub token: String,
+    pub claims: AccessTokenClaims,
+}
+
+impl FromRequestParts<AppState> for BearerUser {
+    type Rejection = OAuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(parts)?;
+
+        let Ok(audit) = Audit::from_request_parts(parts, state).await;
+        let claims = validate_access_token(
+            state.banned_token_store.clone(),
+            state.user_store.clone(),
+            &audit,
+            &token,
+        )
+        .await?;
+        let email = claims
+            .claims
+            .subject
+            .parse()
+            .map_err(|_| OAuthError::InvalidToken)?;
+        audit.subject(&email);
+        touch_session(state, &claims.claims).await;
+
+        Ok(Self {
+            email,
+            token,
+            claims,
+        })
+    }
+}
+
+/// The service client behind a valid, non-banned `Authorization: Bearer` service token granted
+/// scope `S`, e.g. `AuthenticatedService<VerifyTokens>`.
+pub struct AuthenticatedService<S> {
+    pub client_id: String,
+    pub token: String,
+    pub claims: ServiceClaims,
+    scope: PhantomData<S>,
+}
+
+impl<S: ServiceScope> FromRequestParts<AppState> for AuthenticatedService<S> {
+    type Rejection = OAuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(parts)?;
+
+        let claims = validate_service_token(state.banned_token_store.clone(), &token).await?;
+        if !claims.has_scope(S::NAME) {
+            return Err(OAuthError::InsufficientScope);
+        }
+        let client_id = claims
+            .client_id()
+            .ok_or(OAuthError::InvalidToken)?
+            .to_string();
+
+        Ok(Self {
+            client_id,
+            token,
+            claims,
+            scope: PhantomData,
+        })
+    }
+}
+
+/// The device a request came from, for recording sessions.
+impl<S: Send + Sync> FromRequestParts<S> for DeviceInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string());
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+
+        Ok(Self::new(ip, user_agent))
+    }
+}
+
+/// Marks the token's session as just used.
+pub(crate) async fn touch_session(state: &AppState, claims: &Claims) {
+    let id = SessionId::from_token_id(claims.id.clone());
+    // tokens without a session, e.g. from before a restart, are still good until they expire
+    let _ = state
+        .session_store
+        .touch_session(&id, Utc::now().timestamp())
+        .await;
+}
+
+fn bearer_token(parts: &Parts) -> Result<String, OAuthError> {
+    parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(String::from)
+        .ok_or(OAuthError::InvalidToken)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::Request;
+
+    use crate::{
+        domain::{SCOPE_TOKENS_VERIFY, User, VerifyTokens},
+        utils::auth::{generate_auth_token, generate_service_token},
+    };
+
+    use super::*;
+
+    fn app_state() -> AppState {
+        AppState::new_tester(
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+            Vec::new(),
+        )
+    }
+
+    async fn extract(token: &str) -> Result<AuthenticatedService<VerifyTokens>, OAuthError> {
+        let (mut parts, _) = Request::builder()
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        AuthenticatedService::from_request_parts(&mut parts, &app_state()).await
+    }
+
+    #[tokio::test]
+    async fn test_service_with_scope_is_authenticated() {
+        let token =
+            generate_service_token("app-service", &[SCOPE_TOKENS_VERIFY.to_string()]).unwrap();
+
+        let service = extract(&token).await.unwrap();
+
+        assert_eq!(service.client_id, "app-service");
+        assert_eq!(service.token, token);
+    }
+
+    #[tokio::test]
+    async fn test_service_without_scope_is_forbidden() {
+        let token = generate_service_token("app-service", &[]).unwrap();
+
+        assert_eq!(
+            extract(&token).await.err(),
+            Some(OAuthError::InsufficientScope)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_user_token_is_not_a_service() {
+        let user = User::new(
+            "a@b.com".parse().unwrap(),
+            "password".parse().unwrap(),
+            None,
+        );
+        let token = generate_auth_token(&user).unwrap();
+
+        assert_eq!(extract(&token).await.err(), Some(OAuthError::InvalidToken));
+    }
+}
@@ -0,0 +1,360 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus_client::{
+    encoding::{EncodeLabelSet, EncodeLabelValue, text::encode},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        histogram::{Histogram, exponential_buckets},
+    },
+    registry::Registry,
+};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEvent, AuditEventKind, AuditOutcome, ReadMetrics, UserStoreError},
+    utils::extractors::AuthenticatedService,
+};
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RequestLabels {
+    method: String,
+    // the route's template, so ids in paths don't blow up the number of series
+    route: String,
+    status: u16,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct LoginLabels {
+    method: LoginMethod,
+    outcome: LoginOutcome,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+#[allow(non_camel_case_types)]
+enum LoginMethod {
+    password,
+    magic_link,
+    federated,
+    passkey,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+#[allow(non_camel_case_types)]
+enum LoginOutcome {
+    success,
+    failure,
+    second_factor_required,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TwoFactorLabels {
+    result: TwoFactorResult,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+#[allow(non_camel_case_types)]
+enum TwoFactorResult {
+    challenged,
+    passed,
+    failed,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TokenRejectionLabels {
+    reason: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct UserStoreErrorLabels {
+    error: String,
+}
+
+/// Counters and histograms served from `/metrics` in the Prometheus text format.
+pub struct Metrics {
+    registry: Registry,
+    requests: Family<RequestLabels, Counter>,
+    request_duration: Family<RequestLabels, Histogram>,
+    logins: Family<LoginLabels, Counter>,
+    two_factor: Family<TwoFactorLabels, Counter>,
+    token_rejections: Family<TokenRejectionLabels, Counter>,
+    banned_token_hits: Counter,
+    user_store_errors: Family<UserStoreErrorLabels, Counter>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let requests = Family::default();
+        let request_duration = Family::<RequestLabels, Histogram>::new_with_constructor(|| {
+            // 5ms to 10s
+            Histogram::new(exponential_buckets(0.005, 2.0, 12))
+        });
+        let logins = Family::default();
+        let two_factor = Family::default();
+        let token_rejections = Family::default();
+        let banned_token_hits = Counter::default();
+        let user_store_errors = Family::default();
+
+        let mut registry = Registry::with_prefix("auth");
+        registry.register(
+            "http_requests",
+            "Requests handled, by route and status",
+            requests.clone(),
+        );
+        registry.register(
+            "http_request_duration_seconds",
+            "Time taken to handle requests, by route and status",
+            request_duration.clone(),
+        );
+        registry.register("logins", "Login attempts, by outcome", logins.clone());
+        registry.register(
+            "two_factor_challenges",
+            "Second factors asked for, and how checking them went",
+            two_factor.clone(),
+        );
+        registry.register(
+            "token_rejections",
+            "Auth tokens turned down, by reason",
+            token_rejections.clone(),
+        );
+        registry.register(
+            "banned_token_hits",
+            "Auth tokens turned down for having been banned",
+            banned_token_hits.clone(),
+        );
+        registry.register(
+            "user_store_errors",
+            "Errors returned by the user store, by type",
+            user_store_errors.clone(),
+        );
+
+        Self {
+            registry,
+            requests,
+            request_duration,
+            logins,
+            two_factor,
+            token_rejections,
+            banned_token_hits,
+            user_store_errors,
+        }
+    }
+}
+
+impl Metrics {
+    pub fn encode(&self) -> String {
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry).expect("writing to a string can't fail");
+        buffer
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: StatusCode, seconds: f64) {
+        let labels = RequestLabels {
+            method: method_label(method).to_string(),
+            route: route.to_string(),
+            status: status.as_u16(),
+        };
+        self.requests.get_or_create(&labels).inc();
+        self.request_duration
+            .get_or_create(&labels)
+            .observe(seconds);
+    }
+
+    pub fn two_factor_challenged(&self) {
+        self.two_factor
+            .get_or_create(&TwoFactorLabels {
+                result: TwoFactorResult::challenged,
+            })
+            .inc();
+    }
+
+    pub fn user_store_error(&self, error: &UserStoreError) {
+        let labels = UserStoreErrorLabels {
+            error: format!("{error:?}"),
+        };
+        self.user_store_errors.get_or_create(&labels).inc();
+    }
+
+    /// Counts the auth specific outcomes the audit log already tells apart.
+    pub fn observe_audit_event(&self, event: &AuditEvent) {
+        let succeeded = event.outcome == AuditOutcome::Success;
+        let login = |method, outcome| {
+            self.logins
+                .get_or_create(&LoginLabels { method, outcome })
+                .inc();
+        };
+        let login_outcome = if succeeded {
+            LoginOutcome::success
+        } else {
+            LoginOutcome::failure
+        };
+
+        match event.kind {
+            AuditEventKind::Login => login(LoginMethod::password, login_outcome),
+            AuditEventKind::LoginPendingSecondFactor => {
+                login(LoginMethod::password, LoginOutcome::second_factor_required)
+            }
+            AuditEventKind::MagicLinkLogin => login(LoginMethod::magic_link, login_outcome),
+            AuditEventKind::FederatedLogin => login(LoginMethod::federated, login_outcome),
+            AuditEventKind::PasskeyLogin => login(LoginMethod::passkey, login_outcome),
+            AuditEventKind::SecondFactor => {
+                let result = if succeeded {
+                    TwoFactorResult::passed
+                } else {
+                    TwoFactorResult::failed
+                };
+                self.two_factor
+                    .get_or_create(&TwoFactorLabels { result })
+                    .inc();
+            }
+            AuditEventKind::TokenRejected => {
+                if let AuditOutcome::Failure { reason } = &event.outcome {
+                    if reason == "banned" {
+                        self.banned_token_hits.inc();
+                    }
+                    let labels = TokenRejectionLabels {
+                        reason: reason.clone(),
+                    };
+                    self.token_rejections.get_or_create(&labels).inc();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// clients can send any method they like, each of which would be a new series
+fn method_label(method: &str) -> &str {
+    match method {
+        "GET" | "HEAD" | "POST" | "PUT" | "DELETE" | "CONNECT" | "OPTIONS" | "TRACE" | "PATCH" => {
+            method
+        }
+        _ => "other",
+    }
+}
+
+/// Times each request, counting it under its route.
+pub async fn track_requests(
+    State(state): State<AppState>,
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    // the assets fallback has no route, and counting every path it's asked for would be
+    // unbounded
+    let route = matched_path
+        .as_ref()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    state.metrics.observe_request(
+        &method,
+        &route,
+        response.status(),
+        start.elapsed().as_secs_f64(),
+    );
+    response
+}
+
+pub async fn metrics(
+    State(state): State<AppState>,
+    _: AuthenticatedService<ReadMetrics>,
+) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, CONTENT_TYPE)],
+        state.metrics.encode(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: AuditEventKind, outcome: AuditOutcome) -> AuditEvent {
+        AuditEvent {
+            timestamp: 0,
+            kind,
+            email: None,
+            actor: None,
+            request_id: None,
+            ip: None,
+            user_agent: None,
+            outcome,
+        }
+    }
+
+    fn failure(reason: &str) -> AuditOutcome {
+        AuditOutcome::Failure {
+            reason: reason.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_audit_events_are_counted() {
+        let metrics = Metrics::default();
+
+        metrics.observe_audit_event(&event(AuditEventKind::Login, AuditOutcome::Success));
+        metrics.observe_audit_event(&event(AuditEventKind::Login, failure("Nope")));
+        metrics.observe_audit_event(&event(AuditEventKind::TokenRejected, failure("banned")));
+        metrics.observe_audit_event(&event(AuditEventKind::SecondFactor, AuditOutcome::Success));
+        metrics.observe_audit_event(&event(AuditEventKind::Signup, AuditOutcome::Success));
+        metrics.two_factor_challenged();
+        metrics.user_store_error(&UserStoreError::UserNotFound);
+
+        let text = metrics.encode();
+        for line in [
+            r#"auth_logins_total{method="password",outcome="success"} 1"#,
+            r#"auth_logins_total{method="password",outcome="failure"} 1"#,
+            r#"auth_token_rejections_total{reason="banned"} 1"#,
+            "auth_banned_token_hits_total 1",
+            r#"auth_two_factor_challenges_total{result="passed"} 1"#,
+            r#"auth_two_factor_challenges_total{result="challenged"} 1"#,
+            r#"auth_user_store_errors_total{error="UserNotFound"} 1"#,
+        ] {
+            assert!(text.contains(line), "missing {line} in\n{text}");
+        }
+    }
+
+    #[test]
+    fn test_requests_are_counted_by_route() {
+        let metrics = Metrics::default();
+
+        metrics.observe_request("DELETE", "/sessions/{id}", StatusCode::NO_CONTENT, 0.01);
+        metrics.observe_request("DELETE", "/sessions/{id}", StatusCode::NO_CONTENT, 0.02);
+
+        let text = metrics.encode();
+        assert!(text.contains(
+            r#"auth_http_requests_total{method="DELETE",route="/sessions/{id}",status="204"} 2"#
+        ));
+        assert!(text.contains(
+            r#"auth_http_request_duration_seconds_count{method="DELETE",route="/sessions/{id}",status="204"} 2"#
+        ));
+    }
+
+    #[test]
+    fn test_unknown_methods_are_counted_as_other() {
+        let metrics = Metrics::default();
+
+        metrics.observe_request("BREW", "unmatched", StatusCode::NOT_FOUND, 0.01);
+        metrics.observe_request("WHEN", "unmatched", StatusCode::NOT_FOUND, 0.01);
+
+        let text = metrics.encode();
+        assert!(text.contains(
+            r#"auth_http_requests_total{method="other",route="unmatched",status="404"} 2"#
+        ));
+        assert!(!text.contains("BREW"));
+    }
+}
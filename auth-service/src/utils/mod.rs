@@ -1,2 +1,14 @@
+pub mod audit;
 pub mod auth;
 pub mod constants;
+pub mod extractors;
+pub mod federation;
+pub mod metrics;
+pub mod problem_details;
+pub mod security_headers;
+pub mod shutdown;
+pub mod signing_key;
+pub mod sweeper;
+pub mod telemetry;
+pub mod tls;
+pub mod webauthn;
@@ -0,0 +1,50 @@
+use axum::{
+    Json,
+    extract::Request,
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{ErrorResponse, ProblemDetails};
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Turns error responses into RFC 7807 problem details for clients that accept them, leaving
+/// everyone else with the plain `ErrorResponse`.
+pub async fn negotiate_problem_details(request: Request, next: Next) -> Response {
+    let accepts_problem_details = request
+        .headers()
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|accept| accept.to_str().ok())
+        .any(|accept| accept.contains(PROBLEM_JSON));
+
+    let response = next.run(request).await;
+    if !accepts_problem_details {
+        return response;
+    }
+    let Some(error) = response.extensions().get::<ErrorResponse>().cloned() else {
+        return response;
+    };
+
+    let (mut parts, _) = response.into_parts();
+    let problem = ProblemDetails {
+        // the status says it all, `code` tells errors with the same one apart
+        problem_type: "about:blank".to_string(),
+        title: parts
+            .status
+            .canonical_reason()
+            .unwrap_or_default()
+            .to_string(),
+        status: parts.status.as_u16(),
+        detail: error.error,
+        code: error.code,
+        errors: error.details,
+    };
+    let mut body = Json(problem).into_response();
+    body.headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    parts.headers.extend(body.headers_mut().drain());
+    Response::from_parts(parts, body.into_body())
+}
@@ -0,0 +1,155 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::watch;
+
+/// Stops an `Application` from outside the task running it: from a signal handler in
+/// production, or deterministically at the end of a test.
+#[derive(Clone, Debug)]
+pub struct ShutdownHandle {
+    requested: Arc<watch::Sender<bool>>,
+    drained: Arc<watch::Sender<bool>>,
+    finished: Arc<watch::Sender<bool>>,
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        Self {
+            requested: Arc::new(watch::channel(false).0),
+            drained: Arc::new(watch::channel(false).0),
+            finished: Arc::new(watch::channel(false).0),
+        }
+    }
+}
+
+impl ShutdownHandle {
+    /// Stops the server taking new connections, leaving the requests in flight to finish.
+    pub fn trigger(&self) {
+        self.requested.send_replace(true);
+    }
+
+    /// Triggers shutdown and waits until the server has drained its connections and flushed
+    /// what it buffers, like the audit log. Never returns unless the application is running.
+    pub async fn shutdown(&self) {
+        self.trigger();
+        let _ = self
+            .finished
+            .subscribe()
+            .wait_for(|finished| *finished)
+            .await;
+    }
+
+    pub(crate) async fn requested(&self) {
+        wait_for(&self.requested).await;
+    }
+
+    /// Once shutdown is triggered, gives requests in flight `drain_timeout` to finish.
+    pub(crate) async fn drain(&self, drain_timeout: Duration) {
+        self.requested().await;
+        tracing::info!("shutting down, draining connections");
+        tokio::time::sleep(drain_timeout).await;
+        self.drained.send_replace(true);
+    }
+
+    async fn drained(&self) {
+        wait_for(&self.drained).await;
+    }
+
+    pub(crate) fn finish(&self) {
+        self.finished.send_replace(true);
+    }
+}
+
+async fn wait_for(flag: &watch::Sender<bool>) {
+    let _ = flag.subscribe().wait_for(|set| *set).await;
+}
+
+/// Cuts requests still running after the drain timeout short, so their connections close.
+pub async fn abort_when_drained(
+    State(shutdown): State<ShutdownHandle>,
+    request: Request,
+    next: Next,
+) -> Response {
+    tokio::select! {
+        response = next.run(request) => response,
+        _ = shutdown.drained() => {
+            tracing::warn!("request still running after the drain timeout, aborting it");
+            StatusCode::SERVICE_UNAVAILABLE.into_response()
+        }
+    }
+}
+
+/// Resolves on SIGINT, i.e. Ctrl+C, or SIGTERM, which `docker stop` sends.
+pub async fn os_signal() {
+    let interrupt = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for SIGINT!");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM!")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drained_after_timeout() {
+        let handle = ShutdownHandle::default();
+        let server = handle.clone();
+        tokio::spawn(async move { server.drain(Duration::from_millis(10)).await });
+
+        handle.trigger();
+
+        tokio::time::timeout(Duration::from_secs(1), handle.drained())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_until_finished() {
+        let handle = ShutdownHandle::default();
+        let server = handle.clone();
+        let running = tokio::spawn(async move {
+            server.requested().await;
+            server.finish();
+        });
+
+        tokio::time::timeout(Duration::from_secs(1), handle.shutdown())
+            .await
+            .unwrap();
+        running.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_requested_once_triggered_before() {
+        let handle = ShutdownHandle::default();
+
+        handle.trigger();
+
+        tokio::time::timeout(Duration::from_secs(1), handle.requested())
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use p256::{
+    SecretKey,
+    elliptic_curve::sec1::ToEncodedPoint,
+    pkcs8::{DecodePrivateKey, EncodePrivateKey},
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The ES256 key ID tokens are signed with. Relying parties check them against its public
+/// half, published at `/jwks.json`, so they never need the service's own secret.
+pub struct SigningKey {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Jwk,
+}
+
+impl SigningKey {
+    /// A fresh key, good until the process exits.
+    pub fn generate() -> Self {
+        loop {
+            let mut bytes = [0; 32];
+            rand::rng().fill_bytes(&mut bytes);
+            // all but a vanishing few 32 byte strings are valid P-256 scalars
+            if let Ok(key) = SecretKey::from_slice(&bytes) {
+                return Self::new(&key);
+            }
+        }
+    }
+
+    /// Reads a PKCS#8 PEM P-256 key, e.g. from `openssl genpkey -algorithm EC
+    /// -pkeyopt ec_paramgen_curve:P-256`.
+    pub fn from_pem(pem: &str) -> Result<Self, p256::pkcs8::Error> {
+        Ok(Self::new(&SecretKey::from_pkcs8_pem(pem)?))
+    }
+
+    fn new(key: &SecretKey) -> Self {
+        let der = key
+            .to_pkcs8_der()
+            .expect("P-256 keys always encode as PKCS#8");
+        let point = key.public_key().to_encoded_point(false);
+        let x = URL_SAFE_NO_PAD.encode(point.x().expect("uncompressed points have x"));
+        let y = URL_SAFE_NO_PAD.encode(point.y().expect("uncompressed points have y"));
+
+        Self {
+            encoding_key: EncodingKey::from_ec_der(der.as_bytes()),
+            decoding_key: DecodingKey::from_ec_der(point.as_bytes()),
+            jwk: Jwk::p256(x, y),
+        }
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    pub fn jwk(&self) -> &Jwk {
+        &self.jwk
+    }
+}
+
+/// A public key as published in a JWK Set (RFC 7517).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    pub y: String,
+    #[serde(rename = "use")]
+    pub usage: String,
+    pub alg: String,
+    pub kid: String,
+}
+
+impl Jwk {
+    fn p256(x: String, y: String) -> Self {
+        // the key's RFC 7638 thumbprint, so a new key always gets a new id
+        let thumbprint = format!(r#"{{"crv":"P-256","kty":"EC","x":"{x}","y":"{y}"}}"#);
+        let kid = URL_SAFE_NO_PAD.encode(Sha256::digest(thumbprint.as_bytes()));
+
+        Self {
+            kty: "EC".to_string(),
+            crv: "P-256".to_string(),
+            x,
+            y,
+            usage: "sig".to_string(),
+            alg: "ES256".to_string(),
+            kid,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{Algorithm, Header, Validation, decode, encode};
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    // the key from RFC 7515 appendix A.3
+    const D: &str = "jpsQnnGQmL-YBIffH1136cspYG6-0iY7X1fCE9-E9LI";
+
+    #[test]
+    fn test_jwk_from_pem() {
+        let secret = SecretKey::from_slice(&URL_SAFE_NO_PAD.decode(D).unwrap()).unwrap();
+        let pem = secret.to_pkcs8_pem(Default::default()).unwrap();
+
+        let key = SigningKey::from_pem(&pem).unwrap();
+
+        let jwk = key.jwk();
+        assert_eq!(jwk.x, "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU");
+        assert_eq!(jwk.y, "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0");
+        assert_eq!((jwk.kty.as_str(), jwk.alg.as_str()), ("EC", "ES256"));
+    }
+
+    #[test]
+    fn test_signs_and_verifies() {
+        let key = SigningKey::generate();
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(key.jwk().kid.clone());
+
+        let token = encode(&header, &json!({ "exp": u32::MAX }), key.encoding_key()).unwrap();
+
+        let validation = Validation::new(Algorithm::ES256);
+        assert!(decode::<Value>(&token, key.decoding_key(), &validation).is_ok());
+        let other = SigningKey::generate();
+        assert!(decode::<Value>(&token, other.decoding_key(), &validation).is_err());
+    }
+
+    #[test]
+    fn test_rejects_other_keys() {
+        assert!(SigningKey::from_pem("not a key").is_err());
+    }
+}
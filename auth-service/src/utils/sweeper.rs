@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{app_state::AppState, utils::shutdown::ShutdownHandle};
+
+/// Drops expired entries from the stores every `interval` until shutdown. They're refused
+/// when used anyway, this only stops abandoned ones from piling up.
+pub async fn sweep_expired(app_state: AppState, interval: Duration, shutdown: ShutdownHandle) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => sweep(&app_state).await,
+            _ = shutdown.requested() => return,
+        }
+    }
+}
+
+async fn sweep(app_state: &AppState) {
+    let now = Utc::now().timestamp();
+    match app_state.passkey_store.remove_expired_challenges(now).await {
+        Ok(removed) => tracing::debug!(removed, "swept expired passkey challenges"),
+        Err(e) => tracing::warn!(error = ?e, "failed to sweep expired passkey challenges"),
+    }
+    match app_state
+        .federated_login_store
+        .remove_expired_logins(now)
+        .await
+    {
+        Ok(removed) => tracing::debug!(removed, "swept expired federated logins"),
+        Err(e) => tracing::warn!(error = ?e, "failed to sweep expired federated logins"),
+    }
+    match app_state.client_store.remove_expired_codes(now).await {
+        Ok(removed) => tracing::debug!(removed, "swept expired authorization codes"),
+        Err(e) => tracing::warn!(error = ?e, "failed to sweep expired authorization codes"),
+    }
+    match app_state.session_store.remove_expired_sessions(now).await {
+        Ok(removed) => tracing::debug!(removed, "swept expired sessions"),
+        Err(e) => tracing::warn!(error = ?e, "failed to sweep expired sessions"),
+    }
+}
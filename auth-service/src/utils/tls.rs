@@ -0,0 +1,324 @@
+use std::{
+    fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, header, uri::Authority},
+    response::{IntoResponse, Redirect, Response},
+    serve::Listener,
+};
+use rustls::{
+    ServerConfig,
+    crypto::{CryptoProvider, ring},
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use thiserror::Error;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{Semaphore, mpsc},
+    task::JoinHandle,
+};
+use tokio_rustls::{TlsAcceptor, server::TlsStream};
+
+use crate::utils::shutdown::ShutdownHandle;
+
+pub const DEFAULT_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+// long enough for a slow network, short enough that idle sockets don't pile up
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// a handshake costs the server far more than the client, so only this many run at once.
+// Connections past that wait in the OS's backlog
+const MAX_CONCURRENT_HANDSHAKES: usize = 256;
+
+/// Serves HTTPS with the PEM encoded certificate chain and private key at these paths.
+#[derive(Clone, Debug)]
+pub struct TlsSettings {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    // plain HTTP requests here are redirected to HTTPS
+    pub redirect_address: Option<String>,
+    // how often the files are checked for a renewed certificate
+    pub reload_interval: Duration,
+}
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("failed to read {0}: {1}")]
+    Pem(PathBuf, rustls::pki_types::pem::Error),
+    #[error("no certificate in {0}")]
+    NoCertificate(PathBuf),
+    #[error("unusable certificate or key: {0}")]
+    Rustls(#[from] rustls::Error),
+}
+
+/// Hands out the certificate last loaded, swapping in a new one whenever the files change, so
+/// renewing it doesn't take a restart.
+#[derive(Debug)]
+pub struct CertificateResolver {
+    settings: TlsSettings,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+    // when the files were last changed, as of the last check
+    modified: Mutex<Option<(SystemTime, SystemTime)>>,
+}
+
+impl CertificateResolver {
+    pub fn load(settings: TlsSettings) -> Result<Self, TlsError> {
+        let provider = Arc::new(ring::default_provider());
+        let modified = modified(&settings);
+        let current = load_certified_key(&settings, &provider)?;
+
+        Ok(Self {
+            settings,
+            provider,
+            current: RwLock::new(Arc::new(current)),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    pub fn server_config(self: &Arc<Self>) -> Result<ServerConfig, TlsError> {
+        let mut config = ServerConfig::builder_with_provider(self.provider.clone())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone());
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(config)
+    }
+
+    /// Loads the certificate again if its files changed since last time. A certificate that
+    /// fails to load, e.g. as it's only half written, leaves the current one in place.
+    pub fn reload_if_changed(&self) {
+        let modified = modified(&self.settings);
+        {
+            let Ok(mut last_modified) = self.modified.lock() else {
+                return;
+            };
+            if *last_modified == modified {
+                return;
+            }
+            *last_modified = modified;
+        }
+
+        match load_certified_key(&self.settings, &self.provider) {
+            Ok(certified_key) => {
+                if let Ok(mut current) = self.current.write() {
+                    *current = Arc::new(certified_key);
+                    tracing::info!("reloaded TLS certificate");
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "failed to reload TLS certificate"),
+        }
+    }
+
+    /// Checks for a new certificate every `reload_interval` until shutdown.
+    pub async fn watch(self: Arc<Self>, shutdown: ShutdownHandle) {
+        let mut interval = tokio::time::interval(self.settings.reload_interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => self.reload_if_changed(),
+                _ = shutdown.requested() => return,
+            }
+        }
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.current.read().ok().map(|current| current.clone())
+    }
+}
+
+fn modified(settings: &TlsSettings) -> Option<(SystemTime, SystemTime)> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    Some((
+        modified(&settings.cert_path).ok()?,
+        modified(&settings.key_path).ok()?,
+    ))
+}
+
+fn load_certified_key(
+    settings: &TlsSettings,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, TlsError> {
+    let cert_chain = CertificateDer::pem_file_iter(&settings.cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| TlsError::Pem(settings.cert_path.clone(), e))?;
+    if cert_chain.is_empty() {
+        return Err(TlsError::NoCertificate(settings.cert_path.clone()));
+    }
+    let key = PrivateKeyDer::from_pem_file(&settings.key_path)
+        .map_err(|e| TlsError::Pem(settings.key_path.clone(), e))?;
+
+    Ok(CertifiedKey::from_der(cert_chain, key, provider)?)
+}
+
+/// Accepts TCP connections, completing their TLS handshakes in the background so a slow
+/// client can't hold up everyone else's.
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    handshaken: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    accepting: JoinHandle<()>,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, config: ServerConfig) -> io::Result<Self> {
+        Self::with_handshake_limit(listener, config, MAX_CONCURRENT_HANDSHAKES)
+    }
+
+    fn with_handshake_limit(
+        listener: TcpListener,
+        config: ServerConfig,
+        max_handshakes: usize,
+    ) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let (sender, handshaken) = mpsc::channel(64);
+        let accepting = tokio::spawn(accept(
+            listener,
+            TlsAcceptor::from(Arc::new(config)),
+            sender,
+            Arc::new(Semaphore::new(max_handshakes)),
+        ));
+
+        Ok(Self {
+            local_addr,
+            handshaken,
+            accepting,
+        })
+    }
+}
+
+impl Drop for TlsListener {
+    fn drop(&mut self) {
+        self.accepting.abort();
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.handshaken.recv().await {
+            Some(connection) => connection,
+            // only once the task accepting connections is gone, i.e. never while serving
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+async fn accept(
+    mut listener: TcpListener,
+    acceptor: TlsAcceptor,
+    handshaken: mpsc::Sender<(TlsStream<TcpStream>, SocketAddr)>,
+    handshakes: Arc<Semaphore>,
+) {
+    loop {
+        // held until the connection is handed over, or given up on
+        let Ok(permit) = handshakes.clone().acquire_owned().await else {
+            return;
+        };
+        // retries on its own when accepting fails
+        let (stream, address) = Listener::accept(&mut listener).await;
+        let acceptor = acceptor.clone();
+        let handshaken = handshaken.clone();
+
+        tokio::spawn(async move {
+            match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => {
+                    let _ = handshaken.send((stream, address)).await;
+                }
+                Ok(Err(e)) => tracing::debug!(error = %e, %address, "TLS handshake failed"),
+                Err(_) => tracing::debug!(%address, "TLS handshake timed out"),
+            }
+            drop(permit);
+        });
+    }
+}
+
+/// Sends plain HTTP requests to the same host and path over HTTPS, on `https_port`.
+pub async fn redirect_to_https(State(https_port): State<u16>, request: Request) -> Response {
+    let Some(authority) = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<Authority>().ok())
+    else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let path = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |path| path.as_str());
+
+    let host = authority.host();
+    let location = match https_port {
+        443 => format!("https://{host}{path}"),
+        port => format!("https://{host}:{port}{path}"),
+    };
+    Redirect::permanent(&location).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use rustls::{ClientConfig, RootCertStore, pki_types::ServerName};
+    use tokio_rustls::TlsConnector;
+
+    use super::*;
+
+    // a server with a self-signed certificate for localhost, and a client trusting only it
+    fn configs() -> (ServerConfig, ClientConfig) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let key = PrivateKeyDer::from_pem_slice(certified.signing_key.serialize_pem().as_bytes())
+            .unwrap();
+        let provider = Arc::new(ring::default_provider());
+
+        let server = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certified.cert.der().clone()], key)
+            .unwrap();
+        let mut roots = RootCertStore::empty();
+        roots.add(certified.cert.der().clone()).unwrap();
+        let client = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        (server, client)
+    }
+
+    #[tokio::test]
+    async fn test_handshakes_past_the_limit_wait() {
+        let (server, client) = configs();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut listener = TlsListener::with_handshake_limit(listener, server, 1).unwrap();
+        let address = listener.local_addr;
+
+        // never says hello, holding the only handshake
+        let idle = TcpStream::connect(address).await.unwrap();
+        let waiting = tokio::spawn(async move {
+            let stream = TcpStream::connect(address).await.unwrap();
+            TlsConnector::from(Arc::new(client))
+                .connect(ServerName::try_from("localhost").unwrap(), stream)
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!waiting.is_finished());
+
+        drop(idle);
+        assert!(waiting.await.unwrap().is_ok());
+        Listener::accept(&mut listener).await;
+    }
+}
@@ -0,0 +1,321 @@
+use ciborium::Value;
+use p256::ecdsa::{Signature, VerifyingKey, signature::Verifier};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::{
+    domain::{Ceremony, PasskeyChallenge, PasskeyCredential},
+    utils::constants::{WEBAUTHN_ORIGIN, WEBAUTHN_RP_ID},
+};
+
+pub const COSE_ALG_ES256: i64 = -7;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+// rp id hash + flags + sign count
+const AUTHENTICATOR_DATA_MIN_LEN: usize = 37;
+// aaguid + credential id length
+const ATTESTED_CREDENTIAL_HEADER_LEN: usize = 18;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum WebAuthnError {
+    #[error("malformed client data")]
+    MalformedClientData,
+    #[error("malformed authenticator data")]
+    MalformedAuthenticatorData,
+    #[error("unsupported attestation or key type")]
+    Unsupported,
+    #[error("client data does not match the ceremony")]
+    CeremonyMismatch,
+    #[error("origin or relying party does not match")]
+    RelyingPartyMismatch,
+    #[error("user was not present")]
+    UserNotPresent,
+    #[error("invalid signature")]
+    InvalidSignature,
+    #[error("signature counter went backwards")]
+    SignCountRegressed,
+}
+
+/// The JSON the browser signs over, as passed back in `clientDataJSON`.
+#[derive(Debug, Deserialize)]
+pub struct ClientData {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub challenge: String,
+    pub origin: String,
+}
+
+impl ClientData {
+    pub fn parse(client_data_json: &[u8]) -> Result<Self, WebAuthnError> {
+        serde_json::from_slice(client_data_json).map_err(|_| WebAuthnError::MalformedClientData)
+    }
+
+    pub fn challenge(&self) -> PasskeyChallenge {
+        PasskeyChallenge::from_client_data(self.challenge.clone())
+    }
+
+    pub fn verify(&self, ceremony: Ceremony) -> Result<(), WebAuthnError> {
+        if self.kind != ceremony.client_data_type() {
+            return Err(WebAuthnError::CeremonyMismatch);
+        }
+        if self.origin != *WEBAUTHN_ORIGIN {
+            return Err(WebAuthnError::RelyingPartyMismatch);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct AuthenticatorData {
+    rp_id_hash: [u8; 32],
+    flags: u8,
+    sign_count: u32,
+    attested_credential: Option<PasskeyCredential>,
+}
+
+impl AuthenticatorData {
+    fn parse(bytes: &[u8]) -> Result<Self, WebAuthnError> {
+        if bytes.len() < AUTHENTICATOR_DATA_MIN_LEN {
+            return Err(WebAuthnError::MalformedAuthenticatorData);
+        }
+        let (rp_id_hash, rest) = bytes.split_at(32);
+        let flags = rest[0];
+        let sign_count = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]);
+
+        let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL_DATA != 0 {
+            Some(parse_attested_credential(
+                &bytes[AUTHENTICATOR_DATA_MIN_LEN..],
+                sign_count,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            rp_id_hash: rp_id_hash
+                .try_into()
+                .map_err(|_| WebAuthnError::MalformedAuthenticatorData)?,
+            flags,
+            sign_count,
+            attested_credential,
+        })
+    }
+
+    fn verify(&self) -> Result<(), WebAuthnError> {
+        if self.rp_id_hash != *Sha256::digest(WEBAUTHN_RP_ID.as_bytes()) {
+            return Err(WebAuthnError::RelyingPartyMismatch);
+        }
+        if self.flags & FLAG_USER_PRESENT == 0 {
+            return Err(WebAuthnError::UserNotPresent);
+        }
+        Ok(())
+    }
+}
+
+fn parse_attested_credential(
+    bytes: &[u8],
+    sign_count: u32,
+) -> Result<PasskeyCredential, WebAuthnError> {
+    if bytes.len() < ATTESTED_CREDENTIAL_HEADER_LEN {
+        return Err(WebAuthnError::MalformedAuthenticatorData);
+    }
+    let id_len = u16::from_be_bytes([bytes[16], bytes[17]]) as usize;
+    let id = bytes
+        .get(ATTESTED_CREDENTIAL_HEADER_LEN..ATTESTED_CREDENTIAL_HEADER_LEN + id_len)
+        .ok_or(WebAuthnError::MalformedAuthenticatorData)?;
+
+    // any extensions after the key are ignored
+    let cose_key: Value = ciborium::from_reader(&bytes[ATTESTED_CREDENTIAL_HEADER_LEN + id_len..])
+        .map_err(|_| WebAuthnError::MalformedAuthenticatorData)?;
+
+    Ok(PasskeyCredential {
+        id: id.to_vec(),
+        public_key: cose_key_to_sec1(&cose_key)?,
+        sign_count,
+    })
+}
+
+// only ES256 on P-256 is supported, which every platform authenticator offers
+fn cose_key_to_sec1(cose_key: &Value) -> Result<Vec<u8>, WebAuthnError> {
+    let map = cose_key
+        .as_map()
+        .ok_or(WebAuthnError::MalformedAuthenticatorData)?;
+    let get = |label: i64| {
+        map.iter()
+            .find(|(key, _)| key.as_integer() == Some(label.into()))
+            .map(|(_, value)| value)
+    };
+    let get_int = |label: i64| {
+        get(label)
+            .and_then(Value::as_integer)
+            .and_then(|value| i64::try_from(value).ok())
+    };
+
+    // kty: EC2, alg: ES256, crv: P-256
+    if get_int(1) != Some(2) || get_int(3) != Some(COSE_ALG_ES256) || get_int(-1) != Some(1) {
+        return Err(WebAuthnError::Unsupported);
+    }
+    let x = get(-2).and_then(Value::as_bytes);
+    let y = get(-3).and_then(Value::as_bytes);
+    let (Some(x), Some(y)) = (x, y) else {
+        return Err(WebAuthnError::MalformedAuthenticatorData);
+    };
+
+    let mut sec1 = Vec::with_capacity(1 + x.len() + y.len());
+    sec1.push(0x04);
+    sec1.extend_from_slice(x);
+    sec1.extend_from_slice(y);
+
+    VerifyingKey::from_sec1_bytes(&sec1).map_err(|_| WebAuthnError::MalformedAuthenticatorData)?;
+    Ok(sec1)
+}
+
+/// Pulls the new credential out of an attestation object. Only `none` attestation is accepted.
+pub fn verify_registration(attestation_object: &[u8]) -> Result<PasskeyCredential, WebAuthnError> {
+    let attestation: Value = ciborium::from_reader(attestation_object)
+        .map_err(|_| WebAuthnError::MalformedAuthenticatorData)?;
+    let map = attestation
+        .as_map()
+        .ok_or(WebAuthnError::MalformedAuthenticatorData)?;
+    let get = |label: &str| {
+        map.iter()
+            .find(|(key, _)| key.as_text() == Some(label))
+            .map(|(_, value)| value)
+    };
+
+    if get("fmt").and_then(Value::as_text) != Some("none") {
+        return Err(WebAuthnError::Unsupported);
+    }
+    let auth_data = get("authData")
+        .and_then(Value::as_bytes)
+        .ok_or(WebAuthnError::MalformedAuthenticatorData)?;
+
+    let auth_data = AuthenticatorData::parse(auth_data)?;
+    auth_data.verify()?;
+    auth_data
+        .attested_credential
+        .ok_or(WebAuthnError::MalformedAuthenticatorData)
+}
+
+/// Checks an assertion was signed by `credential`, returning the authenticator's new sign count.
+pub fn verify_assertion(
+    credential: &PasskeyCredential,
+    authenticator_data: &[u8],
+    client_data_json: &[u8],
+    signature: &[u8],
+) -> Result<u32, WebAuthnError> {
+    let auth_data = AuthenticatorData::parse(authenticator_data)?;
+    auth_data.verify()?;
+
+    let key = VerifyingKey::from_sec1_bytes(&credential.public_key)
+        .map_err(|_| WebAuthnError::InvalidSignature)?;
+    let signature = Signature::from_der(signature).map_err(|_| WebAuthnError::InvalidSignature)?;
+    let mut signed = authenticator_data.to_vec();
+    signed.extend_from_slice(&Sha256::digest(client_data_json));
+    key.verify(&signed, &signature)
+        .map_err(|_| WebAuthnError::InvalidSignature)?;
+
+    // authenticators that don't count always report 0, anything else has to keep going up
+    let counting = auth_data.sign_count != 0 || credential.sign_count != 0;
+    if counting && auth_data.sign_count <= credential.sign_count {
+        return Err(WebAuthnError::SignCountRegressed);
+    }
+
+    Ok(auth_data.sign_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn authenticator_data(rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn test_parse_authenticator_data() {
+        let data = AuthenticatorData::parse(&authenticator_data("localhost", 0x05, 9)).unwrap();
+
+        assert_eq!(data.flags, 0x05);
+        assert_eq!(data.sign_count, 9);
+        assert!(data.attested_credential.is_none());
+        assert_eq!(data.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_truncated_authenticator_data_fails() {
+        let data = authenticator_data("localhost", FLAG_USER_PRESENT, 0);
+
+        assert!(matches!(
+            AuthenticatorData::parse(&data[..36]),
+            Err(WebAuthnError::MalformedAuthenticatorData)
+        ));
+        // claims a credential that isn't there
+        let mut data = data;
+        data[32] |= FLAG_ATTESTED_CREDENTIAL_DATA;
+        assert!(matches!(
+            AuthenticatorData::parse(&data),
+            Err(WebAuthnError::MalformedAuthenticatorData)
+        ));
+    }
+
+    #[test]
+    fn test_authenticator_data_for_other_rp_fails() {
+        let data = AuthenticatorData::parse(&authenticator_data("evil.com", FLAG_USER_PRESENT, 0))
+            .unwrap();
+
+        assert_eq!(data.verify(), Err(WebAuthnError::RelyingPartyMismatch));
+    }
+
+    #[test]
+    fn test_authenticator_data_without_user_presence_fails() {
+        let data = AuthenticatorData::parse(&authenticator_data("localhost", 0, 0)).unwrap();
+
+        assert_eq!(data.verify(), Err(WebAuthnError::UserNotPresent));
+    }
+
+    #[test]
+    fn test_client_data_verify() {
+        let client_data = ClientData::parse(
+            br#"{"type":"webauthn.get","challenge":"abc","origin":"http://localhost:3000"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(client_data.challenge().as_ref(), "abc");
+        assert_eq!(client_data.verify(Ceremony::Authentication), Ok(()));
+        assert_eq!(
+            client_data.verify(Ceremony::Registration),
+            Err(WebAuthnError::CeremonyMismatch)
+        );
+
+        let client_data = ClientData::parse(
+            br#"{"type":"webauthn.get","challenge":"abc","origin":"http://evil.com"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            client_data.verify(Ceremony::Authentication),
+            Err(WebAuthnError::RelyingPartyMismatch)
+        );
+    }
+
+    #[test]
+    fn test_unsupported_attestation_format_fails() {
+        let attestation = Value::Map(vec![
+            (Value::Text("fmt".into()), Value::Text("packed".into())),
+            (
+                Value::Text("authData".into()),
+                Value::Bytes(authenticator_data("localhost", FLAG_USER_PRESENT, 0)),
+            ),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&attestation, &mut bytes).unwrap();
+
+        assert_eq!(verify_registration(&bytes), Err(WebAuthnError::Unsupported));
+    }
+}
@@ -0,0 +1,126 @@
+use auth_service::{
+    domain::{BannedTokenStore, UserStore, UserStoreError},
+    routes::{AccountExport, UserRecord},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use serde_json::json;
+
+use crate::helpers::TestApp;
+
+#[tokio::test]
+async fn export_should_return_200_with_user_record() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+
+    let response = app.get_account_export().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.json::<AccountExport>().await.unwrap();
+    assert_eq!(
+        body.user,
+        UserRecord {
+            email: "sample@example.com".to_string(),
+            roles: vec![],
+            requires_2fa: false,
+            two_factor_method: None,
+            recovery_codes_remaining: 0,
+            linked_identities: vec![],
+        }
+    );
+    assert!(body.passkeys.is_empty());
+    // the session doing the export
+    assert_eq!(body.sessions.len(), 1);
+    assert!(body.sessions[0].current);
+}
+
+#[tokio::test]
+async fn export_should_not_leak_password() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+
+    let body = app.get_account_export().await.text().await.unwrap();
+
+    assert!(!body.contains("password123"));
+}
+
+#[tokio::test]
+async fn export_should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.get_account_export().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn delete_should_remove_user_and_ban_token() {
+    let app = TestApp::new().await;
+    let jwt_token = login_user(&app).await;
+
+    let response = app.delete_account().await;
+
+    assert_eq!(response.status().as_u16(), 204);
+    assert_eq!(
+        app.user_store
+            .get_user(&"sample@example.com".parse().unwrap())
+            .await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert!(
+        app.banned_token_store
+            .check_token(&jwt_token)
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn delete_should_return_401_if_token_reused() {
+    let app = TestApp::new().await;
+    let jwt_token = login_user(&app).await;
+
+    app.delete_account().await.error_for_status().unwrap();
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Secure; Path=/",
+            JWT_COOKIE_NAME, &jwt_token,
+        ),
+        &"http://127.0.0.1".parse().expect("Failed to parse URL"),
+    );
+    let response = app.delete_account().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn delete_should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.delete_account().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+async fn login_user(app: &TestApp) -> String {
+    app.post_signup(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    app.post_login(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+    }))
+    .await
+    .error_for_status()
+    .unwrap()
+    .cookies()
+    .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+    .expect("no jwt given from /login")
+    .value()
+    .to_string()
+}
@@ -0,0 +1,211 @@
+use auth_service::{
+    ErrorResponse,
+    domain::{Role, User, UserStore},
+    routes::{UserSummary, VerifyTokenResponse},
+};
+use serde_json::json;
+
+use crate::helpers::TestApp;
+
+const ADMIN_EMAIL: &str = "admin@example.com";
+
+async fn add_user(app: &TestApp, email: &str, roles: Vec<Role>) {
+    let mut user = User::new(email.parse().unwrap(), "password123".parse().unwrap(), None);
+    user.roles = roles;
+    app.user_store.add_user(user).await.unwrap();
+}
+
+async fn login(app: &TestApp, email: &str) -> reqwest::Response {
+    app.post_login(&json!({
+        "email": email,
+        "password": "password123",
+    }))
+    .await
+}
+
+/// An admin logged in on `app`, plus two regular users.
+async fn setup(app: &TestApp) {
+    add_user(app, ADMIN_EMAIL, vec![Role::Admin]).await;
+    add_user(app, "alice@example.com", vec![]).await;
+    add_user(app, "bob@other.com", vec![]).await;
+    login(app, ADMIN_EMAIL).await.error_for_status().unwrap();
+}
+
+async fn get_users(app: &TestApp, query: &str) -> reqwest::Response {
+    app.http_client
+        .get(format!("{}/admin/users{query}", app.address))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn post_admin(app: &TestApp, email: &str, action: &str) -> reqwest::Response {
+    app.http_client
+        .post(format!("{}/admin/users/{email}/{action}", app.address))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn delete_user(app: &TestApp, email: &str) -> reqwest::Response {
+    app.http_client
+        .delete(format!("{}/admin/users/{email}", app.address))
+        .send()
+        .await
+        .unwrap()
+}
+
+fn emails(users: &[UserSummary]) -> Vec<&str> {
+    users.iter().map(|user| user.email.as_str()).collect()
+}
+
+#[tokio::test]
+async fn should_list_and_search_users() {
+    let app = TestApp::new().await;
+    setup(&app).await;
+
+    let response = get_users(&app, "").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let users = response.json::<Vec<UserSummary>>().await.unwrap();
+    assert_eq!(
+        emails(&users),
+        ["admin@example.com", "alice@example.com", "bob@other.com"]
+    );
+    assert_eq!(users[0].roles, vec![Role::Admin]);
+
+    let users = get_users(&app, "?q=OTHER")
+        .await
+        .json::<Vec<UserSummary>>()
+        .await
+        .unwrap();
+    assert_eq!(emails(&users), ["bob@other.com"]);
+}
+
+#[tokio::test]
+async fn should_return_403_without_permission() {
+    let app = TestApp::new().await;
+    add_user(&app, "alice@example.com", vec![]).await;
+    add_user(&app, "bob@other.com", vec![]).await;
+    login(&app, "alice@example.com")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let responses = [
+        get_users(&app, "").await,
+        post_admin(&app, "bob@other.com", "lock").await,
+        post_admin(&app, "bob@other.com", "unlock").await,
+        delete_user(&app, "bob@other.com").await,
+    ];
+
+    for response in responses {
+        assert_eq!(response.status().as_u16(), 403);
+        let body = response.json::<ErrorResponse>().await.unwrap();
+        assert_eq!(body.error, "Insufficient permissions!");
+    }
+}
+
+#[tokio::test]
+async fn support_can_read_but_not_manage_users() {
+    let app = TestApp::new().await;
+    add_user(&app, "support@example.com", vec![Role::Support]).await;
+    add_user(&app, "bob@other.com", vec![]).await;
+    login(&app, "support@example.com")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    assert_eq!(get_users(&app, "").await.status().as_u16(), 200);
+    assert_eq!(
+        post_admin(&app, "bob@other.com", "lock")
+            .await
+            .status()
+            .as_u16(),
+        403
+    );
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    assert_eq!(get_users(&app, "").await.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn locked_user_should_be_logged_out_and_unable_to_log_in() {
+    let app = TestApp::new().await;
+    setup(&app).await;
+    let alice = app.other_device("curl/8.5.0");
+    login(&alice, "alice@example.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let token = alice.auth_token().unwrap();
+
+    let response = post_admin(&app, "alice@example.com", "lock").await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = login(&alice, "alice@example.com").await;
+    assert_eq!(response.status().as_u16(), 403);
+    let body = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(body.error, "Account is locked!");
+
+    let response = post_admin(&app, "alice@example.com", "unlock").await;
+    assert_eq!(response.status().as_u16(), 204);
+    assert_eq!(
+        login(&alice, "alice@example.com").await.status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_delete_user() {
+    let app = TestApp::new().await;
+    setup(&app).await;
+
+    let response = delete_user(&app, "alice@example.com").await;
+
+    assert_eq!(response.status().as_u16(), 204);
+    assert!(
+        app.user_store
+            .get_user(&"alice@example.com".parse().unwrap())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_user() {
+    let app = TestApp::new().await;
+    setup(&app).await;
+
+    let responses = [
+        post_admin(&app, "nobody@example.com", "lock").await,
+        post_admin(&app, "nobody@example.com", "unlock").await,
+        delete_user(&app, "nobody@example.com").await,
+    ];
+
+    for response in responses {
+        assert_eq!(response.status().as_u16(), 404);
+        let body = response.json::<ErrorResponse>().await.unwrap();
+        assert_eq!(body.error, "User not found!");
+    }
+}
+
+#[tokio::test]
+async fn verify_token_should_return_roles() {
+    let app = TestApp::new().await;
+    setup(&app).await;
+
+    let body = app
+        .post_verify_token(&json!({ "token": app.auth_token().unwrap() }))
+        .await
+        .json::<VerifyTokenResponse>()
+        .await
+        .unwrap();
+
+    assert_eq!(body.roles, vec![Role::Admin]);
+}
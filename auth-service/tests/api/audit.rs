@@ -0,0 +1,251 @@
+use auth_service::{
+    domain::{AuditEvent, AuditEventKind, AuditOutcome, Role, User, UserStore},
+    routes::AccountExport,
+};
+use serde_json::json;
+
+use crate::helpers::TestApp;
+
+async fn signup(app: &TestApp, email: &str) {
+    app.post_signup(&json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+}
+
+async fn login(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    app.post_login(&json!({
+        "email": email,
+        "password": password,
+    }))
+    .await
+}
+
+fn events_of(app: &TestApp, kind: AuditEventKind) -> Vec<AuditEvent> {
+    app.audit_sink
+        .events()
+        .into_iter()
+        .filter(|event| event.kind == kind)
+        .collect()
+}
+
+#[tokio::test]
+async fn should_record_logins_with_request_details() {
+    let app = TestApp::new().await;
+    let device = app.other_device("curl/8.5.0");
+    signup(&device, "a@b.com").await;
+
+    let failed = login(&device, "a@b.com", "wrong password").await;
+    let succeeded = login(&device, "a@b.com", "password123").await;
+
+    assert_eq!(failed.status().as_u16(), 401);
+    assert_eq!(succeeded.status().as_u16(), 200);
+    let events = events_of(&app, AuditEventKind::Login);
+    assert_eq!(events.len(), 2);
+    for (event, response) in events.iter().zip([&failed, &succeeded]) {
+        assert_eq!(event.email.as_deref(), Some("a@b.com"));
+        assert_eq!(event.ip.as_deref(), Some("127.0.0.1"));
+        assert_eq!(event.user_agent.as_deref(), Some("curl/8.5.0"));
+        assert_eq!(
+            event.request_id.as_deref(),
+            response
+                .headers()
+                .get("x-request-id")
+                .map(|id| id.to_str().unwrap())
+        );
+    }
+    assert_eq!(
+        events[0].outcome,
+        AuditOutcome::Failure {
+            reason: "Authentication failed!".to_string()
+        }
+    );
+    assert_eq!(events[1].outcome, AuditOutcome::Success);
+    assert_eq!(events_of(&app, AuditEventKind::Signup).len(), 1);
+}
+
+#[tokio::test]
+async fn should_keep_the_callers_request_id() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/login", app.address))
+        .header("x-request-id", "from-the-caller")
+        .json(&json!({ "email": "a@b.com", "password": "password123" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.headers()["x-request-id"], "from-the-caller");
+    let events = events_of(&app, AuditEventKind::Login);
+    assert_eq!(events[0].request_id.as_deref(), Some("from-the-caller"));
+}
+
+#[tokio::test]
+async fn should_record_malformed_requests_without_subject() {
+    let app = TestApp::new().await;
+
+    let response = app.post_login(&json!({ "email": "a@b.com" })).await;
+
+    assert_eq!(response.status().as_u16(), 422);
+    let events = events_of(&app, AuditEventKind::Login);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].email, None);
+    assert_eq!(
+        events[0].outcome,
+        AuditOutcome::Failure {
+            reason: "422 Unprocessable Entity".to_string()
+        }
+    );
+}
+
+#[tokio::test]
+async fn should_record_rejected_tokens() {
+    let app = TestApp::new().await;
+    signup(&app, "a@b.com").await;
+    login(&app, "a@b.com", "password123").await;
+    let token = app.auth_token().unwrap();
+    app.post_logout().await.error_for_status().unwrap();
+
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    let rejected = events_of(&app, AuditEventKind::TokenRejected);
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(
+        rejected[0].outcome,
+        AuditOutcome::Failure {
+            reason: "banned".to_string()
+        }
+    );
+    assert!(matches!(
+        events_of(&app, AuditEventKind::TokenVerified)[0].outcome,
+        AuditOutcome::Failure { .. }
+    ));
+    let logout = events_of(&app, AuditEventKind::Logout);
+    assert_eq!(logout[0].email.as_deref(), Some("a@b.com"));
+}
+
+#[tokio::test]
+async fn should_never_record_secrets() {
+    let app = TestApp::new().await;
+    signup(&app, "a@b.com").await;
+    login(&app, "a@b.com", "password123").await;
+    let token = app.auth_token().unwrap();
+    app.post_verify_token(&json!({ "token": token })).await;
+    login(&app, "a@b.com", "not the password").await;
+
+    let events = serde_json::to_string(&app.audit_sink.events()).unwrap();
+
+    for secret in ["password123", "not the password", token.as_str()] {
+        assert!(!events.contains(secret));
+    }
+}
+
+#[tokio::test]
+async fn should_record_the_admin_acting_on_a_user() {
+    let app = TestApp::new().await;
+    let mut admin = User::new(
+        "admin@example.com".parse().unwrap(),
+        "password123".parse().unwrap(),
+        None,
+    );
+    admin.roles = vec![Role::Admin];
+    app.user_store.add_user(admin).await.unwrap();
+    signup(&app, "a@b.com").await;
+    login(&app, "admin@example.com", "password123").await;
+
+    app.http_client
+        .post(format!("{}/admin/users/a@b.com/lock", app.address))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let events = events_of(&app, AuditEventKind::UserLocked);
+    assert_eq!(events[0].email.as_deref(), Some("a@b.com"));
+    assert_eq!(events[0].actor.as_deref(), Some("admin@example.com"));
+}
+
+#[tokio::test]
+async fn export_should_include_auth_history() {
+    let app = TestApp::new().await;
+    signup(&app, "a@b.com").await;
+    signup(&app, "c@d.com").await;
+    login(&app, "a@b.com", "password123").await;
+
+    let body = app
+        .get_account_export()
+        .await
+        .json::<AccountExport>()
+        .await
+        .unwrap();
+
+    let kinds: Vec<AuditEventKind> = body.auth_history.iter().map(|event| event.kind).collect();
+    assert_eq!(kinds, [AuditEventKind::Signup, AuditEventKind::Login]);
+}
+
+#[tokio::test]
+async fn deleting_account_should_forget_its_history() {
+    let app = TestApp::new().await;
+    signup(&app, "a@b.com").await;
+    login(&app, "a@b.com", "password123").await;
+    app.delete_account().await.error_for_status().unwrap();
+
+    // someone else signs up with the address
+    signup(&app, "a@b.com").await;
+    login(&app, "a@b.com", "password123").await;
+    let body = app
+        .get_account_export()
+        .await
+        .json::<AccountExport>()
+        .await
+        .unwrap();
+
+    let kinds: Vec<AuditEventKind> = body.auth_history.iter().map(|event| event.kind).collect();
+    assert_eq!(kinds, [AuditEventKind::Signup, AuditEventKind::Login]);
+    let deleted = events_of(&app, AuditEventKind::AccountDeleted);
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].email, None);
+    let first_login = &events_of(&app, AuditEventKind::Login)[0];
+    assert_eq!(first_login.email, None);
+    assert_eq!(first_login.ip, None);
+    assert_eq!(first_login.user_agent, None);
+}
+
+#[tokio::test]
+async fn admin_deleting_user_should_forget_their_history() {
+    let app = TestApp::new().await;
+    let mut admin = User::new(
+        "admin@example.com".parse().unwrap(),
+        "password123".parse().unwrap(),
+        None,
+    );
+    admin.roles = vec![Role::Admin];
+    app.user_store.add_user(admin).await.unwrap();
+    signup(&app, "a@b.com").await;
+    login(&app, "admin@example.com", "password123").await;
+
+    app.http_client
+        .delete(format!("{}/admin/users/a@b.com", app.address))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    assert!(
+        app.audit_sink
+            .events()
+            .iter()
+            .all(|event| event.email.as_deref() != Some("a@b.com"))
+    );
+    let deleted = events_of(&app, AuditEventKind::UserDeleted);
+    assert_eq!(deleted[0].actor.as_deref(), Some("admin@example.com"));
+}
@@ -0,0 +1,131 @@
+use auth_service::{
+    ErrorResponse, domain::SCOPE_TOKENS_VERIFY, routes::ServiceTokenResponse,
+    utils::auth::validate_service_token,
+};
+use reqwest::header;
+use serde_json::json;
+
+use crate::helpers::{SERVICE_CLIENT_ID, SERVICE_CLIENT_SECRET, TestApp, add_service_client};
+
+#[tokio::test]
+async fn should_issue_scoped_service_token() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY]).await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/token", app.address))
+        .basic_auth(SERVICE_CLIENT_ID, Some(SERVICE_CLIENT_SECRET))
+        .form(&json!({ "grant_type": "client_credentials" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+    let body = response.json::<ServiceTokenResponse>().await.unwrap();
+    assert_eq!(body.token_type, "Bearer");
+    assert_eq!(body.scope, SCOPE_TOKENS_VERIFY);
+
+    let claims = validate_service_token(app.banned_token_store.clone(), &body.access_token)
+        .await
+        .unwrap();
+    assert_eq!(claims.claims.subject, "client:app-service");
+    assert!(claims.has_scope(SCOPE_TOKENS_VERIFY));
+}
+
+#[tokio::test]
+async fn should_accept_credentials_in_form() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY]).await;
+
+    let response = app
+        .post_token(&json!({
+            "grant_type": "client_credentials",
+            "client_id": SERVICE_CLIENT_ID,
+            "client_secret": SERVICE_CLIENT_SECRET,
+            "scope": SCOPE_TOKENS_VERIFY,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_client_credentials_invalid() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY]).await;
+
+    let test_cases = [
+        json!({
+            "grant_type": "client_credentials",
+            "client_id": SERVICE_CLIENT_ID,
+            "client_secret": "the wrong secret, also long enough",
+        }),
+        json!({
+            "grant_type": "client_credentials",
+            "client_id": "unknown",
+            "client_secret": SERVICE_CLIENT_SECRET,
+        }),
+        json!({
+            "grant_type": "client_credentials",
+            "client_id": SERVICE_CLIENT_ID,
+        }),
+    ];
+
+    for test_case in test_cases {
+        let response = app.post_token(&test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            test_case
+        );
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Basic");
+        let body = response.json::<ErrorResponse>().await.unwrap();
+        assert_eq!(body.error, "invalid_client");
+    }
+}
+
+#[tokio::test]
+async fn should_return_400_if_scope_not_allowed() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY]).await;
+
+    let response = app
+        .post_token(&json!({
+            "grant_type": "client_credentials",
+            "client_id": SERVICE_CLIENT_ID,
+            "client_secret": SERVICE_CLIENT_SECRET,
+            "scope": "openid",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(body.error, "invalid_scope");
+}
+
+#[tokio::test]
+async fn service_token_should_not_pass_as_user_token() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY]).await;
+    let token = app
+        .post_token(&json!({
+            "grant_type": "client_credentials",
+            "client_id": SERVICE_CLIENT_ID,
+            "client_secret": SERVICE_CLIENT_SECRET,
+        }))
+        .await
+        .json::<ServiceTokenResponse>()
+        .await
+        .unwrap()
+        .access_token;
+
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_userinfo(&token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
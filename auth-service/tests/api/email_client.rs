@@ -0,0 +1,144 @@
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use auth_service::{
+    domain::{EmailClient, EmailClientError},
+    services::{PostmarkEmailClient, PostmarkSettings},
+};
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+};
+use serde_json::{Value, json};
+use tokio::net::TcpListener;
+
+const SERVER_TOKEN: &str = "server-token";
+
+/// Stands in for Postmark, keeping every email it accepts and failing with `status` otherwise.
+struct MockPostmark {
+    client: PostmarkEmailClient,
+    sent: Arc<Mutex<Vec<Value>>>,
+    health_checks: Arc<AtomicUsize>,
+}
+
+impl MockPostmark {
+    async fn start(server_token: &str, status: StatusCode) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let health_checks = Arc::new(AtomicUsize::new(0));
+
+        let router = Router::new()
+            .route("/email", post(send_email))
+            .route("/server", get(server))
+            .with_state((sent.clone(), health_checks.clone(), status));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = PostmarkEmailClient::new(PostmarkSettings {
+            base_url: base_url.parse().unwrap(),
+            sender: "sender@example.com".parse().unwrap(),
+            server_token: server_token.to_string(),
+        })
+        .unwrap();
+        Self {
+            client,
+            sent,
+            health_checks,
+        }
+    }
+}
+
+type MockState = (Arc<Mutex<Vec<Value>>>, Arc<AtomicUsize>, StatusCode);
+
+fn authorized(headers: &HeaderMap) -> bool {
+    headers
+        .get("X-Postmark-Server-Token")
+        .is_some_and(|token| token == SERVER_TOKEN)
+}
+
+async fn send_email(
+    State((sent, _, status)): State<MockState>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> StatusCode {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    if status.is_success() {
+        sent.lock().unwrap().push(body);
+    }
+    status
+}
+
+async fn server(
+    State((_, health_checks, status)): State<MockState>,
+    headers: HeaderMap,
+) -> StatusCode {
+    health_checks.fetch_add(1, Ordering::SeqCst);
+    if authorized(&headers) {
+        status
+    } else {
+        StatusCode::UNAUTHORIZED
+    }
+}
+
+#[tokio::test]
+async fn should_send_email_through_postmark() {
+    let postmark = MockPostmark::start(SERVER_TOKEN, StatusCode::OK).await;
+
+    let result = postmark
+        .client
+        .send_email(&"a@b.com".parse().unwrap(), "subject", "content")
+        .await;
+
+    assert_eq!(result, Ok(()));
+    assert_eq!(
+        *postmark.sent.lock().unwrap(),
+        vec![json!({
+            "From": "sender@example.com",
+            "To": "a@b.com",
+            "Subject": "subject",
+            "TextBody": "content",
+            "MessageStream": "outbound",
+        })]
+    );
+    assert_eq!(postmark.client.health_check().await, Ok(()));
+}
+
+#[tokio::test]
+async fn should_fail_if_postmark_rejects_email() {
+    let postmark = MockPostmark::start(SERVER_TOKEN, StatusCode::INTERNAL_SERVER_ERROR).await;
+
+    let result = postmark
+        .client
+        .send_email(&"a@b.com".parse().unwrap(), "subject", "content")
+        .await;
+
+    assert_eq!(result, Err(EmailClientError::UnexpectedError));
+    assert!(postmark.sent.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn health_check_should_fail_if_token_rejected() {
+    let postmark = MockPostmark::start("wrong-token", StatusCode::OK).await;
+
+    assert_eq!(
+        postmark.client.health_check().await,
+        Err(EmailClientError::UnexpectedError)
+    );
+}
+
+#[tokio::test]
+async fn health_check_should_reuse_recent_answer() {
+    let postmark = MockPostmark::start(SERVER_TOKEN, StatusCode::OK).await;
+
+    for _ in 0..3 {
+        assert_eq!(postmark.client.health_check().await, Ok(()));
+    }
+
+    assert_eq!(postmark.health_checks.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,79 @@
+use auth_service::{ErrorResponse, ProblemDetails};
+use reqwest::header;
+use serde_json::json;
+
+use crate::helpers::TestApp;
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+#[tokio::test]
+async fn should_return_stable_error_codes() {
+    let app = TestApp::new().await;
+    let user = json!({
+        "email": "azure@diamond.com",
+        "password": "hunter22",
+        "requires2FA": false
+    });
+    app.post_signup(&user).await.error_for_status().unwrap();
+
+    let response = app.post_signup(&user).await;
+    assert_eq!(response.status().as_u16(), 409);
+    let body = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(body.code, "user_already_exists");
+    assert_eq!(body.details, None);
+
+    let response = app
+        .post_login(&json!({
+            "email": "azure@diamond.com",
+            "password": "wrongpassword"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let body = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(body.code, "authentication_failed");
+}
+
+#[tokio::test]
+async fn should_return_problem_details_when_accepted() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/login", app.address))
+        .header(header::ACCEPT, format!("{PROBLEM_JSON}, application/json"))
+        .json(&json!({
+            "email": "nobody@example.com",
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+    let problem = response.json::<ProblemDetails>().await.unwrap();
+    assert_eq!(problem.problem_type, "about:blank");
+    assert_eq!(problem.title, "Unauthorized");
+    assert_eq!(problem.status, 401);
+    assert_eq!(problem.detail, "Authentication failed!");
+    assert_eq!(problem.code, "authentication_failed");
+}
+
+#[tokio::test]
+async fn should_keep_oauth_errors_as_the_spec_says() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/token", app.address))
+        .header(header::ACCEPT, PROBLEM_JSON)
+        .form(&[("grant_type", "password")])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    let body = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(body.code, "unsupported_grant_type");
+}
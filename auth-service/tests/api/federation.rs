@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use auth_service::{
+    domain::{ExternalIdentity, UserStore},
+    routes::{AccountExport, IdentityProviderSummary},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use reqwest::header;
+use serde_json::json;
+use url::Url;
+
+use crate::{
+    helpers::{TestApp, setup_totp_user},
+    mock_idp::{MockIdentityProvider, MockIdpUser},
+};
+
+fn idp_user() -> MockIdpUser {
+    MockIdpUser {
+        subject: "idp-123".to_string(),
+        email: "sample@example.com".to_string(),
+        email_verified: true,
+    }
+}
+
+async fn setup() -> (TestApp, MockIdentityProvider) {
+    let idp = MockIdentityProvider::start(idp_user()).await;
+    let app = TestApp::with_identity_providers(vec![idp.provider()]).await;
+    (app, idp)
+}
+
+#[tokio::test]
+async fn should_list_identity_providers() {
+    let (app, _idp) = setup().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/login/oidc", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let providers = response
+        .json::<Vec<IdentityProviderSummary>>()
+        .await
+        .unwrap();
+    assert_eq!(
+        providers,
+        [IdentityProviderSummary {
+            id: "mock".to_string(),
+            name: "Mock IdP".to_string(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn should_return_404_if_provider_unknown() {
+    let (app, _idp) = setup().await;
+
+    let response = app
+        .get_without_redirect(&format!("{}/login/oidc/nope", app.address))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn should_create_and_log_in_new_user() {
+    let (app, _idp) = setup().await;
+
+    let response = login_with_idp(&app, "").await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers()[header::LOCATION], "/");
+    assert!(
+        response
+            .cookies()
+            .any(|cookie| cookie.name() == JWT_COOKIE_NAME)
+    );
+
+    let user = app
+        .user_store
+        .get_user(&"sample@example.com".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(user.external_identities, [identity("idp-123")]);
+}
+
+#[tokio::test]
+async fn should_link_existing_user_by_email() {
+    let (app, _idp) = setup().await;
+    app.post_signup(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    login_with_idp(&app, "").await.error_for_status().unwrap();
+
+    let export = app
+        .get_account_export()
+        .await
+        .json::<AccountExport>()
+        .await
+        .unwrap();
+    assert_eq!(export.user.linked_identities, [identity("idp-123")]);
+
+    // the password still works too
+    app.post_login(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+}
+
+#[tokio::test]
+async fn should_return_401_if_email_unverified() {
+    let (app, idp) = setup().await;
+    idp.set_user(MockIdpUser {
+        email_verified: false,
+        ..idp_user()
+    });
+
+    let response = login_with_idp(&app, "").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(
+        app.user_store
+            .get_user(&"sample@example.com".parse().unwrap())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn should_return_401_if_other_identity_already_linked() {
+    let (app, idp) = setup().await;
+    login_with_idp(&app, "").await.error_for_status().unwrap();
+
+    // same email at the provider, but a different account
+    idp.set_user(MockIdpUser {
+        subject: "idp-456".to_string(),
+        ..idp_user()
+    });
+    let response = login_with_idp(&app, "").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_require_totp_if_enabled() {
+    let (app, _idp) = setup().await;
+    let (_, recovery_codes) = setup_totp_user(&app, "sample@example.com", "password123").await;
+    app.post_logout().await.error_for_status().unwrap();
+
+    let response = login_with_idp(&app, "?return_to=%2Fauthorize%3Fclient_id%3Dabc").await;
+
+    // to the login page, which asks for the code
+    assert_eq!(response.status().as_u16(), 303);
+    let location = Url::parse("http://localhost")
+        .unwrap()
+        .join(response.headers()[header::LOCATION].to_str().unwrap())
+        .unwrap();
+    assert_eq!(location.path(), "/");
+    let query: HashMap<String, String> = location.query_pairs().into_owned().collect();
+    assert_eq!(query["email"], "sample@example.com");
+    assert_eq!(query["return_to"], "/authorize?client_id=abc");
+    app.post_verify_2fa(&json!({
+        "email": query["email"],
+        "loginAttemptId": query["loginAttemptId"],
+        "2FACode": recovery_codes[0],
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+}
+
+#[tokio::test]
+async fn should_return_to_authorization_request() {
+    let (app, _idp) = setup().await;
+
+    let response = login_with_idp(&app, "?return_to=%2Fauthorize%3Fclient_id%3Dabc").await;
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "/authorize?client_id=abc"
+    );
+
+    // anywhere else is ignored
+    let response = login_with_idp(&app, "?return_to=https%3A%2F%2Fevil.com").await;
+    assert_eq!(response.headers()[header::LOCATION], "/");
+}
+
+#[tokio::test]
+async fn callback_should_reject_state_from_other_browser() {
+    let (app, _idp) = setup().await;
+    let callback = idp_callback(&app, "").await;
+
+    // no state cookie at all
+    let other_browser = TestApp::new().await;
+    let response = other_browser.get_without_redirect(&callback).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    // a state cookie from a login of its own
+    let other_browser = second_browser(&app).await;
+    idp_callback(&other_browser, "").await;
+    let response = other_browser.get_without_redirect(&callback).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn callback_should_return_401_if_replayed() {
+    let (app, _idp) = setup().await;
+    let callback = idp_callback(&app, "").await;
+    app.get_without_redirect(&callback)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let response = app.get_without_redirect(&callback).await;
+
+    assert!(response.status().is_client_error());
+}
+
+fn identity(subject: &str) -> ExternalIdentity {
+    ExternalIdentity {
+        provider: "mock".to_string(),
+        subject: subject.to_string(),
+    }
+}
+
+// a second browser pointed at the same app
+async fn second_browser(app: &TestApp) -> TestApp {
+    let mut other = TestApp::new().await;
+    other.address = app.address.clone();
+    other
+}
+
+/// Starts a login and lets the provider approve it, returning the callback url on `app`.
+async fn idp_callback(app: &TestApp, query: &str) -> String {
+    let response = app
+        .get_without_redirect(&format!("{}/login/oidc/mock{query}", app.address))
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+    let authorize = response.headers()[header::LOCATION].to_str().unwrap();
+
+    let response = app.get_without_redirect(authorize).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let callback = response.headers()[header::LOCATION].to_str().unwrap();
+
+    // the provider sends users to the public url, which isn't where the test app listens
+    let callback = url::Url::parse(callback).unwrap();
+    format!(
+        "{}{}?{}",
+        app.address,
+        callback.path(),
+        callback.query().unwrap()
+    )
+}
+
+async fn login_with_idp(app: &TestApp, query: &str) -> reqwest::Response {
+    let callback = idp_callback(app, query).await;
+    app.get_without_redirect(&callback).await
+}
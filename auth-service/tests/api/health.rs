@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use auth_service::{
+    Application,
+    app_state::AppState,
+    domain::{Email, EmailClient, EmailClientError},
+    routes::{HealthStatus, LivenessResponse, ReadinessResponse},
+    services::{
+        HashMapClientStore, HashMapFederatedLoginStore, HashMapLoginAttemptStore,
+        HashMapPasskeyStore, HashMapSessionStore, HashMapUserStore, HashSetTokenStore,
+        VecAuditSink,
+    },
+    utils::constants::test,
+};
+
+use crate::helpers::TestApp;
+
+// an email provider that can't be reached
+struct UnreachableEmailClient;
+
+#[async_trait]
+impl EmailClient for UnreachableEmailClient {
+    async fn send_email(&self, _: &Email, _: &str, _: &str) -> Result<(), EmailClientError> {
+        Err(EmailClientError::UnexpectedError)
+    }
+
+    async fn health_check(&self) -> Result<(), EmailClientError> {
+        Err(EmailClientError::UnexpectedError)
+    }
+}
+
+async fn get(address: &str, path: &str) -> reqwest::Response {
+    reqwest::get(format!("{address}{path}")).await.unwrap()
+}
+
+#[tokio::test]
+async fn should_be_live() {
+    let app = TestApp::new().await;
+
+    let response = get(&app.address, "/health/live").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.json::<LivenessResponse>().await.unwrap();
+    assert_eq!(body.status, HealthStatus::Up);
+}
+
+#[tokio::test]
+async fn should_be_ready_when_every_backend_is_up() {
+    let app = TestApp::new().await;
+
+    let response = get(&app.address, "/health/ready").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.json::<ReadinessResponse>().await.unwrap();
+    assert_eq!(body.status, HealthStatus::Up);
+    for dependency in [
+        body.checks.user_store,
+        body.checks.banned_token_store,
+        body.checks.email_client,
+    ] {
+        assert_eq!(dependency.status, HealthStatus::Up);
+        assert_eq!(dependency.error, None);
+    }
+}
+
+#[tokio::test]
+async fn should_not_be_ready_while_a_backend_is_down() {
+    let app_state = AppState::new(
+        HashMapUserStore::default(),
+        HashSetTokenStore::default(),
+        HashMapLoginAttemptStore::default(),
+        HashMapPasskeyStore::default(),
+        UnreachableEmailClient,
+        HashMapClientStore::default(),
+        HashMapFederatedLoginStore::default(),
+        HashMapSessionStore::default(),
+        Arc::new(VecAuditSink::default()),
+        Vec::new(),
+    );
+    let app = Application::build(app_state, test::APP_ADDRESS, None)
+        .await
+        .unwrap();
+    let address = format!("http://{}", app.address);
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(app.run());
+
+    let response = get(&address, "/health/ready").await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    let body = response.json::<ReadinessResponse>().await.unwrap();
+    assert_eq!(body.status, HealthStatus::Down);
+    assert_eq!(body.checks.user_store.status, HealthStatus::Up);
+    assert_eq!(body.checks.email_client.status, HealthStatus::Down);
+    assert_eq!(
+        body.checks.email_client.error.as_deref(),
+        Some("UnexpectedError")
+    );
+    // still live, restarting wouldn't bring the email provider back
+    assert_eq!(get(&address, "/health/live").await.status().as_u16(), 200);
+}
@@ -1,13 +1,28 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use auth_service::{
     Application,
     app_state::AppState,
-    services::{HashMapUserStore, HashSetTokenStore},
-    utils::constants::test,
+    domain::{
+        ClientSecretHash, ClientStore, IdentityProvider, SCOPE_TOKENS_VERIFY, ServiceClient,
+        TotpSecret, UserStore,
+    },
+    routes::{RecoveryCodesResponse, ServiceTokenResponse},
+    services::{
+        HashMapClientStore, HashMapFederatedLoginStore, HashMapLoginAttemptStore,
+        HashMapPasskeyStore, HashMapSessionStore, HashMapUserStore, HashSetTokenStore,
+        MockEmailClient, VecAuditSink,
+    },
+    utils::{
+        constants::{DEFAULT_SWEEP_INTERVAL, JWT_COOKIE_NAME, test},
+        shutdown::ShutdownHandle,
+    },
 };
 
-use reqwest::cookie::Jar;
+use chrono::Utc;
+use reqwest::cookie::{CookieStore, Jar};
+use serde_json::json;
+use tokio::sync::OnceCell;
 use uuid::Uuid;
 
 pub struct TestApp {
@@ -17,18 +32,59 @@ pub struct TestApp {
     #[allow(unused)] // forgot to add this until later, don't want to refactor
     pub user_store: Arc<HashMapUserStore>,
     pub banned_token_store: Arc<HashSetTokenStore>,
+    pub login_attempt_store: Arc<HashMapLoginAttemptStore>,
+    pub passkey_store: Arc<HashMapPasskeyStore>,
+    pub email_client: Arc<MockEmailClient>,
+    pub client_store: Arc<HashMapClientStore>,
+    pub session_store: Arc<HashMapSessionStore>,
+    pub audit_sink: Arc<VecAuditSink>,
+    pub shutdown: ShutdownHandle,
+    // lets `post_verify_token` through, fetched when first needed so other tests don't see
+    // the extra requests
+    verifier_token: Arc<OnceCell<String>>,
 }
 
 impl TestApp {
     pub async fn new() -> Self {
+        Self::with_identity_providers(Vec::new()).await
+    }
+
+    pub async fn with_identity_providers(identity_providers: Vec<IdentityProvider>) -> Self {
+        Self::build(identity_providers, DEFAULT_SWEEP_INTERVAL).await
+    }
+
+    pub async fn with_sweep_interval(sweep_interval: Duration) -> Self {
+        Self::build(Vec::new(), sweep_interval).await
+    }
+
+    async fn build(identity_providers: Vec<IdentityProvider>, sweep_interval: Duration) -> Self {
         let user_store = Arc::new(HashMapUserStore::default());
         let banned_token_store = Arc::new(HashSetTokenStore::default());
-        let app_state = AppState::new_tester(user_store.clone(), banned_token_store.clone());
+        let login_attempt_store = Arc::new(HashMapLoginAttemptStore::default());
+        let passkey_store = Arc::new(HashMapPasskeyStore::default());
+        let email_client = Arc::new(MockEmailClient::default());
+        let client_store = Arc::new(HashMapClientStore::default());
+        let session_store = Arc::new(HashMapSessionStore::default());
+        let audit_sink = Arc::new(VecAuditSink::default());
+        let app_state = AppState::new_tester(
+            user_store.clone(),
+            banned_token_store.clone(),
+            login_attempt_store.clone(),
+            passkey_store.clone(),
+            email_client.clone(),
+            client_store.clone(),
+            Arc::new(HashMapFederatedLoginStore::default()),
+            session_store.clone(),
+            audit_sink.clone(),
+            identity_providers,
+        );
 
-        let app = Application::build(app_state, test::APP_ADDRESS)
+        let app = Application::build(app_state, test::APP_ADDRESS, None)
             .await
-            .expect("could not build application");
+            .expect("could not build application")
+            .with_sweep_interval(sweep_interval);
         let address = format!("http://{}", &app.address);
+        let shutdown = app.shutdown_handle();
 
         #[allow(clippy::let_underscore_future)]
         let _ = tokio::spawn(app.run());
@@ -45,13 +101,72 @@ impl TestApp {
             http_client,
             banned_token_store,
             user_store,
+            login_attempt_store,
+            passkey_store,
+            email_client,
+            client_store,
+            session_store,
+            audit_sink,
+            shutdown,
+            verifier_token: Arc::default(),
+        }
+    }
+
+    // a token for `VERIFIER_CLIENT_ID`, which is kept apart from `SERVICE_CLIENT_ID` so tests
+    // are free to register that one however they like
+    async fn fetch_verifier_token(&self) -> String {
+        self.client_store
+            .add_service_client(ServiceClient {
+                id: VERIFIER_CLIENT_ID.to_string(),
+                secret_hash: ClientSecretHash::of(SERVICE_CLIENT_SECRET),
+                scopes: vec![SCOPE_TOKENS_VERIFY.to_string()],
+            })
+            .await
+            .unwrap();
+        self.post_token(&json!({
+            "grant_type": "client_credentials",
+            "client_id": VERIFIER_CLIENT_ID,
+            "client_secret": SERVICE_CLIENT_SECRET,
+        }))
+        .await
+        .error_for_status()
+        .unwrap()
+        .json::<ServiceTokenResponse>()
+        .await
+        .unwrap()
+        .access_token
+    }
+
+    /// Another browser on the same server, with its own cookies and user agent.
+    pub fn other_device(&self, user_agent: &str) -> TestApp {
+        let cookie_jar = Arc::new(Jar::default());
+        let http_client = reqwest::Client::builder()
+            .cookie_provider(cookie_jar.clone())
+            .user_agent(user_agent)
+            .build()
+            .unwrap();
+
+        TestApp {
+            address: self.address.clone(),
+            cookie_jar,
+            http_client,
+            user_store: self.user_store.clone(),
+            banned_token_store: self.banned_token_store.clone(),
+            login_attempt_store: self.login_attempt_store.clone(),
+            passkey_store: self.passkey_store.clone(),
+            email_client: self.email_client.clone(),
+            client_store: self.client_store.clone(),
+            session_store: self.session_store.clone(),
+            audit_sink: self.audit_sink.clone(),
+            shutdown: self.shutdown.clone(),
+            verifier_token: self.verifier_token.clone(),
         }
     }
 
     #[inline]
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("could not execute request")
@@ -63,7 +178,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", self.address))
+            .post(format!("{}/signup", self.address))
             .json(body)
             .send()
             .await
@@ -76,7 +191,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", self.address))
+            .post(format!("{}/login", self.address))
             .json(body)
             .send()
             .await
@@ -86,15 +201,41 @@ impl TestApp {
     #[inline]
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", self.address))
+            .post(format!("{}/logout", self.address))
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    #[inline]
+    pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-2fa", &self.address))
+            .json(body)
             .send()
             .await
             .expect("failed to execute request")
     }
 
     #[inline]
-    pub async fn post_verify_2fa(&self) -> reqwest::Response {
-        self.post("/verify-2fa").await
+    pub async fn post_enroll_totp(&self) -> reqwest::Response {
+        self.post("/2fa/totp").await
+    }
+
+    #[inline]
+    pub async fn post_confirm_totp<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("failed to execute request")
     }
 
     #[inline]
@@ -102,8 +243,198 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
+        let verifier_token = self
+            .verifier_token
+            .get_or_init(|| self.fetch_verifier_token())
+            .await;
         self.http_client
             .post(format!("{}/verify-token", &self.address))
+            .bearer_auth(verifier_token)
+            .json(body)
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    #[inline]
+    pub async fn get_account_export(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/account/export", &self.address))
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    #[inline]
+    pub async fn get_metrics(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/metrics", &self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    // the JWT currently in the cookie jar, if logged in
+    pub fn auth_token(&self) -> Option<String> {
+        let cookies = self.cookie_jar.cookies(&self.address.parse().unwrap())?;
+        cookies
+            .to_str()
+            .ok()?
+            .split("; ")
+            .find_map(|cookie| cookie.strip_prefix(&format!("{JWT_COOKIE_NAME}=")))
+            .map(String::from)
+    }
+
+    #[inline]
+    pub async fn delete_account(&self) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/account", &self.address))
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{id}", &self.address))
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    pub async fn delete_all_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    #[inline]
+    pub async fn post_regenerate_recovery_codes(&self) -> reqwest::Response {
+        self.post("/2fa/recovery-codes").await
+    }
+
+    #[inline]
+    pub async fn get_magic_link_callback(&self, link: &str) -> reqwest::Response {
+        self.http_client
+            .get(link)
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    #[inline]
+    pub async fn get_authorize<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.no_redirect_client()
+            .get(format!("{}/authorize", self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    #[inline]
+    pub async fn post_consent<Form>(&self, form: &Form) -> reqwest::Response
+    where
+        Form: serde::Serialize,
+    {
+        self.no_redirect_client()
+            .post(format!("{}/authorize", self.address))
+            .form(form)
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    #[inline]
+    pub async fn get_without_redirect(&self, url: &str) -> reqwest::Response {
+        self.no_redirect_client()
+            .get(url)
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    // redirects are left for the test to inspect, as they usually point somewhere else
+    fn no_redirect_client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .cookie_provider(self.cookie_jar.clone())
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap()
+    }
+
+    #[inline]
+    pub async fn post_token<Form>(&self, form: &Form) -> reqwest::Response
+    where
+        Form: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/token", self.address))
+            .form(form)
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    #[inline]
+    pub async fn post_introspect<Form>(&self, form: &Form) -> reqwest::Response
+    where
+        Form: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/introspect", self.address))
+            .basic_auth(SERVICE_CLIENT_ID, Some(SERVICE_CLIENT_SECRET))
+            .form(form)
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    #[inline]
+    pub async fn post_revoke<Form>(&self, form: &Form) -> reqwest::Response
+    where
+        Form: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/revoke", self.address))
+            .basic_auth(SERVICE_CLIENT_ID, Some(SERVICE_CLIENT_SECRET))
+            .form(form)
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    #[inline]
+    pub async fn get_userinfo(&self, access_token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/userinfo", self.address))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
+
+    #[inline]
+    pub async fn post_json<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}{path}", &self.address))
             .json(body)
             .send()
             .await
@@ -113,7 +444,7 @@ impl TestApp {
     #[inline]
     async fn post(&self, addr: &str) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}{addr}", &self.address))
+            .post(format!("{}{addr}", &self.address))
             .send()
             .await
             .expect("could not execute request")
@@ -124,3 +455,84 @@ impl TestApp {
         format!("{}@example.com", Uuid::new_v4())
     }
 }
+
+pub const SERVICE_CLIENT_ID: &str = "app-service";
+pub const VERIFIER_CLIENT_ID: &str = "verifier";
+pub const SERVICE_CLIENT_SECRET: &str = "a secret that is long enough to use";
+
+// registers `SERVICE_CLIENT_ID` with the given scopes
+pub async fn add_service_client(app: &TestApp, scopes: &[&str]) {
+    app.client_store
+        .add_service_client(ServiceClient {
+            id: SERVICE_CLIENT_ID.to_string(),
+            secret_hash: ClientSecretHash::of(SERVICE_CLIENT_SECRET),
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+        })
+        .await
+        .unwrap();
+}
+
+// registers `SERVICE_CLIENT_ID` with the given scopes, and gets a token for it
+pub async fn service_token(app: &TestApp, scopes: &[&str]) -> String {
+    add_service_client(app, scopes).await;
+    app.post_token(&json!({
+        "grant_type": "client_credentials",
+        "client_id": SERVICE_CLIENT_ID,
+        "client_secret": SERVICE_CLIENT_SECRET,
+    }))
+    .await
+    .error_for_status()
+    .unwrap()
+    .json::<ServiceTokenResponse>()
+    .await
+    .unwrap()
+    .access_token
+}
+
+pub fn now() -> u64 {
+    Utc::now().timestamp() as u64
+}
+
+// signs up and logs in a user, then enrolls them in TOTP 2FA
+pub async fn setup_totp_user(
+    app: &TestApp,
+    email: &str,
+    password: &str,
+) -> (TotpSecret, Vec<String>) {
+    app.post_signup(&json!({
+        "email": email,
+        "password": password,
+        "requires2FA": false
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.post_login(&json!({
+        "email": email,
+        "password": password,
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.post_enroll_totp().await.error_for_status().unwrap();
+
+    let secret = app
+        .user_store
+        .get_user(&email.parse().unwrap())
+        .await
+        .unwrap()
+        .pending_totp
+        .expect("no pending totp secret after enrollment");
+
+    let recovery_codes = app
+        .post_confirm_totp(&json!({ "code": secret.generate_code(now()) }))
+        .await
+        .error_for_status()
+        .unwrap()
+        .json::<RecoveryCodesResponse>()
+        .await
+        .unwrap()
+        .recovery_codes;
+
+    (secret, recovery_codes)
+}
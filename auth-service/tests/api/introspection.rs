@@ -0,0 +1,171 @@
+use auth_service::{
+    ErrorResponse,
+    domain::{SCOPE_TOKENS_REVOKE, SCOPE_TOKENS_VERIFY},
+    routes::IntrospectionResponse,
+    utils::{auth::generate_service_token, constants::JWT_COOKIE_NAME},
+};
+use reqwest::{Url, header};
+use serde_json::json;
+
+use crate::helpers::{SERVICE_CLIENT_ID, TestApp, add_service_client};
+
+async fn login_user(app: &TestApp) -> String {
+    app.post_signup(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    let response = app
+        .post_login(&json!({
+            "email": "sample@example.com",
+            "password": "password123",
+        }))
+        .await;
+
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("no auth cookie")
+        .value()
+        .to_string()
+}
+
+#[tokio::test]
+async fn should_introspect_user_token() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY]).await;
+    let token = login_user(&app).await;
+
+    let response = app.post_introspect(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+    let body = response.json::<IntrospectionResponse>().await.unwrap();
+    assert!(body.active);
+    assert_eq!(body.subject.as_deref(), Some("sample@example.com"));
+    assert_eq!(body.token_type.as_deref(), Some("Bearer"));
+    assert!(body.expirary.is_some());
+    assert_eq!(body.client_id, None);
+}
+
+#[tokio::test]
+async fn should_introspect_service_token() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY]).await;
+    let token = generate_service_token("gateway", &[SCOPE_TOKENS_VERIFY.to_string()]).unwrap();
+
+    let body = app
+        .post_introspect(&json!({ "token": token, "token_type_hint": "access_token" }))
+        .await
+        .json::<IntrospectionResponse>()
+        .await
+        .unwrap();
+
+    assert!(body.active);
+    assert_eq!(body.subject.as_deref(), Some("client:gateway"));
+    assert_eq!(body.client_id.as_deref(), Some("gateway"));
+    assert_eq!(body.scope.as_deref(), Some(SCOPE_TOKENS_VERIFY));
+}
+
+#[tokio::test]
+async fn should_return_only_inactive_for_invalid_token() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY]).await;
+
+    let response = app.post_introspect(&json!({ "token": "invalid" })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.text().await.unwrap(), r#"{"active":false}"#);
+}
+
+#[tokio::test]
+async fn should_revoke_token() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY, SCOPE_TOKENS_REVOKE]).await;
+    let token = login_user(&app).await;
+
+    let response = app.post_revoke(&json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = app
+        .post_introspect(&json!({ "token": token }))
+        .await
+        .json::<IntrospectionResponse>()
+        .await
+        .unwrap();
+    assert!(!body.active);
+
+    // the user's browser is logged out too
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn revoke_should_return_200_for_invalid_token() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_REVOKE]).await;
+
+    let response = app.post_revoke(&json!({ "token": "invalid" })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_client_unauthenticated() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY, SCOPE_TOKENS_REVOKE]).await;
+    let token = login_user(&app).await;
+
+    for endpoint in ["introspect", "revoke"] {
+        let url = Url::parse(&format!("{}/{endpoint}", app.address)).unwrap();
+
+        let response = app
+            .http_client
+            .post(url.clone())
+            .form(&json!({ "token": token }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 401, "Failed for {endpoint}");
+
+        let response = app
+            .http_client
+            .post(url)
+            .basic_auth(SERVICE_CLIENT_ID, Some("the wrong secret"))
+            .form(&json!({ "token": token }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 401, "Failed for {endpoint}");
+    }
+
+    // nothing was revoked along the way
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_if_client_lacks_scope() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY]).await;
+    let token = login_user(&app).await;
+
+    let response = app.post_revoke(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(body.error, "unauthorized_client");
+}
+
+#[tokio::test]
+async fn should_return_400_if_token_missing() {
+    let app = TestApp::new().await;
+    add_service_client(&app, &[SCOPE_TOKENS_VERIFY]).await;
+
+    let response = app.post_introspect(&json!({})).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
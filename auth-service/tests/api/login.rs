@@ -1,8 +1,13 @@
 use serde_json::json;
 
-use auth_service::{ErrorResponse, utils::constants::JWT_COOKIE_NAME};
+use auth_service::{
+    ErrorResponse,
+    domain::{LegacyPasswordHash, User, UserStore},
+    routes::TwoFactorAuthResponse,
+    utils::constants::JWT_COOKIE_NAME,
+};
 
-use crate::helpers::TestApp;
+use crate::helpers::{TestApp, setup_totp_user};
 
 #[tokio::test]
 async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
@@ -26,6 +31,61 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
     assert!(!auth_cookie.value().is_empty());
 }
 
+#[tokio::test]
+async fn should_return_206_if_valid_credentials_and_totp_enabled() {
+    let app = TestApp::new().await;
+    setup_totp_user(&app, "azure@diamond.com", "hunter22").await;
+
+    let response = app
+        .post_login(&json!({
+            "email": "azure@diamond.com",
+            "password": "hunter22"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(
+        response
+            .cookies()
+            .all(|cookie| cookie.name() != JWT_COOKIE_NAME)
+    );
+
+    let body = response.json::<TwoFactorAuthResponse>().await.unwrap();
+    assert_eq!(body.message, "2FA required");
+    assert!(!body.login_attempt_id.is_empty());
+}
+
+#[tokio::test]
+async fn should_return_206_and_email_code_if_email_2fa_enabled() {
+    let app = TestApp::new().await;
+    app.post_signup(&json!({
+        "email": "azure@diamond.com",
+        "password": "hunter22",
+        "requires2FA": true
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    let response = app
+        .post_login(&json!({
+            "email": "azure@diamond.com",
+            "password": "hunter22"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(
+        response
+            .cookies()
+            .all(|cookie| cookie.name() != JWT_COOKIE_NAME)
+    );
+    let sent = app.email_client.sent_emails();
+    let email = sent.last().expect("no login code sent");
+    assert_eq!(email.recipient.as_ref(), "azure@diamond.com");
+    assert_eq!(email.subject, "Your login code");
+}
+
 #[tokio::test]
 async fn should_return_422_if_malformed_credentials() {
     let app = TestApp::new().await;
@@ -56,7 +116,6 @@ async fn should_return_400_if_invalid_input() {
     let app = TestApp::new().await;
 
     let invalid_emails = ["", "don't have amerspand", "longstring12345?"];
-    let invalid_passwords = ["", "1234567", "passwor"];
 
     for invalid_email in invalid_emails {
         let response = app
@@ -67,25 +126,31 @@ async fn should_return_400_if_invalid_input() {
             .await;
 
         assert_eq!(response.status().as_u16(), 400);
-        assert_eq!(
-            response.json::<ErrorResponse>().await.unwrap().error,
-            "Invalid credentials!"
-        );
+        let body = response.json::<ErrorResponse>().await.unwrap();
+        assert_eq!(body.error, "Invalid credentials!");
+        // unlike signup, nothing about which field was wrong
+        assert_eq!(body.details, None);
     }
+}
 
-    for invalid_password in invalid_passwords {
+#[tokio::test]
+async fn should_return_401_if_password_too_short_for_signup() {
+    let app = TestApp::new().await;
+    setup_users(&app).await;
+
+    // only new passwords need 8 characters, so these are just wrong
+    for short_password in ["", "1234567", "passwor"] {
         let response = app
             .post_login(&json!({
-                "email": "totally@valid.com",
-                "password": invalid_password,
+                "email": "azure@diamond.com",
+                "password": short_password,
             }))
             .await;
 
-        assert_eq!(response.status().as_u16(), 400);
-        assert_eq!(
-            response.json::<ErrorResponse>().await.unwrap().error,
-            "Invalid credentials!"
-        );
+        assert_eq!(response.status().as_u16(), 401);
+        let body = response.json::<ErrorResponse>().await.unwrap();
+        assert_eq!(body.error, "Authentication failed!");
+        assert_eq!(body.details, None);
     }
 }
 
@@ -122,6 +187,69 @@ async fn should_return_401_if_incorrect_credentials() {
 }
 
 // helper database
+#[tokio::test]
+async fn should_accept_and_upgrade_imported_password_hash() {
+    let app = TestApp::new().await;
+    let hash = LegacyPasswordHash::parse(&bcrypt::hash("hunter22", 4).unwrap()).unwrap();
+    app.user_store
+        .add_user(User::with_legacy_password(
+            "azure@diamond.com".parse().unwrap(),
+            hash,
+        ))
+        .await
+        .unwrap();
+    let app = &app;
+    let login = |password: &'static str| async move {
+        app.post_login(&json!({
+            "email": "azure@diamond.com",
+            "password": password
+        }))
+        .await
+    };
+
+    assert_eq!(login("hunter23").await.status().as_u16(), 401);
+    assert_eq!(login("hunter22").await.status().as_u16(), 200);
+
+    let user = app
+        .user_store
+        .get_user(&"azure@diamond.com".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(user.legacy_password, None);
+    assert!(user.password.verify(&"hunter22".parse().unwrap()));
+    assert_eq!(login("hunter22").await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_accept_imported_password_shorter_than_signup_allows() {
+    let app = TestApp::new().await;
+    let hash = LegacyPasswordHash::parse(&bcrypt::hash("hunter2", 4).unwrap()).unwrap();
+    app.user_store
+        .add_user(User::with_legacy_password(
+            "azure@diamond.com".parse().unwrap(),
+            hash,
+        ))
+        .await
+        .unwrap();
+
+    for _ in 0..2 {
+        let response = app
+            .post_login(&json!({
+                "email": "azure@diamond.com",
+                "password": "hunter2"
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    let user = app
+        .user_store
+        .get_user(&"azure@diamond.com".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(user.legacy_password, None);
+}
+
 async fn setup_users(app: &TestApp) {
     let users = [
         json!({
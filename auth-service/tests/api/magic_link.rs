@@ -0,0 +1,214 @@
+use std::{collections::HashMap, time::Duration};
+
+use auth_service::{services::SentEmail, utils::constants::JWT_COOKIE_NAME};
+use reqwest::cookie::CookieStore;
+use serde_json::json;
+
+use crate::helpers::{TestApp, setup_totp_user};
+
+#[tokio::test]
+async fn should_email_link_and_set_nonce_cookie() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+
+    let response = app
+        .post_json(
+            "/login/magic-link",
+            &json!({ "email": "sample@example.com" }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let emails = sent_emails(&app, 1).await;
+    let nonce_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == "magic_link_nonce")
+        .expect("no nonce cookie found");
+    assert!(nonce_cookie.http_only());
+    assert_eq!(nonce_cookie.path(), Some("/login/magic-link"));
+
+    assert_eq!(emails[0].recipient.as_ref(), "sample@example.com");
+    assert!(
+        emails[0]
+            .content
+            .contains("http://localhost:3000/login/magic-link/callback?token=")
+    );
+    // the nonce stays in the browser
+    assert!(!emails[0].content.contains(nonce_cookie.value()));
+}
+
+#[tokio::test]
+async fn should_not_reveal_unknown_emails() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_json(
+            "/login/magic-link",
+            &json!({ "email": "nobody@example.com" }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(app.email_client.sent_emails().is_empty());
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_email() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_json("/login/magic-link", &json!({ "email": "not an email" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn callback_should_log_in_and_redirect() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    let link = request_link(&app).await;
+
+    let response = app.get_magic_link_callback(&link).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.url().path(), "/");
+    let cookies = app
+        .cookie_jar
+        .cookies(&app.address.parse().unwrap())
+        .unwrap();
+    assert!(
+        cookies
+            .to_str()
+            .unwrap()
+            .contains(&format!("{JWT_COOKIE_NAME}="))
+    );
+
+    app.get_account_export().await.error_for_status().unwrap();
+}
+
+#[tokio::test]
+async fn callback_should_return_401_if_link_reused() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    let link = request_link(&app).await;
+    app.get_magic_link_callback(&link)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // a fresh nonce doesn't revive the old link
+    request_link(&app).await;
+    let response = app.get_magic_link_callback(&link).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn callback_should_return_400_if_link_forwarded() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    let link = request_link(&app).await;
+
+    let other_browser = TestApp::new().await;
+    let response = other_browser.get_magic_link_callback(&link).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn callback_should_return_401_if_nonce_from_other_request() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    let link = request_link(&app).await;
+
+    // the attacker's browser has a nonce cookie, just not the right one
+    let attacker = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    attacker
+        .post(format!("{}/login/magic-link", app.address))
+        .json(&json!({ "email": "sample@example.com" }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let response = attacker.get(&link).send().await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn callback_should_require_totp_if_enabled() {
+    let app = TestApp::new().await;
+    let (_, recovery_codes) = setup_totp_user(&app, "sample@example.com", "password123").await;
+    app.post_logout().await.error_for_status().unwrap();
+    let link = request_link(&app).await;
+
+    let response = app.get_magic_link_callback(&link).await;
+
+    // the login page, asking for the code
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.url().path(), "/");
+    let query: HashMap<String, String> = response.url().query_pairs().into_owned().collect();
+    assert_eq!(query["email"], "sample@example.com");
+    app.post_verify_2fa(&json!({
+        "email": query["email"],
+        "loginAttemptId": query["loginAttemptId"],
+        "2FACode": recovery_codes[0],
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.get_account_export().await.error_for_status().unwrap();
+}
+
+async fn signup_user(app: &TestApp) {
+    app.post_signup(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+}
+
+// requests a login link, returning it as it'd be clicked in the email
+async fn request_link(app: &TestApp) -> String {
+    let sent = app.email_client.sent_emails().len();
+    app.post_json(
+        "/login/magic-link",
+        &json!({ "email": "sample@example.com" }),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+
+    let email = sent_emails(app, sent + 1)
+        .await
+        .pop()
+        .expect("no email sent");
+    let token = email
+        .content
+        .split("token=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("no link in email");
+
+    format!("{}/login/magic-link/callback?token={token}", app.address)
+}
+
+// the link is sent in the background, so it may not be out yet when the response is
+async fn sent_emails(app: &TestApp, count: usize) -> Vec<SentEmail> {
+    for _ in 0..100 {
+        let emails = app.email_client.sent_emails();
+        if emails.len() >= count {
+            return emails;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("expected {count} emails to be sent");
+}
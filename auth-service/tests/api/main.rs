@@ -1,7 +1,28 @@
+mod account;
+mod admin;
+mod audit;
+mod client_credentials;
+mod email_client;
+mod errors;
+mod federation;
+mod health;
 mod helpers;
+mod introspection;
 mod login;
 mod logout;
+mod magic_link;
+mod metrics;
+mod mock_collector;
+mod mock_idp;
+mod oauth;
+mod passkeys;
 mod root;
+mod security_headers;
+mod sessions;
+mod shutdown;
 mod signup;
+mod telemetry;
+mod tls;
+mod totp;
 mod verify_2fa;
 mod verify_token;
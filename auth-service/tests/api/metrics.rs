@@ -0,0 +1,111 @@
+use auth_service::domain::{SCOPE_METRICS_READ, SCOPE_TOKENS_VERIFY};
+use serde_json::json;
+
+use crate::helpers::{TestApp, service_token, setup_totp_user};
+
+async fn metrics(app: &TestApp) -> String {
+    let token = service_token(app, &[SCOPE_METRICS_READ]).await;
+    let response = app.get_metrics(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("application/openmetrics-text")
+    );
+    response.text().await.unwrap()
+}
+
+#[tokio::test]
+async fn should_count_requests_and_logins() {
+    let app = TestApp::new().await;
+    app.post_signup(&json!({
+        "email": "a@b.com",
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    app.post_login(&json!({ "email": "a@b.com", "password": "wrong password" }))
+        .await;
+    app.post_login(&json!({ "email": "a@b.com", "password": "password123" }))
+        .await;
+    app.delete_session("not-a-session").await;
+
+    let text = metrics(&app).await;
+
+    for line in [
+        r#"auth_http_requests_total{method="POST",route="/signup",status="201"} 1"#,
+        r#"auth_http_requests_total{method="POST",route="/login",status="401"} 1"#,
+        r#"auth_http_requests_total{method="POST",route="/login",status="200"} 1"#,
+        r#"auth_logins_total{method="password",outcome="failure"} 1"#,
+        r#"auth_logins_total{method="password",outcome="success"} 1"#,
+        r#"auth_user_store_errors_total{error="InvalidCredentials"} 1"#,
+    ] {
+        assert!(text.contains(line), "missing {line} in\n{text}");
+    }
+    // counted under the route, not the id asked for
+    assert!(text.contains(r#"route="/sessions/{id}""#));
+    assert!(!text.contains("not-a-session"));
+}
+
+#[tokio::test]
+async fn should_count_banned_token_hits() {
+    let app = TestApp::new().await;
+    app.post_signup(&json!({
+        "email": "a@b.com",
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    app.post_login(&json!({ "email": "a@b.com", "password": "password123" }))
+        .await;
+    let token = app.auth_token().unwrap();
+    app.post_logout().await;
+
+    app.post_verify_token(&json!({ "token": token })).await;
+
+    let text = metrics(&app).await;
+    assert!(text.contains("auth_banned_token_hits_total 1"));
+    assert!(text.contains(r#"auth_token_rejections_total{reason="banned"} 1"#));
+}
+
+#[tokio::test]
+async fn should_count_second_factor_challenges() {
+    let app = TestApp::new().await;
+    setup_totp_user(&app, "a@b.com", "password123").await;
+
+    let response = app
+        .post_login(&json!({ "email": "a@b.com", "password": "password123" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+    let text = metrics(&app).await;
+    assert!(text.contains(r#"auth_two_factor_challenges_total{result="challenged"} 1"#));
+    assert!(
+        text.contains(r#"auth_logins_total{method="password",outcome="second_factor_required"} 1"#)
+    );
+}
+
+#[tokio::test]
+async fn should_return_401_if_anonymous() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/metrics", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_reject_service_without_metrics_scope() {
+    let app = TestApp::new().await;
+    let token = service_token(&app, &[SCOPE_TOKENS_VERIFY]).await;
+
+    let response = app.get_metrics(&token).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -0,0 +1,79 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use axum::{Router, body::Bytes, extract::State, http::StatusCode, routing::post};
+use opentelemetry_proto::tonic::{
+    collector::trace::v1::ExportTraceServiceRequest, trace::v1::Span,
+};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use prost::Message;
+use tokio::{net::TcpListener, runtime::Runtime};
+
+/// An OTLP/HTTP collector that keeps every span it's sent.
+///
+/// Runs on its own thread, as flushing a tracer provider blocks the test's until the spans are
+/// exported.
+pub struct MockCollector {
+    pub endpoint: String,
+    spans: Arc<Mutex<Vec<Span>>>,
+}
+
+impl MockCollector {
+    pub fn start() -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let spans = Arc::new(Mutex::new(Vec::new()));
+
+        let router = Router::new()
+            .route("/v1/traces", post(export))
+            .with_state(spans.clone());
+
+        thread::spawn(move || {
+            Runtime::new().unwrap().block_on(async move {
+                let listener = TcpListener::from_std(listener).unwrap();
+                axum::serve(listener, router).await
+            })
+        });
+
+        Self { endpoint, spans }
+    }
+
+    /// Flushes `provider` until it has sent a span called `name`, which may take a few tries as
+    /// a request's span only ends after its response has gone out.
+    pub async fn span_named(&self, provider: &SdkTracerProvider, name: &str) -> Span {
+        for _ in 0..20 {
+            provider.force_flush().unwrap();
+            let span = self
+                .spans
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|span| span.name == name)
+                .cloned();
+            if let Some(span) = span {
+                return span;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("no {name} span exported");
+    }
+}
+
+async fn export(State(spans): State<Arc<Mutex<Vec<Span>>>>, body: Bytes) -> StatusCode {
+    let Ok(request) = ExportTraceServiceRequest::decode(body) else {
+        return StatusCode::BAD_REQUEST;
+    };
+
+    spans.lock().unwrap().extend(
+        request
+            .resource_spans
+            .into_iter()
+            .flat_map(|resource| resource.scope_spans)
+            .flat_map(|scope| scope.spans),
+    );
+    StatusCode::OK
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use auth_service::domain::{CodeChallenge, IdentityProvider};
+use axum::{
+    Form, Json, Router,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use jsonwebtoken::{EncodingKey, Header, encode};
+use serde_json::json;
+use tokio::net::TcpListener;
+use url::Url;
+use uuid::Uuid;
+
+pub const CLIENT_ID: &str = "auth-service";
+pub const CLIENT_SECRET: &str = "mock-secret";
+
+/// Who the mock provider says is logged in.
+#[derive(Clone, Debug)]
+pub struct MockIdpUser {
+    pub subject: String,
+    pub email: String,
+    pub email_verified: bool,
+}
+
+struct IssuedCode {
+    redirect_uri: String,
+    nonce: String,
+    code_challenge: String,
+}
+
+struct MockIdpState {
+    issuer: String,
+    user: Mutex<MockIdpUser>,
+    codes: Mutex<HashMap<String, IssuedCode>>,
+}
+
+/// An OpenID Connect provider that logs in whoever `user` is, without asking.
+pub struct MockIdentityProvider {
+    pub issuer: String,
+    state: Arc<MockIdpState>,
+}
+
+impl MockIdentityProvider {
+    pub async fn start(user: MockIdpUser) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(MockIdpState {
+            issuer: issuer.clone(),
+            user: Mutex::new(user),
+            codes: Mutex::new(HashMap::new()),
+        });
+
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/authorize", get(authorize))
+            .route("/token", post(token))
+            .with_state(state.clone());
+
+        #[allow(clippy::let_underscore_future)]
+        let _ = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        Self { issuer, state }
+    }
+
+    pub fn provider(&self) -> IdentityProvider {
+        IdentityProvider {
+            id: "mock".to_string(),
+            name: "Mock IdP".to_string(),
+            issuer: self.issuer.clone(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: CLIENT_SECRET.to_string(),
+        }
+    }
+
+    pub fn set_user(&self, user: MockIdpUser) {
+        *self.state.user.lock().unwrap() = user;
+    }
+}
+
+async fn discovery(State(state): State<Arc<MockIdpState>>) -> impl IntoResponse {
+    Json(json!({
+        "issuer": state.issuer,
+        "authorization_endpoint": format!("{}/authorize", state.issuer),
+        "token_endpoint": format!("{}/token", state.issuer),
+    }))
+}
+
+async fn authorize(
+    State(state): State<Arc<MockIdpState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let valid = query.get("client_id").map(String::as_str) == Some(CLIENT_ID)
+        && query.get("response_type").map(String::as_str) == Some("code")
+        && query.get("code_challenge_method").map(String::as_str) == Some("S256");
+    if !valid {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let code = Uuid::new_v4().to_string();
+    let redirect_uri = query["redirect_uri"].clone();
+    state.codes.lock().unwrap().insert(
+        code.clone(),
+        IssuedCode {
+            redirect_uri: redirect_uri.clone(),
+            nonce: query["nonce"].clone(),
+            code_challenge: query["code_challenge"].clone(),
+        },
+    );
+
+    let mut redirect = Url::parse(&redirect_uri).unwrap();
+    redirect
+        .query_pairs_mut()
+        .append_pair("code", &code)
+        .append_pair("state", &query["state"]);
+    Redirect::to(redirect.as_str()).into_response()
+}
+
+async fn token(
+    State(state): State<Arc<MockIdpState>>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let issued = form
+        .get("code")
+        .and_then(|code| state.codes.lock().unwrap().remove(code));
+    let Some(issued) = issued else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let valid = form.get("client_id").map(String::as_str) == Some(CLIENT_ID)
+        && form.get("client_secret").map(String::as_str) == Some(CLIENT_SECRET)
+        && form.get("redirect_uri") == Some(&issued.redirect_uri)
+        && form
+            .get("code_verifier")
+            .is_some_and(|verifier| CodeChallenge::of(verifier).as_ref() == issued.code_challenge);
+    if !valid {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let user = state.user.lock().unwrap().clone();
+    let claims = json!({
+        "iss": state.issuer,
+        "aud": CLIENT_ID,
+        "sub": user.subject,
+        "exp": chrono::Utc::now().timestamp() + 300,
+        "email": user.email,
+        "email_verified": user.email_verified,
+        "nonce": issued.nonce,
+    });
+    let id_token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(b"mock-idp-key"),
+    )
+    .unwrap();
+
+    Json(json!({
+        "access_token": Uuid::new_v4().to_string(),
+        "token_type": "Bearer",
+        "id_token": id_token,
+    }))
+    .into_response()
+}
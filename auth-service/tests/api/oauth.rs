@@ -0,0 +1,576 @@
+use std::time::Duration;
+
+use auth_service::{
+    ErrorResponse,
+    domain::{
+        AuditEventKind, AuthorizationCode, ClientId, ClientStore, CodeChallenge,
+        PendingAuthorization, Role, User, UserStore,
+    },
+    routes::{ClientRegistrationResponse, ProviderMetadata, TokenResponse, UserInfo},
+    utils::{
+        auth::{IdTokenClaims, validate_id_token},
+        signing_key::JwkSet,
+    },
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use rand::RngCore;
+use reqwest::header;
+use serde_json::json;
+use url::Url;
+
+use crate::helpers::TestApp;
+
+const REDIRECT_URI: &str = "http://localhost:9000/callback";
+
+#[tokio::test]
+async fn discovery_document_should_list_endpoints() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/.well-known/openid-configuration", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let metadata = response.json::<ProviderMetadata>().await.unwrap();
+    assert_eq!(metadata.issuer, "http://localhost:3000");
+    assert_eq!(metadata.token_endpoint, "http://localhost:3000/token");
+    assert_eq!(metadata.code_challenge_methods_supported, ["S256"]);
+    assert_eq!(metadata.jwks_uri, "http://localhost:3000/jwks.json");
+    assert_eq!(metadata.id_token_signing_alg_values_supported, ["ES256"]);
+}
+
+#[tokio::test]
+async fn should_register_client() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+
+    let response = app
+        .post_json(
+            "/clients",
+            &json!({ "client_name": "app", "redirect_uris": [REDIRECT_URI] }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+    let body = response.json::<ClientRegistrationResponse>().await.unwrap();
+    assert_eq!(body.redirect_uris, [REDIRECT_URI]);
+    assert_eq!(body.token_endpoint_auth_method, "none");
+
+    let client = app
+        .client_store
+        .get_client(&ClientId::parse(&body.client_id).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(client.owner.as_ref(), "sample@example.com");
+}
+
+#[tokio::test]
+async fn register_should_return_400_if_redirect_uri_invalid() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+
+    let test_cases = [
+        json!({ "client_name": "app", "redirect_uris": [] }),
+        json!({ "client_name": "app", "redirect_uris": ["not a url"] }),
+        json!({ "client_name": "app", "redirect_uris": ["http://localhost/#fragment"] }),
+        json!({ "client_name": "app", "redirect_uris": ["javascript:alert(1)"] }),
+    ];
+
+    for test_case in &test_cases {
+        let response = app.post_json("/clients", test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+        let body = response.json::<ErrorResponse>().await.unwrap();
+        assert_eq!(body.error, "invalid_redirect_uri");
+    }
+}
+
+#[tokio::test]
+async fn register_should_return_403_if_user_not_admin() {
+    let app = TestApp::new().await;
+    app.post_signup(&json!({
+        "email": "user@example.com",
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.post_login(&json!({
+        "email": "user@example.com",
+        "password": "password123",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    let response = app
+        .post_json(
+            "/clients",
+            &json!({ "client_name": "app", "redirect_uris": [REDIRECT_URI] }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn register_should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_json(
+            "/clients",
+            &json!({ "client_name": "app", "redirect_uris": [REDIRECT_URI] }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_complete_authorization_code_flow() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app).await;
+    let verifier = code_verifier();
+
+    let response = app
+        .get_authorize(&authorize_query(&client_id, &verifier))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .post_consent(&json!({ "consent": consent_token(response).await, "decision": "allow" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    let redirect = location(&response);
+    assert!(redirect.as_str().starts_with(REDIRECT_URI));
+    assert_eq!(query_param(&redirect, "state").as_deref(), Some("xyz"));
+    let code = query_param(&redirect, "code").expect("no code in redirect");
+    assert!(app.audit_sink.events().iter().any(|event| {
+        event.kind == AuditEventKind::ConsentGranted
+            && event.email.as_deref() == Some("sample@example.com")
+    }));
+
+    let response = app
+        .post_token(&json!({
+            "grant_type": "authorization_code",
+            "code": code,
+            "redirect_uri": REDIRECT_URI,
+            "client_id": client_id,
+            "code_verifier": verifier,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+    let tokens = response.json::<TokenResponse>().await.unwrap();
+    assert_eq!(tokens.token_type, "Bearer");
+    assert_eq!(tokens.scope, "openid email");
+
+    let claims =
+        validate_id_token(&tokens.id_token, &ClientId::parse(&client_id).unwrap()).unwrap();
+    assert_eq!(claims.claims.subject, "sample@example.com");
+    assert_eq!(claims.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+
+    let response = app.get_userinfo(&tokens.access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let userinfo = response.json::<UserInfo>().await.unwrap();
+    assert_eq!(userinfo.email, "sample@example.com");
+}
+
+#[tokio::test]
+async fn authorize_should_ask_for_consent_before_issuing_code() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app).await;
+
+    let response = app
+        .get_authorize(&authorize_query(&client_id, &code_verifier()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers().get(header::LOCATION).is_none());
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+    // the answer redirects to the client, which the page's policy has to allow
+    let policy = response.headers()[header::CONTENT_SECURITY_POLICY]
+        .to_str()
+        .unwrap();
+    assert!(policy.contains("form-action 'self' http://localhost:9000;"));
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Allow app to log you in?"));
+    assert!(page.contains("sample@example.com"));
+}
+
+#[tokio::test]
+async fn consent_should_redirect_error_if_user_denies() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app).await;
+    let response = app
+        .get_authorize(&authorize_query(&client_id, &code_verifier()))
+        .await;
+
+    let response = app
+        .post_consent(&json!({ "consent": consent_token(response).await, "decision": "deny" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    let redirect = location(&response);
+    assert_eq!(
+        query_param(&redirect, "error").as_deref(),
+        Some("access_denied")
+    );
+    assert_eq!(query_param(&redirect, "state").as_deref(), Some("xyz"));
+    assert!(query_param(&redirect, "code").is_none());
+    let decisions: Vec<_> = app
+        .audit_sink
+        .events()
+        .into_iter()
+        .filter(|event| {
+            matches!(
+                event.kind,
+                AuditEventKind::ConsentGranted | AuditEventKind::ConsentDenied
+            )
+        })
+        .collect();
+    assert_eq!(decisions.len(), 1);
+    assert_eq!(decisions[0].kind, AuditEventKind::ConsentDenied);
+    assert_eq!(decisions[0].email.as_deref(), Some("sample@example.com"));
+}
+
+#[tokio::test]
+async fn consent_should_return_400_if_not_shown_in_this_session() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app).await;
+    let response = app
+        .get_authorize(&authorize_query(&client_id, &code_verifier()))
+        .await;
+    let consent = consent_token(response).await;
+
+    // a forged page, and a real one from a session since ended
+    let response = app
+        .post_consent(&json!({ "consent": "forged", "decision": "allow" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.post_logout().await.error_for_status().unwrap();
+    login_admin_again(&app).await;
+    let response = app
+        .post_consent(&json!({ "consent": consent, "decision": "allow" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response.headers().get(header::LOCATION).is_none());
+}
+
+#[tokio::test]
+async fn access_token_should_only_be_good_for_userinfo() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app).await;
+    let verifier = code_verifier();
+    let code = authorize(&app, &client_id, &verifier).await;
+    let tokens = app
+        .post_token(&token_form(&client_id, &code, &verifier))
+        .await
+        .json::<TokenResponse>()
+        .await
+        .unwrap();
+
+    assert_eq!(app.get_userinfo(&tokens.access_token).await.status(), 200);
+    let response = app
+        .post_verify_token(&json!({ "token": tokens.access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    // nor does the ID token work as one
+    assert_eq!(app.get_userinfo(&tokens.id_token).await.status(), 401);
+}
+
+#[tokio::test]
+async fn id_token_should_verify_against_published_key() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app).await;
+    let verifier = code_verifier();
+    let code = authorize(&app, &client_id, &verifier).await;
+    let tokens = app
+        .post_token(&token_form(&client_id, &code, &verifier))
+        .await
+        .json::<TokenResponse>()
+        .await
+        .unwrap();
+
+    let jwks = app
+        .http_client
+        .get(format!("{}/jwks.json", app.address))
+        .send()
+        .await
+        .unwrap()
+        .json::<JwkSet>()
+        .await
+        .unwrap();
+
+    let header = decode_header(&tokens.id_token).unwrap();
+    assert_eq!(header.alg, Algorithm::ES256);
+    let jwk = jwks
+        .keys
+        .iter()
+        .find(|jwk| Some(&jwk.kid) == header.kid.as_ref())
+        .expect("signing key not published");
+    let mut validation = Validation::new(Algorithm::ES256);
+    validation.set_audience(&[&client_id]);
+    let claims = decode::<IdTokenClaims>(
+        &tokens.id_token,
+        &DecodingKey::from_ec_components(&jwk.x, &jwk.y).unwrap(),
+        &validation,
+    )
+    .unwrap()
+    .claims;
+    assert_eq!(claims.email, "sample@example.com");
+}
+
+#[tokio::test]
+async fn authorize_should_send_unauthenticated_user_to_login() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app).await;
+    app.post_logout().await.error_for_status().unwrap();
+
+    let response = app
+        .get_authorize(&authorize_query(&client_id, &code_verifier()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    assert!(location.starts_with("/?return_to=%2Fauthorize%3F"));
+}
+
+#[tokio::test]
+async fn authorize_should_return_400_if_redirect_uri_unregistered() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app).await;
+    let mut query = authorize_query(&client_id, &code_verifier());
+    query[2].1 = "http://evil.com/callback".to_string();
+
+    let response = app.get_authorize(&query).await;
+
+    // never redirect to a uri the client didn't register
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response.headers().get(header::LOCATION).is_none());
+}
+
+#[tokio::test]
+async fn authorize_should_redirect_error_if_pkce_missing() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app).await;
+    let query: Vec<_> = authorize_query(&client_id, &code_verifier())
+        .into_iter()
+        .filter(|(key, _)| !key.starts_with("code_challenge"))
+        .collect();
+
+    let response = app.get_authorize(&query).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    let redirect = location(&response);
+    assert_eq!(
+        query_param(&redirect, "error").as_deref(),
+        Some("invalid_request")
+    );
+    assert_eq!(query_param(&redirect, "state").as_deref(), Some("xyz"));
+    assert!(query_param(&redirect, "code").is_none());
+}
+
+#[tokio::test]
+async fn token_should_return_400_if_code_reused_or_verifier_wrong() {
+    let app = TestApp::new().await;
+    login_admin(&app).await;
+    let client_id = register_client(&app).await;
+    let verifier = code_verifier();
+
+    let code = authorize(&app, &client_id, &verifier).await;
+    let response = app
+        .post_token(&token_form(&client_id, &code, &code_verifier()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(body.error, "invalid_grant");
+
+    // a failed exchange still burns the code
+    let response = app
+        .post_token(&token_form(&client_id, &code, &verifier))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let code = authorize(&app, &client_id, &verifier).await;
+    app.post_token(&token_form(&client_id, &code, &verifier))
+        .await
+        .error_for_status()
+        .unwrap();
+    let response = app
+        .post_token(&token_form(&client_id, &code, &verifier))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn token_should_return_400_if_grant_type_unsupported() {
+    let app = TestApp::new().await;
+
+    let response = app.post_token(&json!({ "grant_type": "password" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(body.error, "unsupported_grant_type");
+}
+
+#[tokio::test]
+async fn userinfo_should_return_401_if_token_invalid() {
+    let app = TestApp::new().await;
+
+    let response = app.get_userinfo("invalid").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.headers()[header::WWW_AUTHENTICATE],
+        r#"Bearer error="invalid_token""#
+    );
+}
+
+async fn login_admin(app: &TestApp) {
+    let mut admin = User::new(
+        "sample@example.com".parse().unwrap(),
+        "password123".parse().unwrap(),
+        None,
+    );
+    admin.roles = vec![Role::Admin];
+    app.user_store.add_user(admin).await.unwrap();
+
+    login_admin_again(app).await;
+}
+
+async fn login_admin_again(app: &TestApp) {
+    app.post_login(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+}
+
+async fn register_client(app: &TestApp) -> String {
+    app.post_json(
+        "/clients",
+        &json!({ "client_name": "app", "redirect_uris": [REDIRECT_URI] }),
+    )
+    .await
+    .error_for_status()
+    .unwrap()
+    .json::<ClientRegistrationResponse>()
+    .await
+    .unwrap()
+    .client_id
+}
+
+async fn authorize(app: &TestApp, client_id: &str, verifier: &str) -> String {
+    let response = app
+        .get_authorize(&authorize_query(client_id, verifier))
+        .await;
+    let response = app
+        .post_consent(&json!({ "consent": consent_token(response).await, "decision": "allow" }))
+        .await;
+    query_param(&location(&response), "code").expect("no code in redirect")
+}
+
+// the hidden field the consent page posts back
+async fn consent_token(response: reqwest::Response) -> String {
+    let page = response.text().await.unwrap();
+    let (_, rest) = page
+        .split_once(r#"name="consent" value=""#)
+        .expect("not a consent page");
+    rest.split('"').next().unwrap().to_string()
+}
+
+fn code_verifier() -> String {
+    let mut bytes = [0; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn authorize_query(client_id: &str, verifier: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("response_type", "code".to_string()),
+        ("client_id", client_id.to_string()),
+        ("redirect_uri", REDIRECT_URI.to_string()),
+        ("scope", "openid email".to_string()),
+        ("state", "xyz".to_string()),
+        ("nonce", "n-0S6_WzA2Mj".to_string()),
+        (
+            "code_challenge",
+            CodeChallenge::of(verifier).as_ref().to_string(),
+        ),
+        ("code_challenge_method", "S256".to_string()),
+    ]
+}
+
+fn token_form(client_id: &str, code: &str, verifier: &str) -> serde_json::Value {
+    json!({
+        "grant_type": "authorization_code",
+        "code": code,
+        "redirect_uri": REDIRECT_URI,
+        "client_id": client_id,
+        "code_verifier": verifier,
+    })
+}
+
+fn location(response: &reqwest::Response) -> Url {
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    Url::parse(location).expect("redirect is not absolute")
+}
+
+fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+#[tokio::test]
+async fn should_sweep_expired_codes() {
+    let app = TestApp::with_sweep_interval(Duration::from_millis(10)).await;
+    let code = AuthorizationCode::generate();
+    app.client_store
+        .add_code(
+            code.clone(),
+            PendingAuthorization {
+                client_id: ClientId::default(),
+                redirect_uri: REDIRECT_URI.to_string(),
+                email: "sample@example.com".parse().unwrap(),
+                scopes: vec!["openid".to_string()],
+                nonce: None,
+                code_challenge: CodeChallenge::of("verifier"),
+                expires_at: 0,
+            },
+        )
+        .await
+        .unwrap();
+
+    // taking the code would remove it, so give the sweeper plenty of turns first
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert!(app.client_store.take_code(&code).await.is_err());
+}
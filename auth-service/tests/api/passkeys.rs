@@ -0,0 +1,424 @@
+use std::time::Duration;
+
+use auth_service::{
+    domain::{Ceremony, PasskeyChallenge, PasskeyStore, PendingCeremony},
+    routes::{AuthenticationOptions, RegistrationOptions},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use ciborium::Value;
+use p256::ecdsa::{Signature, SigningKey, signature::Signer};
+use rand::RngCore;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::helpers::TestApp;
+
+const ORIGIN: &str = "http://localhost:3000";
+
+// user present, user verified
+const FLAGS_ASSERTION: u8 = 0x05;
+// ... with attested credential data
+const FLAGS_ATTESTATION: u8 = 0x45;
+
+/// Stands in for a platform authenticator, producing `none` attestations and ES256 assertions.
+struct SoftwareAuthenticator {
+    key: SigningKey,
+    credential_id: Vec<u8>,
+    sign_count: u32,
+    origin: String,
+}
+
+impl SoftwareAuthenticator {
+    fn new() -> Self {
+        let mut secret = [0; 32];
+        rand::rng().fill_bytes(&mut secret);
+        let mut credential_id = vec![0; 16];
+        rand::rng().fill_bytes(&mut credential_id);
+
+        Self {
+            key: SigningKey::from_slice(&secret).expect("random scalar out of range"),
+            credential_id,
+            sign_count: 0,
+            origin: ORIGIN.to_string(),
+        }
+    }
+
+    fn encoded_id(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.credential_id)
+    }
+
+    fn client_data(&self, kind: &str, challenge: &str) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "type": kind,
+            "challenge": challenge,
+            "origin": self.origin,
+            "crossOrigin": false,
+        }))
+        .unwrap()
+    }
+
+    fn authenticator_data(&self, rp_id: &str, flags: u8) -> Vec<u8> {
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&self.sign_count.to_be_bytes());
+        data
+    }
+
+    fn cose_key(&self) -> Vec<u8> {
+        let point = self.key.verifying_key().to_encoded_point(false);
+        let key = Value::Map(vec![
+            (Value::Integer(1.into()), Value::Integer(2.into())),
+            (Value::Integer(3.into()), Value::Integer((-7).into())),
+            (Value::Integer((-1).into()), Value::Integer(1.into())),
+            (
+                Value::Integer((-2).into()),
+                Value::Bytes(point.x().unwrap().to_vec()),
+            ),
+            (
+                Value::Integer((-3).into()),
+                Value::Bytes(point.y().unwrap().to_vec()),
+            ),
+        ]);
+
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&key, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn register(&self, options: &RegistrationOptions) -> serde_json::Value {
+        let client_data = self.client_data("webauthn.create", &options.challenge);
+
+        let mut auth_data = self.authenticator_data(&options.rp.id, FLAGS_ATTESTATION);
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&self.credential_id);
+        auth_data.extend_from_slice(&self.cose_key());
+
+        let attestation = Value::Map(vec![
+            (Value::Text("fmt".into()), Value::Text("none".into())),
+            (Value::Text("attStmt".into()), Value::Map(vec![])),
+            (Value::Text("authData".into()), Value::Bytes(auth_data)),
+        ]);
+        let mut attestation_object = Vec::new();
+        ciborium::into_writer(&attestation, &mut attestation_object).unwrap();
+
+        json!({
+            "id": self.encoded_id(),
+            "rawId": self.encoded_id(),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+                "attestationObject": URL_SAFE_NO_PAD.encode(attestation_object),
+            },
+        })
+    }
+
+    fn authenticate(&mut self, email: &str, options: &AuthenticationOptions) -> serde_json::Value {
+        self.sign_count += 1;
+        let client_data = self.client_data("webauthn.get", &options.challenge);
+        let auth_data = self.authenticator_data(&options.rp_id, FLAGS_ASSERTION);
+
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&Sha256::digest(&client_data));
+        let signature: Signature = self.key.sign(&signed);
+
+        json!({
+            "email": email,
+            "id": self.encoded_id(),
+            "rawId": self.encoded_id(),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+                "authenticatorData": URL_SAFE_NO_PAD.encode(auth_data),
+                "signature": URL_SAFE_NO_PAD.encode(signature.to_der().as_bytes()),
+            },
+        })
+    }
+}
+
+#[tokio::test]
+async fn should_register_and_login_with_passkey() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+
+    let response = register(&app, &authenticator).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let options = start_login(&app).await;
+    assert_eq!(options.rp_id, "localhost");
+    assert_eq!(options.allow_credentials.len(), 1);
+    assert_eq!(options.allow_credentials[0].id, authenticator.encoded_id());
+
+    let response = app
+        .post_json(
+            "/passkeys/login/finish",
+            &authenticator.authenticate("sample@example.com", &options),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("no cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    let credentials = app
+        .passkey_store
+        .get_credentials(&"sample@example.com".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(credentials[0].sign_count, 1);
+}
+
+#[tokio::test]
+async fn registration_options_should_exclude_existing_passkeys() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+    let authenticator = SoftwareAuthenticator::new();
+    register(&app, &authenticator)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let options = start_registration(&app).await;
+
+    assert_eq!(options.rp.id, "localhost");
+    assert_eq!(options.user.name, "sample@example.com");
+    assert_ne!(options.user.id, "sample@example.com");
+    assert_eq!(options.pub_key_cred_params[0].alg, -7);
+    assert_eq!(options.exclude_credentials.len(), 1);
+    assert_eq!(
+        options.exclude_credentials[0].id,
+        authenticator.encoded_id()
+    );
+}
+
+#[tokio::test]
+async fn registration_should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_json("/passkeys/register/start", &json!({})).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn registration_should_return_401_if_origin_incorrect() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    authenticator.origin = "http://evil.com".to_string();
+
+    let response = register(&app, &authenticator).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn registration_should_return_401_if_challenge_unknown() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+    let authenticator = SoftwareAuthenticator::new();
+    let mut options = start_registration(&app).await;
+    options.challenge = URL_SAFE_NO_PAD.encode(b"not the challenge");
+
+    let response = app
+        .post_json(
+            "/passkeys/register/finish",
+            &authenticator.register(&options),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn registration_should_return_400_if_not_base64() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+
+    let response = app
+        .post_json(
+            "/passkeys/register/finish",
+            &json!({
+                "id": "abc",
+                "response": {
+                    "clientDataJSON": "not base64!",
+                    "attestationObject": "not base64!",
+                },
+            }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn login_should_return_401_if_no_passkeys() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+
+    let response = app
+        .post_json(
+            "/passkeys/login/start",
+            &json!({ "email": "sample@example.com" }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn login_should_return_401_if_assertion_replayed() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register(&app, &authenticator)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let options = start_login(&app).await;
+    let assertion = authenticator.authenticate("sample@example.com", &options);
+    app.post_json("/passkeys/login/finish", &assertion)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let response = app.post_json("/passkeys/login/finish", &assertion).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn login_should_return_401_if_signed_by_other_key() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+    let authenticator = SoftwareAuthenticator::new();
+    register(&app, &authenticator)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // same credential id, different private key
+    let mut impostor = SoftwareAuthenticator::new();
+    impostor.credential_id = authenticator.credential_id.clone();
+    let options = start_login(&app).await;
+
+    let response = app
+        .post_json(
+            "/passkeys/login/finish",
+            &impostor.authenticate("sample@example.com", &options),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn login_should_return_401_if_sign_count_regressed() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register(&app, &authenticator)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let options = start_login(&app).await;
+    app.post_json(
+        "/passkeys/login/finish",
+        &authenticator.authenticate("sample@example.com", &options),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+
+    // a cloned authenticator would be behind the original
+    authenticator.sign_count = 0;
+    let options = start_login(&app).await;
+    let response = app
+        .post_json(
+            "/passkeys/login/finish",
+            &authenticator.authenticate("sample@example.com", &options),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_sweep_expired_challenges() {
+    let app = TestApp::with_sweep_interval(Duration::from_millis(10)).await;
+    let challenge = PasskeyChallenge::generate();
+    app.passkey_store
+        .add_challenge(
+            challenge.clone(),
+            PendingCeremony {
+                email: "sample@example.com".parse().unwrap(),
+                ceremony: Ceremony::Authentication,
+                expires_at: 0,
+            },
+        )
+        .await
+        .unwrap();
+
+    // looking the challenge up would take it, so give the sweeper plenty of turns first
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert!(app.passkey_store.take_challenge(&challenge).await.is_err());
+}
+
+async fn login_user(app: &TestApp) {
+    app.post_signup(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    app.post_login(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+}
+
+async fn start_registration(app: &TestApp) -> RegistrationOptions {
+    app.post_json("/passkeys/register/start", &json!({}))
+        .await
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn register(app: &TestApp, authenticator: &SoftwareAuthenticator) -> reqwest::Response {
+    let options = start_registration(app).await;
+    app.post_json(
+        "/passkeys/register/finish",
+        &authenticator.register(&options),
+    )
+    .await
+}
+
+async fn start_login(app: &TestApp) -> AuthenticationOptions {
+    app.post_json(
+        "/passkeys/login/start",
+        &json!({ "email": "sample@example.com" }),
+    )
+    .await
+    .error_for_status()
+    .unwrap()
+    .json()
+    .await
+    .unwrap()
+}
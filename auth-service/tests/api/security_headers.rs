@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use auth_service::{
+    Application,
+    app_state::AppState,
+    services::{
+        HashMapClientStore, HashMapFederatedLoginStore, HashMapLoginAttemptStore,
+        HashMapPasskeyStore, HashMapSessionStore, HashMapUserStore, HashSetTokenStore,
+        MockEmailClient, VecAuditSink,
+    },
+    utils::{
+        constants::test,
+        security_headers::{
+            DEFAULT_CONTENT_SECURITY_POLICY, DEFAULT_FRAME_OPTIONS, DEFAULT_REFERRER_POLICY,
+            DEFAULT_STRICT_TRANSPORT_SECURITY, SecurityHeaders,
+        },
+    },
+};
+use reqwest::header::{self, HeaderMap, HeaderValue};
+
+use crate::helpers::TestApp;
+
+fn assert_default_headers(headers: &HeaderMap) {
+    assert_eq!(
+        headers[header::STRICT_TRANSPORT_SECURITY],
+        DEFAULT_STRICT_TRANSPORT_SECURITY
+    );
+    assert_eq!(
+        headers[header::CONTENT_SECURITY_POLICY],
+        DEFAULT_CONTENT_SECURITY_POLICY
+    );
+    assert_eq!(headers[header::X_FRAME_OPTIONS], DEFAULT_FRAME_OPTIONS);
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(headers[header::REFERRER_POLICY], DEFAULT_REFERRER_POLICY);
+}
+
+#[tokio::test]
+async fn should_set_security_headers_on_html() {
+    let app = TestApp::new().await;
+
+    let response = app.get_root().await;
+
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html");
+    assert_default_headers(response.headers());
+}
+
+#[tokio::test]
+async fn should_set_security_headers_on_json() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": "not an email",
+            "password": "password123",
+            "requires2FA": false,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    assert_default_headers(response.headers());
+}
+
+#[tokio::test]
+async fn should_use_configured_security_headers() {
+    let app_state = AppState::new(
+        HashMapUserStore::default(),
+        HashSetTokenStore::default(),
+        HashMapLoginAttemptStore::default(),
+        HashMapPasskeyStore::default(),
+        MockEmailClient::default(),
+        HashMapClientStore::default(),
+        HashMapFederatedLoginStore::default(),
+        HashMapSessionStore::default(),
+        Arc::new(VecAuditSink::default()),
+        Vec::new(),
+    );
+    let security_headers = SecurityHeaders {
+        strict_transport_security: None,
+        content_security_policy: Some(HeaderValue::from_static("default-src 'none'")),
+        ..SecurityHeaders::default()
+    };
+    let app = Application::build(app_state, test::APP_ADDRESS, None)
+        .await
+        .unwrap()
+        .with_security_headers(security_headers);
+    let address = format!("http://{}", app.address);
+
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(app.run());
+
+    let response = reqwest::get(format!("{address}/health/live"))
+        .await
+        .unwrap();
+
+    let headers = response.headers();
+    assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+    assert_eq!(
+        headers[header::CONTENT_SECURITY_POLICY],
+        "default-src 'none'"
+    );
+    assert_eq!(headers[header::X_FRAME_OPTIONS], DEFAULT_FRAME_OPTIONS);
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+}
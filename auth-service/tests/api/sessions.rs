@@ -0,0 +1,251 @@
+use std::time::Duration;
+
+use auth_service::{
+    ErrorResponse,
+    domain::{DeviceInfo, Session, SessionId, SessionStore, UserStore},
+    routes::SessionSummary,
+    utils::auth::generate_auth_token,
+};
+use serde_json::json;
+
+use crate::helpers::TestApp;
+
+const FIREFOX_ON_LINUX: &str =
+    "Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0";
+
+async fn signup_user(app: &TestApp) {
+    app.post_signup(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+}
+
+async fn login_user(app: &TestApp) {
+    app.post_login(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+}
+
+async fn list_sessions(app: &TestApp) -> Vec<SessionSummary> {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn should_list_sessions_with_device_info() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    login_user(&app).await;
+    let laptop = app.other_device(FIREFOX_ON_LINUX);
+    login_user(&laptop).await;
+
+    let sessions = list_sessions(&laptop).await;
+
+    assert_eq!(sessions.len(), 2);
+    let current = sessions.iter().find(|session| session.current).unwrap();
+    assert_eq!(current.device, "Firefox on Linux");
+    assert_eq!(current.user_agent.as_deref(), Some(FIREFOX_ON_LINUX));
+    assert_eq!(current.ip.as_deref(), Some("127.0.0.1"));
+    assert_eq!(sessions.iter().filter(|session| session.current).count(), 1);
+}
+
+#[tokio::test]
+async fn should_not_list_other_users_sessions() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    login_user(&app).await;
+
+    let other = app.other_device(FIREFOX_ON_LINUX);
+    other
+        .post_signup(&json!({
+            "email": "other@example.com",
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await
+        .error_for_status()
+        .unwrap();
+    other
+        .post_login(&json!({
+            "email": "other@example.com",
+            "password": "password123",
+        }))
+        .await
+        .error_for_status()
+        .unwrap();
+
+    assert_eq!(list_sessions(&app).await.len(), 1);
+    assert_eq!(list_sessions(&other).await.len(), 1);
+}
+
+#[tokio::test]
+async fn should_revoke_other_session() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    login_user(&app).await;
+    let laptop = app.other_device(FIREFOX_ON_LINUX);
+    login_user(&laptop).await;
+    let laptop_token = laptop.auth_token().unwrap();
+
+    let sessions = list_sessions(&app).await;
+    let laptop_session = sessions.iter().find(|session| !session.current).unwrap();
+    let response = app.delete_session(&laptop_session.id).await;
+
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app
+        .post_verify_token(&json!({ "token": laptop_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(laptop.get_sessions().await.status().as_u16(), 401);
+    // the browser asking stays logged in
+    assert_eq!(list_sessions(&app).await.len(), 1);
+}
+
+#[tokio::test]
+async fn should_clear_cookie_when_revoking_current_session() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    login_user(&app).await;
+
+    let current = list_sessions(&app).await.remove(0);
+    let response = app.delete_session(&current.id).await;
+
+    assert_eq!(response.status().as_u16(), 204);
+    assert!(app.auth_token().is_none());
+}
+
+#[tokio::test]
+async fn should_return_404_if_session_unknown_or_not_owned() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    login_user(&app).await;
+
+    let other = app.other_device(FIREFOX_ON_LINUX);
+    other
+        .post_signup(&json!({
+            "email": "other@example.com",
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await
+        .error_for_status()
+        .unwrap();
+    other
+        .post_login(&json!({
+            "email": "other@example.com",
+            "password": "password123",
+        }))
+        .await
+        .error_for_status()
+        .unwrap();
+    let other_session = list_sessions(&other).await.remove(0);
+
+    for id in ["unknown", other_session.id.as_str()] {
+        let response = app.delete_session(id).await;
+
+        assert_eq!(response.status().as_u16(), 404, "Failed for {id}");
+        let body = response.json::<ErrorResponse>().await.unwrap();
+        assert_eq!(body.error, "Session not found!");
+    }
+
+    // still logged in elsewhere
+    assert_eq!(other.get_sessions().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_log_out_everywhere() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    login_user(&app).await;
+    let laptop = app.other_device(FIREFOX_ON_LINUX);
+    login_user(&laptop).await;
+    let token = app.auth_token().unwrap();
+    let laptop_token = laptop.auth_token().unwrap();
+
+    let response = app.delete_all_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 204);
+    assert!(app.auth_token().is_none());
+    for token in [token, laptop_token] {
+        let response = app.post_verify_token(&json!({ "token": token })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // logging in again starts over
+    login_user(&app).await;
+    assert_eq!(list_sessions(&app).await.len(), 1);
+}
+
+#[tokio::test]
+async fn log_out_everywhere_should_revoke_tokens_without_session() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    login_user(&app).await;
+    // e.g. issued before a restart emptied the session store
+    let user = app
+        .user_store
+        .get_user(&"sample@example.com".parse().unwrap())
+        .await
+        .unwrap();
+    let token = generate_auth_token(&user).unwrap();
+
+    app.delete_all_sessions().await.error_for_status().unwrap();
+
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn logout_should_end_session() {
+    let app = TestApp::new().await;
+    signup_user(&app).await;
+    login_user(&app).await;
+    let laptop = app.other_device(FIREFOX_ON_LINUX);
+    login_user(&laptop).await;
+
+    laptop.post_logout().await.error_for_status().unwrap();
+
+    let sessions = list_sessions(&app).await;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    assert_eq!(app.get_sessions().await.status().as_u16(), 400);
+    assert_eq!(app.delete_all_sessions().await.status().as_u16(), 400);
+    assert_eq!(app.delete_session("unknown").await.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_sweep_expired_sessions() {
+    let app = TestApp::with_sweep_interval(Duration::from_millis(10)).await;
+    let email = "sample@example.com".parse().unwrap();
+    app.session_store
+        .add_session(Session {
+            id: SessionId::from_token_id("expired".to_string()),
+            email: "sample@example.com".parse().unwrap(),
+            token: "token".to_string(),
+            device: DeviceInfo::default(),
+            created_at: 0,
+            last_seen_at: 0,
+            expires_at: 0,
+        })
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(app.session_store.get_sessions(&email).await, Ok(vec![]));
+}
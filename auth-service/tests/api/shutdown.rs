@@ -0,0 +1,149 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use auth_service::{
+    Application,
+    app_state::AppState,
+    domain::{AuditEvent, AuditSink, AuditSinkError, Email},
+    services::{
+        HashMapClientStore, HashMapFederatedLoginStore, HashMapLoginAttemptStore,
+        HashMapPasskeyStore, HashMapSessionStore, HashMapUserStore, HashSetTokenStore,
+        MockEmailClient,
+    },
+    utils::{constants::test, shutdown::ShutdownHandle},
+};
+use serde_json::json;
+use tokio::{sync::Notify, time::timeout};
+
+use crate::helpers::TestApp;
+
+// holds every request it records an event for until released, keeping them in flight
+#[derive(Default)]
+struct GatedAuditSink {
+    entered: Notify,
+    release: Notify,
+    flushed: AtomicBool,
+}
+
+#[async_trait]
+impl AuditSink for GatedAuditSink {
+    async fn record(&self, _: AuditEvent) -> Result<(), AuditSinkError> {
+        self.entered.notify_one();
+        self.release.notified().await;
+        Ok(())
+    }
+
+    async fn events_for(&self, _: &Email) -> Result<Vec<AuditEvent>, AuditSinkError> {
+        Ok(Vec::new())
+    }
+
+    async fn forget(&self, _: &Email) -> Result<(), AuditSinkError> {
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), AuditSinkError> {
+        self.flushed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+async fn start(
+    audit_sink: Arc<GatedAuditSink>,
+    drain_timeout: Duration,
+) -> (String, ShutdownHandle) {
+    let app_state = AppState::new(
+        HashMapUserStore::default(),
+        HashSetTokenStore::default(),
+        HashMapLoginAttemptStore::default(),
+        HashMapPasskeyStore::default(),
+        MockEmailClient::default(),
+        HashMapClientStore::default(),
+        HashMapFederatedLoginStore::default(),
+        HashMapSessionStore::default(),
+        audit_sink,
+        Vec::new(),
+    );
+    let app = Application::build(app_state, test::APP_ADDRESS, None)
+        .await
+        .unwrap()
+        .with_drain_timeout(drain_timeout);
+    let address = format!("http://{}", app.address);
+    let shutdown = app.shutdown_handle();
+
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(app.run());
+
+    (address, shutdown)
+}
+
+async fn signup(address: String) -> reqwest::Result<reqwest::Response> {
+    reqwest::Client::new()
+        .post(format!("{address}/signup"))
+        .json(&json!({
+            "email": "a@b.com",
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .send()
+        .await
+}
+
+#[tokio::test]
+async fn should_stop_taking_requests_once_shut_down() {
+    let app = TestApp::new().await;
+    assert_eq!(app.get_root().await.status().as_u16(), 200);
+
+    timeout(Duration::from_secs(5), app.shutdown.shutdown())
+        .await
+        .expect("shutdown never finished");
+
+    assert!(reqwest::get(format!("{}/", app.address)).await.is_err());
+}
+
+#[tokio::test]
+async fn should_finish_requests_in_flight_before_stopping() {
+    let audit_sink = Arc::new(GatedAuditSink::default());
+    let (address, shutdown) = start(audit_sink.clone(), Duration::from_secs(30)).await;
+    let request = tokio::spawn(signup(address));
+    audit_sink.entered.notified().await;
+
+    let stopping = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move { shutdown.shutdown().await }
+    });
+    tokio::task::yield_now().await;
+    assert!(!stopping.is_finished());
+    assert!(!audit_sink.flushed.load(Ordering::SeqCst));
+    audit_sink.release.notify_one();
+
+    let response = request.await.unwrap().unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    timeout(Duration::from_secs(5), stopping)
+        .await
+        .expect("shutdown never finished")
+        .unwrap();
+    assert!(audit_sink.flushed.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn should_abort_requests_still_running_after_the_drain_timeout() {
+    let audit_sink = Arc::new(GatedAuditSink::default());
+    let (address, shutdown) = start(audit_sink.clone(), Duration::from_millis(100)).await;
+    let request = tokio::spawn(signup(address));
+    audit_sink.entered.notified().await;
+
+    // the request is never released
+    timeout(Duration::from_secs(5), shutdown.shutdown())
+        .await
+        .expect("shutdown never finished");
+
+    let response = request.await.unwrap().unwrap();
+    assert_eq!(response.status().as_u16(), 503);
+    assert!(audit_sink.flushed.load(Ordering::SeqCst));
+}
@@ -1,6 +1,6 @@
 use crate::helpers::TestApp;
 
-use auth_service::{ErrorResponse, routes::SignupResponse};
+use auth_service::{ErrorResponse, domain::FieldError, routes::SignupResponse};
 use serde_json::json;
 
 #[tokio::test]
@@ -87,10 +87,9 @@ async fn should_return_400_if_invalid_input() {
             .await;
 
         assert_eq!(response.status().as_u16(), 400);
-        assert_eq!(
-            response.json::<ErrorResponse>().await.unwrap().error,
-            "Invalid credentials!"
-        );
+        let body = response.json::<ErrorResponse>().await.unwrap();
+        assert_eq!(body.code, "validation_failed");
+        assert_eq!(invalid_fields(&body), ["email"]);
     }
     for invalid_password in invalid_passwords {
         let response = app
@@ -102,13 +101,45 @@ async fn should_return_400_if_invalid_input() {
             .await;
 
         assert_eq!(response.status().as_u16(), 400);
+        let body = response.json::<ErrorResponse>().await.unwrap();
+        assert_eq!(body.code, "validation_failed");
         assert_eq!(
-            response.json::<ErrorResponse>().await.unwrap().error,
-            "Invalid credentials!"
+            body.details.unwrap(),
+            [FieldError {
+                field: "password".to_string(),
+                code: "length".to_string(),
+                message: Some("must be at least 8 characters long".to_string()),
+            }]
         );
     }
 }
 
+#[tokio::test]
+async fn should_report_every_invalid_field() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_signup(&json!({
+            "email": "not an email",
+            "password": "short",
+            "requires2FA": false,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(body.error, "Validation failed!");
+    assert_eq!(invalid_fields(&body), ["email", "password"]);
+}
+
+fn invalid_fields(body: &ErrorResponse) -> Vec<&str> {
+    body.details
+        .iter()
+        .flatten()
+        .map(|field| field.field.as_str())
+        .collect()
+}
+
 #[tokio::test]
 async fn should_return_409_if_email_already_exists() {
     let app = TestApp::new().await;
@@ -0,0 +1,61 @@
+use auth_service::utils::telemetry::tracer_provider;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use serde_json::json;
+use tracing::subscriber::DefaultGuard;
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::{helpers::TestApp, mock_collector::MockCollector};
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+// the test's runtime is single threaded, so this covers the server it spawns too
+fn export_to(collector: &MockCollector) -> (SdkTracerProvider, DefaultGuard) {
+    let provider = tracer_provider(&collector.endpoint).unwrap();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    let guard = tracing::subscriber::set_default(subscriber);
+    (provider, guard)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[tokio::test]
+async fn should_export_request_spans_in_the_callers_trace() {
+    let collector = MockCollector::start();
+    let (provider, _guard) = export_to(&collector);
+    let app = TestApp::new().await;
+
+    app.http_client
+        .post(format!("{}/login", app.address))
+        .header("traceparent", format!("00-{TRACE_ID}-{PARENT_SPAN_ID}-01"))
+        .json(&json!({ "email": "a@b.com", "password": "password123" }))
+        .send()
+        .await
+        .unwrap();
+
+    let span = collector.span_named(&provider, "request").await;
+    assert_eq!(hex(&span.trace_id), TRACE_ID);
+    assert_eq!(hex(&span.parent_span_id), PARENT_SPAN_ID);
+    assert!(
+        span.attributes
+            .iter()
+            .any(|attribute| attribute.key == "path")
+    );
+}
+
+#[tokio::test]
+async fn should_start_a_new_trace_without_traceparent() {
+    let collector = MockCollector::start();
+    let (provider, _guard) = export_to(&collector);
+    let app = TestApp::new().await;
+
+    app.get_root().await;
+
+    let span = collector.span_named(&provider, "request").await;
+    assert_ne!(hex(&span.trace_id), TRACE_ID);
+    assert!(span.parent_span_id.is_empty());
+}
@@ -0,0 +1,210 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use auth_service::{
+    Application,
+    app_state::AppState,
+    services::{
+        HashMapClientStore, HashMapFederatedLoginStore, HashMapLoginAttemptStore,
+        HashMapPasskeyStore, HashMapSessionStore, HashMapUserStore, HashSetTokenStore,
+        MockEmailClient, VecAuditSink,
+    },
+    utils::{
+        constants::{JWT_COOKIE_NAME, test},
+        tls::TlsSettings,
+    },
+};
+use reqwest::{Certificate, StatusCode, header, redirect};
+use serde_json::json;
+
+struct TlsTestApp {
+    address: SocketAddr,
+    redirect_address: Option<SocketAddr>,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+}
+
+impl TlsTestApp {
+    async fn new(redirect: bool) -> (Self, Certificate) {
+        let id = uuid::Uuid::new_v4();
+        let cert_path = std::env::temp_dir().join(format!("tls-{id}.crt"));
+        let key_path = std::env::temp_dir().join(format!("tls-{id}.key"));
+        let certificate = write_self_signed(&cert_path, &key_path);
+
+        let app_state = AppState::new(
+            HashMapUserStore::default(),
+            HashSetTokenStore::default(),
+            HashMapLoginAttemptStore::default(),
+            HashMapPasskeyStore::default(),
+            MockEmailClient::default(),
+            HashMapClientStore::default(),
+            HashMapFederatedLoginStore::default(),
+            HashMapSessionStore::default(),
+            std::sync::Arc::new(VecAuditSink::default()),
+            Vec::new(),
+        );
+        let settings = TlsSettings {
+            cert_path: cert_path.clone(),
+            key_path: key_path.clone(),
+            redirect_address: redirect.then(|| test::APP_ADDRESS.to_string()),
+            reload_interval: Duration::from_millis(50),
+        };
+        let app = Application::build(app_state, test::APP_ADDRESS, Some(settings))
+            .await
+            .expect("could not build application");
+        let address = app.address.parse().unwrap();
+        let redirect_address = app.redirect_address.as_ref().map(|a| a.parse().unwrap());
+
+        #[allow(clippy::let_underscore_future)]
+        let _ = tokio::spawn(app.run());
+
+        let app = Self {
+            address,
+            redirect_address,
+            cert_path,
+            key_path,
+        };
+        (app, certificate)
+    }
+
+    // a client trusting only `certificate`, that reaches the app as localhost
+    fn client(&self, certificate: Certificate) -> reqwest::Client {
+        reqwest::Client::builder()
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(certificate)
+            .resolve("localhost", self.address)
+            .build()
+            .unwrap()
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("https://localhost:{}{path}", self.address.port())
+    }
+}
+
+impl Drop for TlsTestApp {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.cert_path);
+        let _ = std::fs::remove_file(&self.key_path);
+    }
+}
+
+fn write_self_signed(cert_path: &Path, key_path: &Path) -> Certificate {
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert_pem = certified.cert.pem();
+    // the key first, so the certificate is never paired with the wrong one for long
+    std::fs::write(key_path, certified.signing_key.serialize_pem()).unwrap();
+    std::fs::write(cert_path, &cert_pem).unwrap();
+    Certificate::from_pem(cert_pem.as_bytes()).unwrap()
+}
+
+#[tokio::test]
+async fn should_serve_https() {
+    let (app, certificate) = TlsTestApp::new(false).await;
+
+    let response = app
+        .client(certificate)
+        .get(app.url("/health/live"))
+        .send()
+        .await
+        .expect("HTTPS request failed");
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(app.redirect_address, None);
+}
+
+#[tokio::test]
+async fn should_only_send_auth_cookie_back_over_https() {
+    let (app, certificate) = TlsTestApp::new(false).await;
+    let client = app.client(certificate);
+    let credentials = json!({ "email": "a@b.com", "password": "password123" });
+    client
+        .post(app.url("/signup"))
+        .json(&json!({ "email": "a@b.com", "password": "password123", "requires2FA": false }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = client
+        .post(app.url("/login"))
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("no jwt cookie found");
+    assert!(cookie.secure());
+}
+
+#[tokio::test]
+async fn should_reject_plain_http_on_the_https_port() {
+    let (app, _) = TlsTestApp::new(false).await;
+
+    let result = reqwest::get(format!("http://{}/health/live", app.address)).await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn should_pick_up_renewed_certificate() {
+    let (app, old_certificate) = TlsTestApp::new(false).await;
+    let new_certificate = write_self_signed(&app.cert_path, &app.key_path);
+
+    let mut reloaded = false;
+    for _ in 0..100 {
+        let result = app
+            .client(new_certificate.clone())
+            .get(app.url("/health/live"))
+            .send()
+            .await;
+        if result.is_ok_and(|response| response.status() == StatusCode::OK) {
+            reloaded = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    assert!(reloaded, "the renewed certificate was never served");
+    let result = app
+        .client(old_certificate)
+        .get(app.url("/health/live"))
+        .send()
+        .await;
+    assert!(result.is_err(), "the old certificate is still served");
+}
+
+#[tokio::test]
+async fn should_redirect_http_to_https() {
+    let (app, certificate) = TlsTestApp::new(true).await;
+    let redirect_address = app.redirect_address.expect("no redirect listener");
+    let client = reqwest::Client::builder()
+        .redirect(redirect::Policy::none())
+        .resolve("localhost", redirect_address)
+        .build()
+        .unwrap();
+
+    let response = client
+        .get(format!(
+            "http://localhost:{}/health/live?verbose=1",
+            redirect_address.port()
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    assert_eq!(location, app.url("/health/live?verbose=1"));
+
+    let response = app.client(certificate).get(location).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
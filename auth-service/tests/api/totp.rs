@@ -0,0 +1,269 @@
+use auth_service::{
+    domain::{
+        RECOVERY_CODE_COUNT, RecoveryCodeHash, TOTP_STEP_SECONDS, TotpSecret, TwoFactorMethod,
+        UserStore,
+    },
+    routes::{RecoveryCodesResponse, TotpEnrollmentResponse, TwoFactorAuthResponse},
+};
+use serde_json::json;
+
+use crate::helpers::{TestApp, now, setup_totp_user};
+
+#[tokio::test]
+async fn enroll_should_return_secret_and_otpauth_uri() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+
+    let response = app.post_enroll_totp().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.json::<TotpEnrollmentResponse>().await.unwrap();
+    assert!(
+        body.otpauth_uri
+            .starts_with("otpauth://totp/auth-service:sample%40example.com?")
+    );
+    assert!(body.otpauth_uri.contains(&body.secret));
+
+    // 2FA isn't switched on until the code is confirmed
+    let user = app
+        .user_store
+        .get_user(&"sample@example.com".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(user.two_factor, None);
+    assert_eq!(user.pending_totp.unwrap().to_base32(), body.secret);
+}
+
+#[tokio::test]
+async fn enroll_should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_enroll_totp().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn confirm_should_enable_totp_with_valid_code() {
+    let app = TestApp::new().await;
+
+    let (secret, recovery_codes) = setup_totp_user(&app, "sample@example.com", "password123").await;
+
+    let user = app
+        .user_store
+        .get_user(&"sample@example.com".parse().unwrap())
+        .await
+        .unwrap();
+    assert!(matches!(
+        user.two_factor,
+        Some(TwoFactorMethod::Totp { secret: ref enrolled, .. }) if *enrolled == secret
+    ));
+    assert_eq!(user.pending_totp, None);
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_COUNT);
+    assert_eq!(user.recovery_codes.len(), RECOVERY_CODE_COUNT);
+}
+
+#[tokio::test]
+async fn confirm_should_return_401_if_code_incorrect() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+    app.post_enroll_totp().await.error_for_status().unwrap();
+
+    let response = app.post_confirm_totp(&json!({ "code": "000000" })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    let user = app
+        .user_store
+        .get_user(&"sample@example.com".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(user.two_factor, None);
+    assert!(user.pending_totp.is_some());
+}
+
+#[tokio::test]
+async fn confirm_should_return_401_if_replacing_totp_without_current_code() {
+    let app = TestApp::new().await;
+    let (secret, _) = setup_totp_user(&app, "sample@example.com", "password123").await;
+    let pending = reenroll(&app).await;
+
+    for current_code in [None, Some("000000")] {
+        let response = app
+            .post_confirm_totp(&json!({
+                "code": pending.generate_code(now()),
+                "currentCode": current_code,
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    let user = app
+        .user_store
+        .get_user(&"sample@example.com".parse().unwrap())
+        .await
+        .unwrap();
+    assert!(matches!(
+        user.two_factor,
+        Some(TwoFactorMethod::Totp { secret: ref enrolled, .. }) if *enrolled == secret
+    ));
+}
+
+#[tokio::test]
+async fn confirm_should_replace_totp_with_current_code() {
+    let app = TestApp::new().await;
+    let (secret, _) = setup_totp_user(&app, "sample@example.com", "password123").await;
+    let pending = reenroll(&app).await;
+
+    let response = app
+        .post_confirm_totp(&json!({
+            "code": pending.generate_code(now()),
+            "currentCode": secret.generate_code(now() + TOTP_STEP_SECONDS),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let user = app
+        .user_store
+        .get_user(&"sample@example.com".parse().unwrap())
+        .await
+        .unwrap();
+    assert!(matches!(
+        user.two_factor,
+        Some(TwoFactorMethod::Totp { secret: ref enrolled, .. }) if *enrolled == pending
+    ));
+}
+
+#[tokio::test]
+async fn confirm_should_replace_totp_with_recovery_code() {
+    let app = TestApp::new().await;
+    let (_, recovery_codes) = setup_totp_user(&app, "sample@example.com", "password123").await;
+    let pending = reenroll(&app).await;
+
+    let response = app
+        .post_confirm_totp(&json!({
+            "code": pending.generate_code(now()),
+            "currentCode": recovery_codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn confirm_should_return_400_if_not_enrolling() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+
+    let response = app.post_confirm_totp(&json!({ "code": "123456" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn confirm_should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+    app.post_enroll_totp().await.error_for_status().unwrap();
+
+    let response = app.post_confirm_totp(&json!({ "totp": now() })).await;
+
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[tokio::test]
+async fn regenerate_recovery_codes_should_replace_old_codes() {
+    let app = TestApp::new().await;
+    let (secret, old_codes) = setup_totp_user(&app, "sample@example.com", "password123").await;
+    let login_attempt_id = app
+        .post_login(&json!({
+            "email": "sample@example.com",
+            "password": "password123",
+        }))
+        .await
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id;
+    app.post_verify_2fa(&json!({
+        "email": "sample@example.com",
+        "loginAttemptId": login_attempt_id,
+        "2FACode": secret.generate_code(now() + TOTP_STEP_SECONDS),
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    let response = app.post_regenerate_recovery_codes().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let new_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .unwrap()
+        .recovery_codes;
+    assert_eq!(new_codes.len(), RECOVERY_CODE_COUNT);
+
+    let user = app
+        .user_store
+        .get_user(&"sample@example.com".parse().unwrap())
+        .await
+        .unwrap();
+    assert!(
+        new_codes
+            .iter()
+            .all(|code| user.recovery_codes.contains(&RecoveryCodeHash::of(code)))
+    );
+    assert!(
+        old_codes
+            .iter()
+            .all(|code| !user.recovery_codes.contains(&RecoveryCodeHash::of(code)))
+    );
+}
+
+#[tokio::test]
+async fn regenerate_recovery_codes_should_return_400_if_totp_not_enabled() {
+    let app = TestApp::new().await;
+    login_user(&app).await;
+
+    let response = app.post_regenerate_recovery_codes().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn regenerate_recovery_codes_should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_regenerate_recovery_codes().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+async fn reenroll(app: &TestApp) -> TotpSecret {
+    app.post_enroll_totp().await.error_for_status().unwrap();
+    app.user_store
+        .get_user(&"sample@example.com".parse().unwrap())
+        .await
+        .unwrap()
+        .pending_totp
+        .unwrap()
+}
+
+async fn login_user(app: &TestApp) {
+    app.post_signup(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    app.post_login(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+}
@@ -1,10 +1,310 @@
-use crate::helpers::TestApp;
+use auth_service::{
+    domain::{
+        LoginAttemptStore, MAX_SECOND_FACTOR_GUESSES, TOTP_STEP_SECONDS, TotpSecret, UserStore,
+    },
+    routes::TwoFactorAuthResponse,
+    utils::constants::JWT_COOKIE_NAME,
+};
+use serde_json::json;
+
+use crate::helpers::{TestApp, now, setup_totp_user};
+
+#[tokio::test]
+async fn should_return_200_and_set_cookie_if_correct_code() {
+    let app = TestApp::new().await;
+    let (secret, login_attempt_id) = setup_login_attempt(&app).await;
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": "sample@example.com",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": secret.generate_code(now() + TOTP_STEP_SECONDS),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("no cookie found");
+    assert!(!auth_cookie.value().is_empty());
+    assert!(
+        app.login_attempt_store
+            .get_attempt(&"sample@example.com".parse().unwrap())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn should_return_401_if_code_replayed() {
+    let app = TestApp::new().await;
+    let (secret, login_attempt_id) = setup_login_attempt(&app).await;
+
+    // the code used to confirm enrollment can't be used again
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": "sample@example.com",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": secret.generate_code(now()),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let code = secret.generate_code(now() + TOTP_STEP_SECONDS);
+    app.post_verify_2fa(&json!({
+        "email": "sample@example.com",
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    let login_attempt_id = login(&app).await;
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": "sample@example.com",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_200_if_recovery_code_used_once() {
+    let app = TestApp::new().await;
+    let (_, recovery_codes) = setup_totp_user(&app, "sample@example.com", "password123").await;
+    let login_attempt_id = login(&app).await;
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": "sample@example.com",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let user = app
+        .user_store
+        .get_user(&"sample@example.com".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(user.recovery_codes.len(), recovery_codes.len() - 1);
+
+    let login_attempt_id = login(&app).await;
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": "sample@example.com",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_code_outside_window() {
+    let app = TestApp::new().await;
+    let (secret, login_attempt_id) = setup_login_attempt(&app).await;
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": "sample@example.com",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": secret.generate_code(now() + 3 * TOTP_STEP_SECONDS),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_login_attempt_id_incorrect() {
+    let app = TestApp::new().await;
+    let (secret, _) = setup_login_attempt(&app).await;
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": "sample@example.com",
+            "loginAttemptId": "c0ffee00-0000-4000-8000-000000000000",
+            "2FACode": secret.generate_code(now() + TOTP_STEP_SECONDS),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
 
 #[tokio::test]
-async fn verify_2fa_returns_ok() {
+async fn should_return_401_once_guesses_used_up() {
     let app = TestApp::new().await;
+    let (secret, login_attempt_id) = setup_login_attempt(&app).await;
 
-    let response = app.post_verify_2fa().await;
+    for _ in 0..MAX_SECOND_FACTOR_GUESSES {
+        let response = app
+            .post_verify_2fa(&json!({
+                "email": "sample@example.com",
+                "loginAttemptId": login_attempt_id,
+                "2FACode": "000000",
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // the right code is no use once the attempt is gone
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": "sample@example.com",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": secret.generate_code(now() + TOTP_STEP_SECONDS),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_200_if_correct_email_code() {
+    let app = TestApp::new().await;
+    let login_attempt_id = setup_email_login_attempt(&app).await;
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": "sample@example.com",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": emailed_code(&app),
+        }))
+        .await;
 
     assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response
+            .cookies()
+            .any(|cookie| cookie.name() == JWT_COOKIE_NAME)
+    );
+}
+
+#[tokio::test]
+async fn should_return_401_if_email_code_incorrect() {
+    let app = TestApp::new().await;
+    let login_attempt_id = setup_email_login_attempt(&app).await;
+    let code = emailed_code(&app);
+    let wrong_code = if code == "000000" { "000001" } else { "000000" };
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": "sample@example.com",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": wrong_code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let app = TestApp::new().await;
+
+    let test_cases = [
+        json!({
+            "email": "not an email",
+            "loginAttemptId": "c0ffee00-0000-4000-8000-000000000000",
+            "2FACode": "123456",
+        }),
+        json!({
+            "email": "sample@example.com",
+            "loginAttemptId": "not a uuid",
+            "2FACode": "123456",
+        }),
+    ];
+
+    for test_case in &test_cases {
+        let response = app.post_verify_2fa(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+
+    let test_cases = [
+        json!({
+            "email": "sample@example.com",
+            "loginAttemptId": "c0ffee00-0000-4000-8000-000000000000",
+        }),
+        json!({
+            "loginAttemptId": "c0ffee00-0000-4000-8000-000000000000",
+            "2FACode": "123456",
+        }),
+    ];
+
+    for test_case in &test_cases {
+        let response = app.post_verify_2fa(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+async fn setup_login_attempt(app: &TestApp) -> (TotpSecret, String) {
+    let (secret, _) = setup_totp_user(app, "sample@example.com", "password123").await;
+    let login_attempt_id = login(app).await;
+
+    (secret, login_attempt_id)
+}
+
+async fn setup_email_login_attempt(app: &TestApp) -> String {
+    app.post_signup(&json!({
+        "email": "sample@example.com",
+        "password": "password123",
+        "requires2FA": true
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    login(app).await
+}
+
+fn emailed_code(app: &TestApp) -> String {
+    let sent = app.email_client.sent_emails();
+    let content = &sent.last().expect("no login code sent").content;
+    content
+        .split_whitespace()
+        .find(|word| word.len() == 6 && word.chars().all(|c| c.is_ascii_digit()))
+        .expect("no code in email")
+        .to_string()
+}
+
+async fn login(app: &TestApp) -> String {
+    let response = app
+        .post_login(&json!({
+            "email": "sample@example.com",
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id
 }
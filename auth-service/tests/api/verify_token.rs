@@ -1,7 +1,11 @@
-use auth_service::utils::constants::JWT_COOKIE_NAME;
+use auth_service::{
+    domain::{SCOPE_TOKENS_REVOKE, TwoFactorKind},
+    routes::VerifyTokenResponse,
+    utils::constants::JWT_COOKIE_NAME,
+};
 use serde_json::json;
 
-use crate::helpers::TestApp;
+use crate::helpers::{TestApp, service_token, setup_totp_user};
 
 #[tokio::test]
 async fn should_return_200_valid_token() {
@@ -37,6 +41,58 @@ async fn should_return_200_valid_token() {
         .await;
 
     assert_eq!(verify_response.status().as_u16(), 200);
+    let body = verify_response.json::<VerifyTokenResponse>().await.unwrap();
+    assert_eq!(body.subject, "hello@world.com");
+    assert!(body.expires_at > 0);
+    assert!(body.roles.is_empty());
+    assert!(!body.requires_2fa);
+    assert_eq!(body.two_factor_method, None);
+}
+
+#[tokio::test]
+async fn should_return_2fa_status() {
+    let app = TestApp::new().await;
+    setup_totp_user(&app, "hello@world.com", "password123").await;
+    let token = app.auth_token().expect("no jwt cookie after enrolling");
+
+    let body = app
+        .post_verify_token(&json!({ "token": token }))
+        .await
+        .json::<VerifyTokenResponse>()
+        .await
+        .unwrap();
+
+    assert!(body.requires_2fa);
+    assert_eq!(body.two_factor_method, Some(TwoFactorKind::Totp));
+}
+
+#[tokio::test]
+async fn should_return_401_if_account_deleted() {
+    let app = TestApp::new().await;
+    app.post_signup(&json!({
+        "email": "hello@world.com",
+        "password": "password123",
+        "requires2FA": false,
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    let token = app
+        .post_login(&json!({
+            "email": "hello@world.com",
+            "password": "password123"
+        }))
+        .await
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("no jwt cookie found in /login route")
+        .value()
+        .to_string();
+    app.delete_account().await.error_for_status().unwrap();
+
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
 }
 
 #[tokio::test]
@@ -67,3 +123,57 @@ async fn should_return_401_if_invalid_token() {
 
     assert_eq!(response.status().as_u16(), 401);
 }
+
+#[tokio::test]
+async fn should_return_401_if_caller_anonymous() {
+    let app = TestApp::new().await;
+    let token = login(&app).await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/verify-token", app.address))
+        .json(&json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_403_if_caller_lacks_scope() {
+    let app = TestApp::new().await;
+    let token = login(&app).await;
+    let service_token = service_token(&app, &[SCOPE_TOKENS_REVOKE]).await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/verify-token", app.address))
+        .bearer_auth(service_token)
+        .json(&json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+async fn login(app: &TestApp) -> String {
+    app.post_signup(&json!({
+        "email": "hello@world.com",
+        "password": "password123",
+        "requires2FA": false,
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.post_login(&json!({
+        "email": "hello@world.com",
+        "password": "password123"
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    app.auth_token().expect("no jwt cookie after logging in")
+}